@@ -97,4 +97,31 @@ export const parseAuthCookie = async function (setCookie: string[] = [], cookies
         console.log(e)
         return
     }
-}
\ No newline at end of file
+}
+
+// CSRF header the API requires on cookie-authenticated POST/PUT/DELETE requests
+export const csrfHeaders = (): Record<string, string> => {
+    const cookies = cookie.parse(document.cookie);
+    const token = cookies['__Host-csrf_token'] ?? cookies['csrf_token'];
+    return token ? { 'X-CSRF-Token': token } : {};
+};
+
+// Editorial fields stored with each post and blog:index entry
+export interface PublishState {
+    visibility: string;
+    status?: string; // Absent on records from before editorial statuses, which were live
+    publish_at?: string | null;
+    unpublish_at?: string | null;
+}
+
+// Published and inside the publish window, as the API's is_live
+export const isPublished = (post: PublishState, now = new Date()): boolean => {
+    const passed = (at?: string | null) => !!at && new Date(at).getTime() <= now.getTime();
+    return (post.status ?? 'published') === 'published'
+        && (!post.publish_at || passed(post.publish_at))
+        && !passed(post.unpublish_at);
+};
+
+// Public and published: what the public blog may show
+export const isLive = (post: PublishState, now = new Date()): boolean =>
+    post.visibility === 'public' && isPublished(post, now);
@@ -1,4 +1,5 @@
 import type { PageServerLoad } from "./$types";
+import { isLive } from "$lib/utils";
 
 interface BlogIndexEntry {
   slug: string;
@@ -8,8 +9,28 @@ interface BlogIndexEntry {
   date_published: string;
   tags: string[];
   visibility: string;
+  status?: string;
+  publish_at?: string | null;
+  unpublish_at?: string | null;
 }
 
+interface BlogIndexPage {
+  posts: BlogIndexEntry[];
+  page: number;
+  per_page: number;
+  total: number;
+  total_pages: number;
+  next: string | null;
+  prev: string | null;
+}
+
+const PAGE_SIZE = 10;
+
+// Tags match by slug, as in the API ("Sports Day" finds "sports-day")
+const tagSlug = (tag: string) =>
+  tag.normalize('NFKD').replace(/[\u0300-\u036f]/g, '').toLowerCase()
+    .split(/[^a-z0-9]+/).filter(Boolean).join('-');
+
 export const load: PageServerLoad = async ({ platform, url, setHeaders }) => {
   try {
     // Set cache headers for public blog index
@@ -17,29 +38,39 @@ export const load: PageServerLoad = async ({ platform, url, setHeaders }) => {
       'Cache-Control': 'public, max-age=60, s-maxage=3600'
     });
 
+    const currentPage = Math.max(1, Number(url.searchParams.get('page')) || 1);
+    const currentTag = url.searchParams.get('tag') || "";
+
     // Try to get data from KV if available (production)
     if (platform?.env?.BLOG_KV) {
       const blogIndexData = await platform.env.BLOG_KV.get('blog:index');
       if (blogIndexData) {
         const blogIndex: BlogIndexEntry[] = JSON.parse(blogIndexData);
         
-        // Filter only public posts
-        const publicBlogs = blogIndex.filter(blog => blog.visibility === 'public');
+        // Only live posts: public, published and inside their window
+        const now = new Date();
+        const publicBlogs = blogIndex.filter(blog => isLive(blog, now));
         
         // Get all unique tags
         const allTags = [...new Set(publicBlogs.flatMap(blog => blog.tags))];
+
+        // Only the requested page goes to the page, as with the API
+        const tagged = currentTag
+          ? publicBlogs.filter(blog => blog.tags.some(tag => tagSlug(tag) === tagSlug(currentTag)))
+          : publicBlogs;
+        const start = (currentPage - 1) * PAGE_SIZE;
         
         return {
-          blogs: publicBlogs,
+          blogs: tagged.slice(start, start + PAGE_SIZE),
           pagination: {
-            currentPage: 1,
-            totalBlogs: publicBlogs.length,
-            hasMore: false,
-            limit: 50,
+            currentPage,
+            totalBlogs: tagged.length,
+            hasMore: start + PAGE_SIZE < tagged.length,
+            limit: PAGE_SIZE,
           },
           allTags,
           currentSearch: "",
-          currentTag: "",
+          currentTag,
         };
       }
     }
@@ -47,27 +78,35 @@ export const load: PageServerLoad = async ({ platform, url, setHeaders }) => {
     // Fallback: try to fetch from CMS API
     const cmsApiUrl = platform?.env?.CMS_API_URL || 'http://localhost:3001';
     try {
-      const response = await fetch(`${cmsApiUrl}/api/blog/index`, {
+      // Only the requested page is loaded; the API filters and paginates
+      const params = new URLSearchParams({
+        page: String(currentPage),
+        per_page: String(PAGE_SIZE),
+      });
+      if (currentTag) {
+        params.set('tag', currentTag);
+      }
+      const response = await fetch(`${cmsApiUrl}/api/blog/index?${params}`, {
         headers: {
           'Accept': 'application/json',
         },
       });
       
       if (response.ok) {
-        const blogIndex: BlogIndexEntry[] = await response.json();
-        const allTags = [...new Set(blogIndex.flatMap(blog => blog.tags))];
+        const blogIndex: BlogIndexPage = await response.json();
+        const allTags = [...new Set(blogIndex.posts.flatMap(blog => blog.tags))];
         
         return {
-          blogs: blogIndex,
+          blogs: blogIndex.posts,
           pagination: {
-            currentPage: 1,
-            totalBlogs: blogIndex.length,
-            hasMore: false,
-            limit: 50,
+            currentPage: blogIndex.page,
+            totalBlogs: blogIndex.total,
+            hasMore: blogIndex.next !== null,
+            limit: blogIndex.per_page,
           },
           allTags,
           currentSearch: "",
-          currentTag: "",
+          currentTag,
         };
       }
     } catch (apiError) {
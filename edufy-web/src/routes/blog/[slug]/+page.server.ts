@@ -1,5 +1,6 @@
 import type { PageServerLoad } from "./$types";
-import { error } from "@sveltejs/kit";
+import { error, redirect } from "@sveltejs/kit";
+import { isLive, isPublished } from "$lib/utils";
 
 interface BlogPost {
   id: string;
@@ -11,6 +12,9 @@ interface BlogPost {
   tags: string[];
   date_published: string;
   visibility: string;
+  status?: string;
+  publish_at?: string | null;
+  unpublish_at?: string | null;
   cover_image?: string;
   attachments: string[];
   meta?: any;
@@ -27,8 +31,10 @@ export const load: PageServerLoad = async ({ params, platform, setHeaders, local
       if (blogPostData) {
         const blogPost: BlogPost = JSON.parse(blogPostData);
         
-        // Check if post is private and user is not authenticated
-        if (blogPost.visibility === 'private' && !locals.user) {
+        // Drafts, scheduled and expired posts are never served; private
+        // ones only to signed-in users
+        const visible = locals.user ? isPublished(blogPost) : isLive(blogPost);
+        if (!visible) {
           throw error(404, 'Blog post not found');
         }
 
@@ -79,10 +85,17 @@ export const load: PageServerLoad = async ({ params, platform, setHeaders, local
           };
         }
       } else {
-        // For public posts only
+        // For public posts only. Renamed posts answer their old slug with a
+        // 301 whose body names the current slug; send the reader there too
         const response = await fetch(`${cmsApiUrl}/api/blog/post/${slug}`, {
           headers,
+          redirect: 'manual',
         });
+
+        if (response.status === 301) {
+          const moved: { slug: string } = await response.json();
+          throw redirect(301, '/blog/' + moved.slug);
+        }
         
         if (response.ok) {
           const blogPost: BlogPost = await response.json();
@@ -1,17 +1,32 @@
 import { error } from '@sveltejs/kit';
 import type { ServerLoad } from '@sveltejs/kit';
 
-export const load: ServerLoad = async ({ locals, platform, fetch }) => {
+const PAGE_SIZE = 20;
+
+const emptyPage = (currentPage: number) => ({
+  currentPage,
+  totalPages: 0,
+  totalPosts: 0,
+  hasMore: false,
+});
+
+export const load: ServerLoad = async ({ locals, platform, fetch, url }) => {
   // Check if user is admin (already verified in hooks)
   if (!locals.user || locals.user.role !== 'admin') {
     throw error(403, 'Access denied');
   }
 
+  const currentPage = Math.max(1, Number(url.searchParams.get('page')) || 1);
+
   try {
-    // Fetch all posts (including private) from CMS API
+    // One page of posts (including private) from CMS API
     const cmsApiUrl = platform?.env?.CMS_API_URL || 'http://localhost:8080';
+    const params = new URLSearchParams({
+      page: String(currentPage),
+      per_page: String(PAGE_SIZE),
+    });
     
-    const response = await fetch(`${cmsApiUrl}/api/admin/posts`, {
+    const response = await fetch(`${cmsApiUrl}/api/admin/posts?${params}`, {
       headers: {
         'Cookie': `session=${locals.token || ''}`,
         'Accept': 'application/json',
@@ -19,15 +34,22 @@ export const load: ServerLoad = async ({ locals, platform, fetch }) => {
     });
 
     if (response.ok) {
-      const posts = await response.json();
+      const { posts, page, total, total_pages, next } = await response.json();
       return {
         posts,
+        pagination: {
+          currentPage: page,
+          totalPages: total_pages,
+          totalPosts: total,
+          hasMore: next !== null,
+        },
         user: locals.user
       };
     } else {
       console.error('Failed to fetch posts:', response.status);
       return {
         posts: [],
+        pagination: emptyPage(currentPage),
         user: locals.user
       };
     }
@@ -35,6 +57,7 @@ export const load: ServerLoad = async ({ locals, platform, fetch }) => {
     console.error('Error fetching posts:', err);
     return {
       posts: [],
+      pagination: emptyPage(currentPage),
       user: locals.user
     };
   }
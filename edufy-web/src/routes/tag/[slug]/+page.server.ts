@@ -0,0 +1,80 @@
+import { error } from "@sveltejs/kit";
+import type { PageServerLoad } from "./$types";
+
+interface BlogIndexEntry {
+  slug: string;
+  title: string;
+  summary: string;
+  cover_image?: string;
+  date_published: string;
+  tags: string[];
+  visibility: string;
+}
+
+interface Tag {
+  slug: string;
+  name: string;
+  description: string | null;
+  post_count: number;
+}
+
+interface TagPage {
+  tag: Tag;
+  posts: BlogIndexEntry[];
+  page: number;
+  per_page: number;
+  total: number;
+  total_pages: number;
+  next: string | null;
+  prev: string | null;
+}
+
+const PAGE_SIZE = 10;
+
+export const prerender = false;
+
+export const load: PageServerLoad = async ({ platform, params, url, setHeaders }) => {
+  const cmsApiUrl = platform?.env?.CMS_API_URL || 'http://localhost:3001';
+  const currentPage = Math.max(1, Number(url.searchParams.get('page')) || 1);
+  const query = new URLSearchParams({
+    page: String(currentPage),
+    per_page: String(PAGE_SIZE),
+  });
+  const headers = { 'Accept': 'application/json' };
+
+  const [tagResponse, tagsResponse, recentResponse] = await Promise.all([
+    fetch(`${cmsApiUrl}/api/blog/tags/${encodeURIComponent(params.slug)}?${query}`, { headers }),
+    fetch(`${cmsApiUrl}/api/blog/tags`, { headers }),
+    fetch(`${cmsApiUrl}/api/blog/index?per_page=5`, { headers }),
+  ]);
+
+  if (tagResponse.status === 404) {
+    throw error(404, 'Tag not found');
+  }
+  if (!tagResponse.ok) {
+    throw error(502, 'Failed to load tag');
+  }
+
+  const tagPage: TagPage = await tagResponse.json();
+  const allTags: Tag[] = tagsResponse.ok ? await tagsResponse.json() : [];
+  const recentPosts: BlogIndexEntry[] = recentResponse.ok
+    ? (await recentResponse.json()).posts
+    : [];
+
+  setHeaders({
+    'Cache-Control': 'public, max-age=60, s-maxage=3600'
+  });
+
+  return {
+    tag: tagPage.tag,
+    posts: tagPage.posts,
+    pagination: {
+      currentPage: tagPage.page,
+      totalPages: tagPage.total_pages,
+      totalPosts: tagPage.total,
+      hasMore: tagPage.next !== null,
+    },
+    allTags,
+    recentPosts,
+  };
+};
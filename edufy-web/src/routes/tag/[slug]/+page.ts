@@ -1,8 +0,0 @@
-import { redirect } from "@sveltejs/kit";
-import type { PageLoad } from "./$types";
-
-export const prerender = false;
-
-export const load: PageLoad = async () => {
-  throw redirect(302, "/");
-};
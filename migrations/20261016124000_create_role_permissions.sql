@@ -0,0 +1,19 @@
+-- Role -> permission mapping checked by `require_permission` route layers.
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role TEXT NOT NULL,
+    permission TEXT NOT NULL,
+    PRIMARY KEY (role, permission)
+);
+
+-- Admins can do everything; teachers can author posts and upload media
+INSERT OR IGNORE INTO role_permissions (role, permission) VALUES
+    ('admin', 'posts:write'),
+    ('admin', 'posts:publish'),
+    ('admin', 'media:upload'),
+    ('admin', 'audit:read'),
+    ('admin', 'audit:manage'),
+    ('admin', 'backup:restore'),
+    ('admin', 'users:read'),
+    ('admin', 'users:manage'),
+    ('teacher', 'posts:write'),
+    ('teacher', 'media:upload');
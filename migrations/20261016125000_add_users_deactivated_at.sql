@@ -0,0 +1,2 @@
+-- Deactivated users keep their data but can no longer sign in.
+ALTER TABLE users ADD COLUMN deactivated_at TIMESTAMP;
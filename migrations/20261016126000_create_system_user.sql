@@ -0,0 +1,4 @@
+-- Actor for audit entries that have no signed-in user (e.g. rejected sign-ups).
+-- Deactivated so it can never sign in.
+INSERT OR IGNORE INTO users (id, email, role, google_id, full_name, created_at, deactivated_at)
+VALUES ('system', 'system@localhost', 'student', NULL, 'System', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP);
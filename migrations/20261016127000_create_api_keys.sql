@@ -0,0 +1,17 @@
+-- Admin-issued API keys for server-to-server clients. The secret part of the
+-- key is only stored as a bcrypt hash; `key_prefix` locates the row.
+CREATE TABLE IF NOT EXISTS api_keys (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    key_prefix TEXT NOT NULL UNIQUE,
+    key_hash TEXT NOT NULL,
+    scopes TEXT NOT NULL DEFAULT '[]', -- JSON array of permissions
+    created_by TEXT NOT NULL, -- Owner; requests made with the key act as this user
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP,
+    last_used_at TIMESTAMP,
+    revoked_at TIMESTAMP,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE CASCADE
+);
+
+INSERT OR IGNORE INTO role_permissions (role, permission) VALUES ('admin', 'api_keys:manage');
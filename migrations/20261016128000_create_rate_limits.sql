@@ -0,0 +1,9 @@
+-- Shared rate-limit counters for the authentication endpoints, used when
+-- several instances run with rate_limit_backend = "sqlite". One fixed window
+-- per key ("ip:<addr>" or "identity:<email>").
+CREATE TABLE IF NOT EXISTS rate_limits (
+    key TEXT PRIMARY KEY,
+    window_start INTEGER NOT NULL, -- Unix timestamp the current window began
+    hits INTEGER NOT NULL,
+    locked_until INTEGER NOT NULL DEFAULT 0 -- Unix timestamp; 0 when not locked out
+);
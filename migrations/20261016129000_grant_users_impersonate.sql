@@ -0,0 +1,2 @@
+-- Admins may view the portal as another user through an impersonation token.
+INSERT OR IGNORE INTO role_permissions (role, permission) VALUES ('admin', 'users:impersonate');
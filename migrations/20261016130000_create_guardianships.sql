@@ -0,0 +1,18 @@
+-- Links parents to the students they are responsible for. A parent can have
+-- many students and a student several guardians; at most one of a student's
+-- guardians is the primary contact.
+CREATE TABLE IF NOT EXISTS guardianships (
+    guardian_id TEXT NOT NULL,
+    student_id TEXT NOT NULL,
+    relationship TEXT NOT NULL, -- mother, father, guardian, grandparent, sibling, other
+    is_primary_contact BOOLEAN NOT NULL DEFAULT FALSE,
+    created_by TEXT NOT NULL, -- Admin who made the link
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (guardian_id, student_id),
+    FOREIGN KEY (guardian_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (student_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_guardianships_student_id ON guardianships(student_id);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_guardianships_primary_contact
+    ON guardianships(student_id) WHERE is_primary_contact;
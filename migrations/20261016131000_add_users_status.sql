@@ -0,0 +1,6 @@
+-- Suspension locks an account out without deleting it (which would cascade
+-- its audit history away). Pending accounts await approval.
+ALTER TABLE users ADD COLUMN status TEXT NOT NULL DEFAULT 'active'
+    CHECK(status IN ('active', 'suspended', 'pending'));
+ALTER TABLE users ADD COLUMN status_reason TEXT;
+ALTER TABLE users ADD COLUMN status_changed_at TIMESTAMP;
@@ -0,0 +1,21 @@
+-- Accounts at OIDC providers linked to users, keyed by the provider's
+-- subject so a changed email doesn't orphan the link
+CREATE TABLE IF NOT EXISTS user_identities (
+    provider TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    email TEXT, -- As last reported by the provider
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_login_at TIMESTAMP,
+    PRIMARY KEY (provider, subject),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_identities_user_id ON user_identities(user_id);
+
+-- Existing Google links become identities
+INSERT OR IGNORE INTO user_identities (provider, subject, user_id, email, created_at)
+SELECT 'google', google_id, id, email, created_at FROM users WHERE google_id IS NOT NULL;
+
+-- The provider an in-flight login was started for
+ALTER TABLE oauth_states ADD COLUMN provider TEXT NOT NULL DEFAULT 'google';
@@ -0,0 +1,15 @@
+-- Every saved version of a blog post, numbered per post. The payload is the
+-- full `blog:post:{slug}` JSON as written to KV.
+CREATE TABLE IF NOT EXISTS post_revisions (
+    id TEXT PRIMARY KEY,
+    post_id TEXT NOT NULL, -- BlogPostKv.id, stable across slug changes
+    revision INTEGER NOT NULL,
+    slug TEXT NOT NULL,
+    author_id TEXT NOT NULL, -- Kept without a foreign key so history outlives users
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    restored_from INTEGER, -- Revision this one restored, if any
+    payload TEXT NOT NULL,
+    UNIQUE (post_id, revision)
+);
+
+CREATE INDEX IF NOT EXISTS idx_post_revisions_slug ON post_revisions(slug);
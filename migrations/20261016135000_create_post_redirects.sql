@@ -0,0 +1,11 @@
+-- Slugs a post was previously published under. Public lookups of an old
+-- slug answer with a 301 to the post's current slug.
+CREATE TABLE IF NOT EXISTS post_redirects (
+    old_slug TEXT PRIMARY KEY,
+    new_slug TEXT NOT NULL,
+    post_id TEXT NOT NULL, -- BlogPostKv.id
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_post_redirects_post_id ON post_redirects(post_id);
@@ -0,0 +1,10 @@
+-- Tag registry. Posts store tag names; a tag's slug is its slugified name.
+-- Tags are registered when a post using them is saved, and renamed or
+-- merged across all posts by admins. Post counts come from the blog index.
+CREATE TABLE IF NOT EXISTS tags (
+    slug TEXT PRIMARY KEY,
+    name TEXT NOT NULL, -- Display name written into posts
+    description TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
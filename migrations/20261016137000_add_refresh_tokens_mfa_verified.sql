@@ -0,0 +1,3 @@
+-- Whether the login a refresh token family descends from passed a second
+-- factor. Families without it stop refreshing once the user must use TOTP.
+ALTER TABLE refresh_tokens ADD COLUMN mfa_verified BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- Set when a signed-in user started the flow to link a provider account;
+-- such a state can only finish that link, never a login
+ALTER TABLE oauth_states ADD COLUMN link_user_id TEXT;
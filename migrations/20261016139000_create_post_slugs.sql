@@ -0,0 +1,10 @@
+-- Slugs claimed by posts. KV has no conditional put, so two posts saved at
+-- once claim their slug here first and the loser takes the next number.
+-- A post keeps its old slugs (which redirect to it) until it is deleted.
+CREATE TABLE IF NOT EXISTS post_slugs (
+    slug TEXT PRIMARY KEY,
+    post_id TEXT NOT NULL, -- BlogPostKv.id
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_post_slugs_post_id ON post_slugs(post_id);
@@ -0,0 +1,10 @@
+-- Slug changes that have started but not finished. A move spans several KV
+-- writes, so it is recorded here first and finished when the same change
+-- is retried or at startup.
+CREATE TABLE IF NOT EXISTS post_slug_moves (
+    post_id TEXT PRIMARY KEY, -- BlogPostKv.id
+    old_slug TEXT NOT NULL,
+    new_slug TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
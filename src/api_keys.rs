@@ -0,0 +1,258 @@
+use crate::audit::AuditService;
+use crate::error::{AppError, AppResult};
+use crate::models::{ApiKey, CreateApiKeyRequest, Permission, User, UserResponse};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::Utc;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, Instant};
+
+/// Every key starts with this so leaked keys are easy to recognise and grep for
+const API_KEY_PREFIX: &str = "lla";
+
+/// Keys carry 256 random bits, so a lower cost than for passwords is enough
+const API_KEY_BCRYPT_COST: u32 = 10;
+
+/// Synthetic role reported for requests authenticated with an API key
+pub const API_KEY_ROLE: &str = "api_key";
+
+/// Minimum gap between `last_used_at` writes for the same key
+const LAST_USED_INTERVAL_SECONDS: i64 = 60;
+
+/// How long a successful bcrypt check is remembered for the same key
+const VERIFIED_KEY_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// SHA-256 of recently verified keys -> key id, so each request does not pay
+/// for bcrypt. Revocation and expiry are still read from the table every time.
+static VERIFIED_KEYS: LazyLock<RwLock<HashMap<String, (String, Instant)>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Service for admin-managed API keys used by server-to-server clients
+pub struct ApiKeyService {
+    pub db: SqlitePool,
+    pub audit: AuditService,
+}
+
+impl ApiKeyService {
+    pub fn new(db: SqlitePool) -> Self {
+        let audit = AuditService::new(db.clone());
+        Self { db, audit }
+    }
+
+    /// Create a key owned by `owner`. Returns the plaintext key, which is
+    /// never stored and cannot be shown again.
+    pub async fn create_key(
+        &self,
+        payload: CreateApiKeyRequest,
+        owner: &UserResponse,
+        owner_permissions: &[Permission],
+    ) -> AppResult<(String, ApiKey)> {
+        let name = payload.name.trim().to_string();
+        if name.is_empty() {
+            return Err(AppError::Validation("API key name is required".to_string()));
+        }
+        if payload.scopes.is_empty() {
+            return Err(AppError::Validation("API key needs at least one scope".to_string()));
+        }
+        if payload.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Err(AppError::Validation("API key expiry must be in the future".to_string()));
+        }
+
+        let mut scopes = Vec::new();
+        for scope in &payload.scopes {
+            let permission = Permission::parse(scope)
+                .ok_or_else(|| AppError::Validation(format!("Unknown permission: {}", scope)))?;
+            // A key can never do more than the admin who created it
+            if !owner_permissions.contains(&permission) {
+                return Err(AppError::MissingPermission(scope.clone()));
+            }
+            if !scopes.contains(&permission) {
+                scopes.push(permission);
+            }
+        }
+
+        let mut prefix_bytes = [0u8; 6];
+        rand::thread_rng().fill_bytes(&mut prefix_bytes);
+        let mut secret_bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret_bytes);
+
+        let key_prefix = hex::encode(prefix_bytes);
+        let secret = URL_SAFE_NO_PAD.encode(secret_bytes);
+        let key = format!("{}_{}_{}", API_KEY_PREFIX, key_prefix, secret);
+
+        let api_key = ApiKey::new(
+            name,
+            key_prefix,
+            bcrypt::hash(&secret, API_KEY_BCRYPT_COST)?,
+            &scopes,
+            owner.id.clone(),
+            payload.expires_at,
+        );
+
+        sqlx::query("INSERT INTO api_keys (id, name, key_prefix, key_hash, scopes, created_by, created_at, expires_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)")
+            .bind(&api_key.id)
+            .bind(&api_key.name)
+            .bind(&api_key.key_prefix)
+            .bind(&api_key.key_hash)
+            .bind(&api_key.scopes)
+            .bind(&api_key.created_by)
+            .bind(api_key.created_at)
+            .bind(api_key.expires_at)
+            .execute(&self.db)
+            .await?;
+
+        self.audit
+            .log_action(
+                &owner.id,
+                "create_api_key".to_string(),
+                Some(api_key.id.clone()),
+                Some(serde_json::json!({
+                    "name": api_key.name,
+                    "scopes": scopes,
+                    "expires_at": api_key.expires_at,
+                })),
+            )
+            .await?;
+
+        Ok((key, api_key))
+    }
+
+    pub async fn list_keys(&self) -> AppResult<Vec<ApiKey>> {
+        let keys: Vec<ApiKey> = sqlx::query_as(
+            "SELECT id, name, key_prefix, key_hash, scopes, created_by, created_at, expires_at, last_used_at, revoked_at FROM api_keys ORDER BY created_at DESC",
+        )
+        .fetch_all(&self.db)
+        .await?;
+        Ok(keys)
+    }
+
+    pub async fn revoke_key(&self, key_id: &str, actor_id: &str) -> AppResult<()> {
+        let result = sqlx::query("UPDATE api_keys SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL")
+            .bind(Utc::now())
+            .bind(key_id)
+            .execute(&self.db)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("API key not found".to_string()));
+        }
+
+        self.audit
+            .log_action(actor_id, "revoke_api_key".to_string(), Some(key_id.to_string()), None)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Check a presented key and return the synthetic user it acts as: the
+    /// owner's identity, limited to the key's scopes that the owner's role
+    /// still grants
+    pub async fn authenticate(&self, key: &str) -> AppResult<UserResponse> {
+        let invalid = || AppError::Auth("Invalid API key".to_string());
+
+        let (key_prefix, secret) = key
+            .strip_prefix(API_KEY_PREFIX)
+            .and_then(|rest| rest.strip_prefix('_'))
+            .and_then(|rest| rest.split_once('_'))
+            .ok_or_else(invalid)?;
+
+        let api_key: ApiKey = sqlx::query_as(
+            "SELECT id, name, key_prefix, key_hash, scopes, created_by, created_at, expires_at, last_used_at, revoked_at FROM api_keys WHERE key_prefix = $1",
+        )
+        .bind(key_prefix)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(invalid)?;
+
+        if !self.verify_secret(key, secret, &api_key).await? {
+            return Err(invalid());
+        }
+        if api_key.revoked_at.is_some() {
+            return Err(AppError::Auth("API key has been revoked".to_string()));
+        }
+        if api_key.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Err(AppError::Auth("API key has expired".to_string()));
+        }
+
+        let owner: User = sqlx::query_as(
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE id = $1",
+        )
+        .bind(&api_key.created_by)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(invalid)?;
+
+        if !owner.is_active() {
+            return Err(AppError::Auth("API key owner is not active".to_string()));
+        }
+
+        // Scopes were checked against the owner's role when the key was
+        // created; the role may have lost permissions (or changed) since
+        let owner_permissions: Vec<String> =
+            sqlx::query_scalar("SELECT permission FROM role_permissions WHERE role = $1")
+                .bind(&owner.role)
+                .fetch_all(&self.db)
+                .await?;
+        let permissions = api_key
+            .get_scopes()
+            .into_iter()
+            .filter(|scope| owner_permissions.iter().any(|p| p == scope.as_str()))
+            .collect();
+
+        self.touch_key(&api_key.id).await?;
+
+        Ok(UserResponse {
+            id: owner.id,
+            email: owner.email,
+            role: API_KEY_ROLE.to_string(),
+            full_name: Some(api_key.name.clone()),
+            permissions: Some(permissions),
+        })
+    }
+
+    async fn verify_secret(&self, key: &str, secret: &str, api_key: &ApiKey) -> AppResult<bool> {
+        let fingerprint = hex::encode(Sha256::digest(key.as_bytes()));
+
+        let cached = VERIFIED_KEYS
+            .read()
+            .unwrap()
+            .get(&fingerprint)
+            .is_some_and(|(id, verified_at)| {
+                *id == api_key.id && verified_at.elapsed() < VERIFIED_KEY_TTL
+            });
+        if cached {
+            return Ok(true);
+        }
+
+        // bcrypt is deliberately slow; keep it off the async executor
+        let (secret, key_hash) = (secret.to_string(), api_key.key_hash.clone());
+        let matches = tokio::task::spawn_blocking(move || bcrypt::verify(&secret, &key_hash))
+            .await
+            .map_err(|e| AppError::Internal(format!("API key check failed: {}", e)))??;
+        if !matches {
+            return Ok(false);
+        }
+
+        let mut verified = VERIFIED_KEYS.write().unwrap();
+        verified.retain(|_, (_, verified_at)| verified_at.elapsed() < VERIFIED_KEY_TTL);
+        verified.insert(fingerprint, (api_key.id.clone(), Instant::now()));
+        Ok(true)
+    }
+
+    /// Bump `last_used_at`, at most once per `LAST_USED_INTERVAL_SECONDS`
+    async fn touch_key(&self, key_id: &str) -> AppResult<()> {
+        let now = Utc::now();
+        sqlx::query(
+            "UPDATE api_keys SET last_used_at = $1 WHERE id = $2 AND (last_used_at IS NULL OR last_used_at < $3)",
+        )
+        .bind(now)
+        .bind(key_id)
+        .bind(now - chrono::Duration::seconds(LAST_USED_INTERVAL_SECONDS))
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+}
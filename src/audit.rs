@@ -4,6 +4,9 @@ use chrono::{DateTime, Datelike, TimeZone, Utc};
 use serde_json::Value;
 use sqlx::SqlitePool;
 
+/// Audit actor for events without a signed-in user; seeded by migration
+pub const SYSTEM_USER_ID: &str = "system";
+
 /// Service for managing incremental JSON audit logging with monthly sharding
 pub struct AuditService {
     pub db: SqlitePool,
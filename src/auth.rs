@@ -1,93 +1,482 @@
+use crate::audit::{AuditService, SYSTEM_USER_ID};
 use crate::config::AppConfig;
+use crate::cookies::CookiePolicy;
 use crate::error::{AppError, AppResult};
+use crate::keyring::Keyring;
+use crate::mail::{MailMessage, MailSender};
 use crate::models::{
-    Claims, GoogleAuthRequest, LoginRequest, LoginResponse, Revocation, User, UserResponse, UserRole,
+    ActorClaim, Claims, ClientInfo, IdTokenClaims, ImpersonationResponse, LoginOutcome,
+    LoginRequest, LoginResponse, MagicLinkToken, MfaChallenge, MfaChallengeResponse,
+    MfaLoginResponse, OAuthState, OidcAuthRequest, OidcAuthStartResponse, OidcTokenResponse,
+    OidcUserInfo, Permission, TotpEnrollmentResponse,
+    RefreshToken, Revocation, Session, SessionResponse, User, UserResponse, UserRole,
+    ACCESS_TOKEN_TYPE,
 };
-use chrono::Utc;
+use crate::oidc::{OidcProvider, OidcRegistry, ProviderMetadata, GOOGLE_PROVIDER};
+use crate::rate_limit::AuthRateLimiter;
+use crate::revocation::REVOCATION_CACHE;
+use crate::signup::SignupPolicy;
+use crate::totp::{constant_time_eq, TotpService};
+use chrono::{DateTime, Utc};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use oauth2::{CsrfToken, PkceCodeChallenge};
+use rand::RngCore;
+use ring::hmac;
+use sha2::{Digest, Sha256};
 use sqlx::SqlitePool;
+use std::sync::Arc;
 use uuid::Uuid;
 
+/// Header cookie-authenticated unsafe requests must echo the CSRF token in
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Wrong codes allowed against one login challenge before it is void
+const MAX_MFA_ATTEMPTS: i64 = 5;
+
+/// Minimum gap between `last_seen_at` writes for the same session
+const SESSION_TOUCH_INTERVAL_SECONDS: i64 = 60;
+
+/// Random URL-safe token for one-time links; only its hash is ever stored
+pub(crate) fn generate_secret_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub(crate) fn hash_secret_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// Failures are only logged: the requester gets the same answer either way
+async fn deliver_magic_link(mailer: &dyn MailSender, message: &MailMessage) {
+    if let Err(e) = mailer.send(message).await {
+        tracing::error!("Failed to send magic link email: {}", e);
+    }
+}
+
 pub struct AuthService {
     pub db: SqlitePool,
     pub config: AppConfig,
+    pub client: ClientInfo, // Recorded on sessions issued by this service
 }
 
 impl AuthService {
     pub fn new(db: SqlitePool, config: AppConfig) -> Self {
-        Self { db, config }
+        Self {
+            db,
+            config,
+            client: ClientInfo::default(),
+        }
+    }
+
+    /// Attach the requesting client's details to sessions issued from here on
+    pub fn with_client_info(mut self, client: ClientInfo) -> Self {
+        self.client = client;
+        self
     }
 
-    /// Create JWT token with revocation support
+    /// Create a short-lived access JWT with revocation support
     pub async fn create_jwt_token(&self, user_id: &str) -> AppResult<String> {
+        let (token, _) = self.encode_access_token(user_id)?;
+        Ok(token)
+    }
+
+    /// Sign a new access JWT and return it with its claims
+    fn encode_access_token(&self, user_id: &str) -> AppResult<(String, Claims)> {
+        self.sign_access_token(user_id, self.access_token_ttl(), None)
+    }
+
+    fn sign_access_token(
+        &self,
+        user_id: &str,
+        ttl: chrono::Duration,
+        act: Option<ActorClaim>,
+    ) -> AppResult<(String, Claims)> {
         let jti = Uuid::new_v4().to_string();
         let now = Utc::now();
-        let exp = now + chrono::Duration::days(7);
+        let exp = now + ttl;
 
         let claims = Claims {
             sub: user_id.to_string(),
             exp: exp.timestamp() as usize,
             jti: jti.clone(),
             iat: now.timestamp() as usize,
+            typ: ACCESS_TOKEN_TYPE.to_string(),
+            act,
         };
 
-        let token = jsonwebtoken::encode(
-            &jsonwebtoken::Header::default(),
-            &claims,
-            &jsonwebtoken::EncodingKey::from_secret(self.config.jwt_secret.as_ref()),
-        )?;
+        let token = Keyring::for_config(&self.config)?.encode(&claims)?;
 
-        Ok(token)
+        Ok((token, claims))
     }
 
     /// Verify JWT token and check revocation list
     pub async fn verify_jwt_token(&self, token: &str) -> AppResult<Claims> {
         // Decode JWT token
-        let token_data = jsonwebtoken::decode::<Claims>(
-            token,
-            &jsonwebtoken::DecodingKey::from_secret(self.config.jwt_secret.as_ref()),
-            &jsonwebtoken::Validation::default(),
-        )
-        .map_err(|_| AppError::Auth("Invalid token".to_string()))?;
+        let token_data = Keyring::for_config(&self.config)?
+            .decode::<Claims>(token)
+            .map_err(|_| AppError::Auth("Invalid token".to_string()))?;
 
-        // Check if token is revoked
-        let revoked: Option<String> =
-            sqlx::query_scalar("SELECT jti FROM revocations WHERE jti = $1")
-                .bind(&token_data.claims.jti)
-                .fetch_optional(&self.db)
-                .await?;
+        // Only access tokens may authenticate requests
+        if token_data.claims.typ != ACCESS_TOKEN_TYPE {
+            return Err(AppError::Auth("Invalid token type".to_string()));
+        }
 
-        if revoked.is_some() {
+        // Check if token is revoked, asking the table only on a cache miss
+        let jti = &token_data.claims.jti;
+        let revoked = match REVOCATION_CACHE.get(jti) {
+            Some(revoked) => revoked,
+            None => {
+                let row: Option<String> =
+                    sqlx::query_scalar("SELECT jti FROM revocations WHERE jti = $1")
+                        .bind(jti)
+                        .fetch_optional(&self.db)
+                        .await?;
+
+                let exp = token_data.claims.exp as i64;
+                match row {
+                    Some(_) => REVOCATION_CACHE.mark_revoked(jti, exp),
+                    None => REVOCATION_CACHE.mark_active(jti, exp),
+                }
+                row.is_some()
+            }
+        };
+
+        if revoked {
             return Err(AppError::Auth("Token has been revoked".to_string()));
         }
 
         Ok(token_data.claims)
     }
 
+    fn access_token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.config.access_token_ttl_minutes as i64)
+    }
+
+    /// Store a new hashed refresh token in the given family
+    async fn create_refresh_token(
+        &self,
+        user_id: &str,
+        family_id: &str,
+        mfa_verified: bool,
+    ) -> AppResult<String> {
+        let token = generate_secret_token();
+        let refresh_token = RefreshToken::new(
+            user_id.to_string(),
+            family_id.to_string(),
+            hash_secret_token(&token),
+            self.config.refresh_token_ttl_days as i64,
+            mfa_verified,
+        );
+
+        sqlx::query("INSERT INTO refresh_tokens (id, user_id, family_id, token_hash, created_at, expires_at, mfa_verified) VALUES ($1, $2, $3, $4, $5, $6, $7)")
+            .bind(&refresh_token.id)
+            .bind(&refresh_token.user_id)
+            .bind(&refresh_token.family_id)
+            .bind(&refresh_token.token_hash)
+            .bind(refresh_token.created_at)
+            .bind(refresh_token.expires_at)
+            .bind(refresh_token.mfa_verified)
+            .execute(&self.db)
+            .await?;
+
+        Ok(token)
+    }
+
+    async fn get_refresh_token(&self, token: &str) -> AppResult<Option<RefreshToken>> {
+        let refresh_token: Option<RefreshToken> = sqlx::query_as(
+            "SELECT id, user_id, family_id, token_hash, created_at, expires_at, rotated_at, revoked_at, mfa_verified FROM refresh_tokens WHERE token_hash = $1",
+        )
+        .bind(hash_secret_token(token))
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(refresh_token)
+    }
+
+    /// Exchange a refresh token for a new access token and a rotated refresh token.
+    /// Presenting a token that was already rotated or revoked revokes its whole family.
+    pub async fn refresh_session(&self, refresh_token: &str) -> AppResult<(LoginResponse, String)> {
+        let stored = self
+            .get_refresh_token(refresh_token)
+            .await?
+            .ok_or_else(|| AppError::Auth("Invalid refresh token".to_string()))?;
+
+        if stored.rotated_at.is_some() || stored.revoked_at.is_some() {
+            tracing::warn!(
+                "Refresh token reuse detected for user {}, revoking family {}",
+                stored.user_id,
+                stored.family_id
+            );
+            self.revoke_session_family(&stored.family_id).await?;
+            return Err(AppError::Auth("Refresh token reuse detected".to_string()));
+        }
+
+        let now = Utc::now();
+        if stored.expires_at < now {
+            return Err(AppError::Auth("Refresh token has expired".to_string()));
+        }
+
+        // Claim the token; losing this race means it was used concurrently
+        let result = sqlx::query(
+            "UPDATE refresh_tokens SET rotated_at = $1 WHERE id = $2 AND rotated_at IS NULL AND revoked_at IS NULL",
+        )
+        .bind(now)
+        .bind(&stored.id)
+        .execute(&self.db)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            self.revoke_session_family(&stored.family_id).await?;
+            return Err(AppError::Auth("Refresh token reuse detected".to_string()));
+        }
+
+        let user: Option<User> = sqlx::query_as(
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE id = $1",
+        )
+        .bind(&stored.user_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let user = user.ok_or_else(|| AppError::Auth("User not found".to_string()))?;
+
+        // A login that skipped the second factor ends once the user needs one
+        if !stored.mfa_verified {
+            let totp = TotpService::new(self.db.clone(), self.config.clone());
+            if totp.is_enabled(&user.id).await? || totp.is_required_for(user.get_role()) {
+                self.revoke_session_family(&stored.family_id).await?;
+                return Err(AppError::Auth(
+                    "Sign in again with your second factor".to_string(),
+                ));
+            }
+        }
+
+        self.issue_session(user, Some(stored.family_id), stored.mfa_verified)
+            .await
+    }
+
+    /// Revoke a whole login: every refresh token descended from it and every
+    /// access token issued to it that has not expired yet
+    pub async fn revoke_session_family(&self, family_id: &str) -> AppResult<()> {
+        let now = Utc::now();
+
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = $1 WHERE family_id = $2 AND revoked_at IS NULL",
+        )
+        .bind(now)
+        .bind(family_id)
+        .execute(&self.db)
+        .await?;
+
+        let live_sessions: Vec<Session> = sqlx::query_as(
+            "SELECT jti, user_id, family_id, user_agent, ip_address, created_at, last_seen_at, expires_at, revoked_at FROM sessions WHERE family_id = $1 AND created_at > $2",
+        )
+        .bind(family_id)
+        .bind(now - self.access_token_ttl())
+        .fetch_all(&self.db)
+        .await?;
+
+        for session in live_sessions {
+            let expires_at = session.created_at + self.access_token_ttl();
+            self.revoke_jti(&session.jti, Some(&session.user_id), Some(expires_at))
+                .await?;
+        }
+
+        sqlx::query("UPDATE sessions SET revoked_at = $1 WHERE family_id = $2 AND revoked_at IS NULL")
+            .bind(now)
+            .bind(family_id)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
     /// Revoke a JWT token
     pub async fn revoke_token(&self, token: &str) -> AppResult<()> {
         let claims = self.verify_jwt_token(token).await?;
+        let expires_at = DateTime::from_timestamp(claims.exp as i64, 0).unwrap_or(Utc::now());
 
-        let revocation = Revocation::new(
-            claims.jti,
-            Some(claims.sub),
-            Some(chrono::DateTime::from_timestamp(claims.exp as i64, 0).unwrap_or(Utc::now())),
-        );
+        self.revoke_jti(&claims.jti, Some(&claims.sub), Some(expires_at))
+            .await?;
+
+        sqlx::query("UPDATE sessions SET revoked_at = $1 WHERE jti = $2 AND revoked_at IS NULL")
+            .bind(Utc::now())
+            .bind(&claims.jti)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Add a token ID to the revocation list; revoking twice is a no-op
+    async fn revoke_jti(
+        &self,
+        jti: &str,
+        user_id: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<()> {
+        let revocation = Revocation::new(jti.to_string(), user_id.map(str::to_string), expires_at);
 
-        sqlx::query("INSERT INTO revocations (jti, user_id, revoked_at, expires_at) VALUES ($1, $2, $3, $4)")
+        sqlx::query("INSERT OR IGNORE INTO revocations (jti, user_id, revoked_at, expires_at) VALUES ($1, $2, $3, $4)")
             .bind(&revocation.jti)
             .bind(&revocation.user_id)
-            .bind(&revocation.revoked_at)
-            .bind(&revocation.expires_at)
+            .bind(revocation.revoked_at)
+            .bind(revocation.expires_at)
             .execute(&self.db)
             .await?;
 
+        let exp = revocation.expires_at.unwrap_or(revocation.revoked_at).timestamp();
+        REVOCATION_CACHE.mark_revoked(jti, exp);
+
+        Ok(())
+    }
+
+    /// Record an issued access token against its login
+    async fn record_session(&self, claims: &Claims, family_id: &str) -> AppResult<()> {
+        let session = Session::new(
+            claims.jti.clone(),
+            claims.sub.clone(),
+            family_id.to_string(),
+            &self.client,
+            Utc::now() + chrono::Duration::days(self.config.refresh_token_ttl_days as i64),
+        );
+
+        sqlx::query("INSERT INTO sessions (jti, user_id, family_id, user_agent, ip_address, created_at, last_seen_at, expires_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)")
+            .bind(&session.jti)
+            .bind(&session.user_id)
+            .bind(&session.family_id)
+            .bind(&session.user_agent)
+            .bind(&session.ip_address)
+            .bind(session.created_at)
+            .bind(session.last_seen_at)
+            .bind(session.expires_at)
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_session(&self, jti: &str) -> AppResult<Option<Session>> {
+        let session: Option<Session> = sqlx::query_as(
+            "SELECT jti, user_id, family_id, user_agent, ip_address, created_at, last_seen_at, expires_at, revoked_at FROM sessions WHERE jti = $1",
+        )
+        .bind(jti)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(session)
+    }
+
+    /// Bump `last_seen_at`, at most once per `SESSION_TOUCH_INTERVAL_SECONDS`
+    async fn touch_session(&self, jti: &str) -> AppResult<()> {
+        let now = Utc::now();
+        sqlx::query("UPDATE sessions SET last_seen_at = $1 WHERE jti = $2 AND last_seen_at < $3")
+            .bind(now)
+            .bind(jti)
+            .bind(now - chrono::Duration::seconds(SESSION_TOUCH_INTERVAL_SECONDS))
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// List a user's live logins, newest activity first. Each login is shown
+    /// once, under the jti of its most recent access token.
+    pub async fn list_sessions(
+        &self,
+        user_id: &str,
+        current_jti: Option<&str>,
+    ) -> AppResult<Vec<SessionResponse>> {
+        let sessions: Vec<SessionResponse> = sqlx::query_as(
+            r#"
+            SELECT jti, user_agent, ip_address, first_created_at AS created_at, last_seen_at, expires_at,
+                   family_id = COALESCE((SELECT family_id FROM sessions WHERE jti = $3), '') AS current
+            FROM (
+                SELECT s.*,
+                       MIN(created_at) OVER (PARTITION BY family_id) AS first_created_at,
+                       ROW_NUMBER() OVER (PARTITION BY family_id ORDER BY created_at DESC) AS rn
+                FROM sessions s
+                WHERE user_id = $1
+            )
+            WHERE rn = 1 AND revoked_at IS NULL AND expires_at > $2
+            ORDER BY last_seen_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .bind(Utc::now())
+        .bind(current_jti)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(sessions)
+    }
+
+    /// Revoke one of the user's logins by any jti issued to it
+    pub async fn revoke_session(&self, user_id: &str, jti: &str) -> AppResult<()> {
+        let session = self
+            .get_session(jti)
+            .await?
+            .filter(|session| session.user_id == user_id)
+            .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+
+        self.revoke_session_family(&session.family_id).await
+    }
+
+    /// Sign a user out everywhere; returns how many logins were revoked
+    pub async fn revoke_all_sessions(&self, user_id: &str) -> AppResult<usize> {
+        let families: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT family_id FROM sessions WHERE user_id = $1 AND revoked_at IS NULL
+             UNION
+             SELECT DISTINCT family_id FROM refresh_tokens WHERE user_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        for family_id in &families {
+            self.revoke_session_family(family_id).await?;
+        }
+
+        Ok(families.len())
+    }
+
+    /// Once TOTP is turned on, end the user's logins that did not pass it.
+    /// The login of `keep_jti`, where the code was just entered, is kept and
+    /// counts as verified from now on.
+    pub async fn end_sessions_without_mfa(&self, user_id: &str, keep_jti: Option<&str>) -> AppResult<()> {
+        let keep_family = match keep_jti {
+            Some(jti) => self
+                .get_session(jti)
+                .await?
+                .filter(|session| session.user_id == user_id)
+                .map(|session| session.family_id),
+            None => None,
+        };
+
+        let families: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT family_id FROM sessions WHERE user_id = $1 AND revoked_at IS NULL
+             UNION
+             SELECT DISTINCT family_id FROM refresh_tokens WHERE user_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(user_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        for family_id in &families {
+            if keep_family.as_ref() != Some(family_id) {
+                self.revoke_session_family(family_id).await?;
+            }
+        }
+
+        if let Some(family_id) = keep_family {
+            sqlx::query("UPDATE refresh_tokens SET mfa_verified = 1 WHERE family_id = $1")
+                .bind(&family_id)
+                .execute(&self.db)
+                .await?;
+        }
         Ok(())
     }
 
     /// Get user by email (for authentication)
     pub async fn get_user_by_email(&self, email: &str) -> AppResult<Option<User>> {
         let user: Option<User> = sqlx::query_as(
-            "SELECT id, email, role, google_id, full_name, created_at FROM users WHERE email = $1",
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE email = $1",
         )
         .bind(email)
         .fetch_optional(&self.db)
@@ -98,7 +487,7 @@ impl AuthService {
     /// Get user by Google ID
     pub async fn get_user_by_google_id(&self, google_id: &str) -> AppResult<Option<User>> {
         let user: Option<User> = sqlx::query_as(
-            "SELECT id, email, role, google_id, full_name, created_at FROM users WHERE google_id = $1"
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE google_id = $1"
         )
         .bind(google_id)
         .fetch_optional(&self.db)
@@ -106,14 +495,104 @@ impl AuthService {
         Ok(user)
     }
 
-    /// Create a new user with Google authentication
+    /// User linked to an account at an OIDC provider. Google accounts
+    /// linked before `user_identities` existed are found by `google_id`.
+    pub async fn get_user_by_identity(&self, provider: &str, subject: &str) -> AppResult<Option<User>> {
+        let user: Option<User> = sqlx::query_as(
+            "SELECT u.id, u.email, u.role, u.google_id, u.full_name, u.created_at, u.deactivated_at, u.status, u.status_reason, u.status_changed_at
+             FROM user_identities i JOIN users u ON u.id = i.user_id
+             WHERE i.provider = $1 AND i.subject = $2",
+        )
+        .bind(provider)
+        .bind(subject)
+        .fetch_optional(&self.db)
+        .await?;
+
+        if user.is_none() && provider == GOOGLE_PROVIDER {
+            return self.get_user_by_google_id(subject).await;
+        }
+        Ok(user)
+    }
+
+    /// Link a provider account to a user, or record another login with it
+    pub async fn link_identity(
+        &self,
+        user_id: &str,
+        provider: &str,
+        subject: &str,
+        email: Option<&str>,
+    ) -> AppResult<()> {
+        let now = Utc::now();
+        sqlx::query(
+            "INSERT INTO user_identities (provider, subject, user_id, email, created_at, last_login_at)
+             VALUES ($1, $2, $3, $4, $5, $5)
+             ON CONFLICT(provider, subject) DO UPDATE SET email = excluded.email, last_login_at = excluded.last_login_at",
+        )
+        .bind(provider)
+        .bind(subject)
+        .bind(user_id)
+        .bind(email)
+        .bind(now)
+        .execute(&self.db)
+        .await?;
+
+        // Kept in step for code that still reads users.google_id
+        if provider == GOOGLE_PROVIDER {
+            sqlx::query("UPDATE users SET google_id = $1 WHERE id = $2")
+                .bind(subject)
+                .bind(user_id)
+                .execute(&self.db)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Create a new user with Google authentication. The sign-up policy
+    /// decides whether the email may register and with which role.
     pub async fn create_user_with_google(
         &self,
         email: String,
         google_id: String,
         full_name: Option<String>,
     ) -> AppResult<User> {
-        let user = User::new_with_google(email, UserRole::Student, google_id, full_name);
+        self.create_user_with_identity(GOOGLE_PROVIDER, google_id, email, full_name)
+            .await
+    }
+
+    /// Create a new user signing in through an OIDC provider. The sign-up
+    /// policy decides whether the email may register and with which role.
+    pub async fn create_user_with_identity(
+        &self,
+        provider: &str,
+        subject: String,
+        email: String,
+        full_name: Option<String>,
+    ) -> AppResult<User> {
+        let role = match SignupPolicy::from_config(&self.config)?.role_for(&email) {
+            Ok(role) => role,
+            Err(e) => {
+                tracing::warn!("Rejected {} sign-up for {}: {}", provider, email, e);
+                AuditService::new(self.db.clone())
+                    .log_action(
+                        SYSTEM_USER_ID,
+                        "signup_rejected".to_string(),
+                        Some(email.clone()),
+                        Some(serde_json::json!({
+                            "email": email,
+                            "provider": provider,
+                            "reason": e.to_string(),
+                        })),
+                    )
+                    .await?;
+                return Err(e);
+            }
+        };
+
+        let user = if provider == GOOGLE_PROVIDER {
+            User::new_with_google(email, role, subject.clone(), full_name)
+        } else {
+            User::new(email, role, full_name)
+        };
 
         sqlx::query("INSERT INTO users (id, email, role, google_id, full_name, created_at) VALUES ($1, $2, $3, $4, $5, $6)")
             .bind(&user.id)
@@ -121,20 +600,29 @@ impl AuthService {
             .bind(&user.role)
             .bind(&user.google_id)
             .bind(&user.full_name)
-            .bind(&user.created_at)
+            .bind(user.created_at)
             .execute(&self.db)
             .await?;
+        self.link_identity(&user.id, provider, &subject, Some(&user.email))
+            .await?;
 
         Ok(user)
     }
 
     /// Verify session cookie and return user info
     pub async fn verify_session_cookie(&self, cookie_value: &str) -> AppResult<UserResponse> {
-        let claims = self.verify_jwt_token(cookie_value).await?;
+        let (_, user) = self.authenticate(cookie_value).await?;
+        Ok(user)
+    }
+
+    /// Verify an access token, mark its session as seen and load its user
+    pub async fn authenticate(&self, token: &str) -> AppResult<(Claims, UserResponse)> {
+        let claims = self.verify_jwt_token(token).await?;
+        self.touch_session(&claims.jti).await?;
 
         // Get user details
         let user: Option<User> = sqlx::query_as(
-            "SELECT id, email, role, google_id, full_name, created_at FROM users WHERE id = $1",
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE id = $1",
         )
         .bind(&claims.sub)
         .fetch_optional(&self.db)
@@ -147,17 +635,137 @@ impl AuthService {
             return Err(AppError::Auth("Invalid user role".to_string()));
         }
 
-        Ok(UserResponse {
+        if let Some(reason) = user.inactive_reason() {
+            return Err(AppError::Auth(reason.to_string()));
+        }
+
+        let user = UserResponse {
             id: user.id,
             email: user.email,
             role: user.role,
             full_name: user.full_name,
+            permissions: None,
+        };
+
+        Ok((claims, user))
+    }
+    /// Issue a time-boxed, bearer-only token that authenticates as `user_id`
+    /// with `actor` in its `act` claim. It is not recorded as a session of
+    /// the user and cannot be refreshed.
+    pub async fn impersonate(
+        &self,
+        actor: &UserResponse,
+        user_id: &str,
+        reason: Option<String>,
+    ) -> AppResult<ImpersonationResponse> {
+        if actor.id == user_id {
+            return Err(AppError::Validation("You cannot impersonate yourself".to_string()));
+        }
+
+        let user: User = sqlx::query_as(
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("User not found: {}", user_id)))?;
+
+        if !user.is_active() {
+            return Err(AppError::Validation("Inactive users cannot be impersonated".to_string()));
+        }
+        // Viewing as another admin would hand over permissions the actor may lack
+        if user.get_role() == Some(UserRole::Admin) {
+            return Err(AppError::Validation("Administrators cannot be impersonated".to_string()));
+        }
+
+        let ttl = chrono::Duration::minutes(self.config.impersonation_ttl_minutes as i64);
+        let act = ActorClaim {
+            sub: actor.id.clone(),
+        };
+        let (token, claims) = self.sign_access_token(&user.id, ttl, Some(act))?;
+
+        AuditService::new(self.db.clone())
+            .log_action(
+                &actor.id,
+                "impersonation_started".to_string(),
+                Some(user.id.clone()),
+                Some(serde_json::json!({
+                    "jti": claims.jti,
+                    "role": user.role,
+                    "expires_at": claims.exp,
+                    "reason": reason,
+                })),
+            )
+            .await?;
+
+        Ok(ImpersonationResponse {
+            token,
+            expires_at: claims.exp,
+            user: UserResponse {
+                id: user.id,
+                email: user.email,
+                role: user.role,
+                full_name: user.full_name,
+                permissions: None,
+            },
+            actor: actor.clone(),
         })
     }
+
+    /// Revoke an impersonation token before it runs out
+    pub async fn end_impersonation(&self, claims: &Claims) -> AppResult<()> {
+        let Some(act) = &claims.act else {
+            return Err(AppError::Validation("Not impersonating".to_string()));
+        };
+
+        let expires_at = DateTime::from_timestamp(claims.exp as i64, 0);
+        self.revoke_jti(&claims.jti, Some(&claims.sub), expires_at)
+            .await?;
+
+        AuditService::new(self.db.clone())
+            .log_action(
+                &act.sub,
+                "impersonation_ended".to_string(),
+                Some(claims.sub.clone()),
+                Some(serde_json::json!({ "jti": claims.jti })),
+            )
+            .await
+    }
+
+    /// The admin behind an impersonation token, or `None` for ordinary tokens.
+    /// The actor must still be active and allowed to impersonate.
+    pub async fn resolve_actor(&self, claims: &Claims) -> AppResult<Option<UserResponse>> {
+        let Some(act) = &claims.act else {
+            return Ok(None);
+        };
+
+        let actor: User = sqlx::query_as(
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE id = $1",
+        )
+        .bind(&act.sub)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| AppError::Auth("Impersonating user not found".to_string()))?;
+
+        if !actor.is_active() {
+            return Err(AppError::Auth("Impersonating account is not active".to_string()));
+        }
+        self.ensure_permission(&actor.role, Permission::UsersImpersonate)
+            .await?;
+
+        Ok(Some(UserResponse {
+            id: actor.id,
+            email: actor.email,
+            role: actor.role,
+            full_name: actor.full_name,
+            permissions: None,
+        }))
+    }
+
     /// Check if user has specific role
     pub async fn user_has_role(&self, user_id: &str, required_role: UserRole) -> AppResult<bool> {
         let user: Option<User> = sqlx::query_as(
-            "SELECT id, email, role, google_id, full_name, created_at FROM users WHERE id = $1",
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE id = $1",
         )
         .bind(user_id)
         .fetch_optional(&self.db)
@@ -172,139 +780,920 @@ impl AuthService {
         Ok(false)
     }
 
+    /// Permissions granted to a role by the `role_permissions` table
+    pub async fn role_permissions(&self, role: &UserRole) -> AppResult<Vec<Permission>> {
+        let rows: Vec<String> =
+            sqlx::query_scalar("SELECT permission FROM role_permissions WHERE role = $1 ORDER BY permission")
+                .bind(role.as_str())
+                .fetch_all(&self.db)
+                .await?;
+
+        // Ignore rows for permissions this build no longer knows about
+        Ok(rows.iter().filter_map(|p| Permission::parse(p)).collect())
+    }
+
+    /// Replace the permissions granted to a role
+    pub async fn set_role_permissions(
+        &self,
+        role: &UserRole,
+        permissions: &[Permission],
+    ) -> AppResult<()> {
+        // Keep at least one way back in: admins must be able to manage users
+        if *role == UserRole::Admin && !permissions.contains(&Permission::UsersManage) {
+            return Err(AppError::Validation(
+                "The admin role must keep the users:manage permission".to_string(),
+            ));
+        }
+
+        let mut tx = self.db.begin().await?;
+
+        sqlx::query("DELETE FROM role_permissions WHERE role = $1")
+            .bind(role.as_str())
+            .execute(&mut *tx)
+            .await?;
+
+        for permission in permissions {
+            sqlx::query("INSERT OR IGNORE INTO role_permissions (role, permission) VALUES ($1, $2)")
+                .bind(role.as_str())
+                .bind(permission.as_str())
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Check whether a role (as stored on the user) grants a permission
+    pub async fn role_has_permission(&self, role: &str, permission: Permission) -> AppResult<bool> {
+        let granted: Option<i64> = sqlx::query_scalar(
+            "SELECT 1 FROM role_permissions WHERE role = $1 AND permission = $2",
+        )
+        .bind(role)
+        .bind(permission.as_str())
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(granted.is_some())
+    }
+
+    /// Fail with `MissingPermission` unless the role grants the permission
+    pub async fn ensure_permission(&self, role: &str, permission: Permission) -> AppResult<()> {
+        if self.role_has_permission(role, permission).await? {
+            Ok(())
+        } else {
+            Err(AppError::MissingPermission(permission.as_str().to_string()))
+        }
+    }
+
+    /// Everything a user may do: an API key's scopes, otherwise its role's permissions
+    pub async fn user_permissions(&self, user: &UserResponse) -> AppResult<Vec<Permission>> {
+        match (&user.permissions, UserRole::from_str(&user.role)) {
+            (Some(scopes), _) => Ok(scopes.clone()),
+            (None, Some(role)) => self.role_permissions(&role).await,
+            (None, None) => Ok(Vec::new()),
+        }
+    }
+
+    /// Like `ensure_permission`, but API-key users are limited to their scopes
+    pub async fn ensure_user_permission(
+        &self,
+        user: &UserResponse,
+        permission: Permission,
+    ) -> AppResult<()> {
+        let granted = match &user.permissions {
+            Some(scopes) => scopes.contains(&permission),
+            None => self.role_has_permission(&user.role, permission).await?,
+        };
+
+        if granted {
+            Ok(())
+        } else {
+            Err(AppError::MissingPermission(permission.as_str().to_string()))
+        }
+    }
+
     /// Parse role from string and validate
     pub fn parse_user_role(&self, role_str: &str) -> AppResult<UserRole> {
         UserRole::from_str(role_str)
             .ok_or_else(|| AppError::Auth(format!("Invalid user role: {}", role_str)))
     }
 
-    /// Create cookie string for JWT token
-    pub fn create_cookie_string(&self, token: &str) -> String {
-        if self.config.environment == "development" {
-            format!(
-                "session={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
-                token,
-                7 * 24 * 60 * 60 // 7 days in seconds
-            )
-        } else {
-            format!(
-                "session={}; Domain=.llacademy.ng; Path=/; Secure; HttpOnly; SameSite=None; Max-Age={}",
-                token,
-                7 * 24 * 60 * 60 // 7 days in seconds
-            )
+    /// Names and attributes of the session cookies
+    pub fn cookie_policy(&self) -> AppResult<CookiePolicy> {
+        CookiePolicy::from_config(&self.config)
+    }
+
+    /// Create cookie string for the access JWT
+    pub fn create_cookie_string(&self, token: &str) -> AppResult<String> {
+        Ok(self.cookie_policy()?.session_cookie(token))
+    }
+
+    /// Create cookie string for the refresh token, scoped to the auth endpoints
+    pub fn create_refresh_cookie_string(&self, token: &str) -> AppResult<String> {
+        Ok(self.cookie_policy()?.refresh_cookie(token))
+    }
+
+    /// Create cookie string for the CSRF token, readable by the frontend
+    pub fn create_csrf_cookie_string(&self, csrf_token: &str) -> AppResult<String> {
+        Ok(self.cookie_policy()?.csrf_cookie(csrf_token))
+    }
+
+    /// Create cookie string binding an OAuth state to this browser; only the
+    /// state's hash is stored in it
+    pub fn create_oauth_state_cookie_string(&self, state: &str) -> AppResult<String> {
+        Ok(self.cookie_policy()?.oauth_state_cookie(
+            &hash_secret_token(state),
+            self.config.oauth_state_ttl_seconds as i64,
+        ))
+    }
+
+    /// Create cookie string clearing the OAuth state cookie
+    pub fn create_clear_oauth_state_cookie_string(&self) -> AppResult<String> {
+        Ok(self.cookie_policy()?.clear_oauth_state_cookie())
+    }
+
+    /// Create cookie string clearing the CSRF token cookie
+    pub fn create_logout_csrf_cookie_string(&self) -> AppResult<String> {
+        Ok(self.cookie_policy()?.clear_csrf_cookie())
+    }
+
+    /// CSRF token for an access token: an HMAC of its `jti`, so it needs no
+    /// storage and changes whenever the access token is reissued
+    pub fn csrf_token(&self, jti: &str) -> String {
+        let tag = hmac::sign(&self.csrf_key(), jti.as_bytes());
+        URL_SAFE_NO_PAD.encode(tag.as_ref())
+    }
+
+    /// Check a CSRF token sent with a cookie-authenticated request
+    pub fn verify_csrf_token(&self, jti: &str, csrf_token: &str) -> AppResult<()> {
+        let invalid = || AppError::Csrf("CSRF token missing or invalid".to_string());
+        let tag = URL_SAFE_NO_PAD
+            .decode(csrf_token.trim())
+            .map_err(|_| invalid())?;
+        hmac::verify(&self.csrf_key(), jti.as_bytes(), &tag).map_err(|_| invalid())
+    }
+
+    /// Check the CSRF cookie against the copy echoed in `X-CSRF-Token`, for
+    /// cookie-authenticated requests that have no live access token to bind to
+    pub fn verify_csrf_echo(&self, cookie_token: &str, csrf_token: &str) -> AppResult<()> {
+        let cookie_token = cookie_token.trim();
+        if cookie_token.is_empty() || !constant_time_eq(cookie_token, csrf_token.trim()) {
+            return Err(AppError::Csrf("CSRF token missing or invalid".to_string()));
         }
+        Ok(())
+    }
+
+    fn csrf_key(&self) -> hmac::Key {
+        // Domain-separated from the HS256 JWT key made from the same secret
+        let secret = format!("csrf:{}", self.config.jwt_secret);
+        hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes())
     }
 
     /// Create cookie string for logout (clears the cookie)
-    pub fn create_logout_cookie_string(&self) -> String {
-        if self.config.environment == "development" {
-            "session=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0".to_string()
-        } else {
-            "session=; Domain=.llacademy.ng; Path=/; Secure; HttpOnly; SameSite=None; Max-Age=0"
-                .to_string()
-        }
+    pub fn create_logout_cookie_string(&self) -> AppResult<String> {
+        Ok(self.cookie_policy()?.clear_session_cookie())
     }
 
-    /// Logout by revoking the token
+    /// Create cookie string clearing the refresh token cookie
+    pub fn create_logout_refresh_cookie_string(&self) -> AppResult<String> {
+        Ok(self.cookie_policy()?.clear_refresh_cookie())
+    }
+
+    /// Logout by revoking the token and the login it belongs to
     pub async fn logout_with_cookie(&self, cookie_value: &str) -> AppResult<String> {
-        // Revoke the token
-        if let Err(_) = self.revoke_token(cookie_value).await {
-            // If token is already invalid, that's fine for logout
+        // If token is already invalid, that's fine for logout
+        if let Ok(claims) = self.verify_jwt_token(cookie_value).await {
+            match self.get_session(&claims.jti).await? {
+                Some(session) => self.revoke_session_family(&session.family_id).await?,
+                None => self.revoke_token(cookie_value).await?,
+            }
         }
 
         // Return cookie clearing string
-        Ok(self.create_logout_cookie_string())
+        self.create_logout_cookie_string()
+    }
+
+    /// Logout the refresh side of a session by revoking its token family
+    pub async fn logout_with_refresh_token(&self, refresh_token: &str) -> AppResult<String> {
+        if let Some(stored) = self.get_refresh_token(refresh_token).await? {
+            self.revoke_session_family(&stored.family_id).await?;
+        }
+
+        self.create_logout_refresh_cookie_string()
+    }
+
+    /// Start a Google login; see `start_oidc_login`
+    pub async fn start_google_oauth(&self) -> AppResult<OidcAuthStartResponse> {
+        self.start_oidc_login(GOOGLE_PROVIDER).await
+    }
+
+    /// Start a login with an OIDC provider: store state, nonce and PKCE
+    /// verifier server-side and return the provider's authorization URL to
+    /// redirect the browser to
+    pub async fn start_oidc_login(&self, provider_name: &str) -> AppResult<OidcAuthStartResponse> {
+        self.start_oidc_flow(provider_name, None).await
+    }
+
+    /// Start linking a provider account to a signed-in user; finished by
+    /// `link_oidc_identity`
+    pub async fn start_identity_link(
+        &self,
+        user_id: &str,
+        provider_name: &str,
+    ) -> AppResult<OidcAuthStartResponse> {
+        self.start_oidc_flow(provider_name, Some(user_id)).await
+    }
+
+    async fn start_oidc_flow(
+        &self,
+        provider_name: &str,
+        link_user_id: Option<&str>,
+    ) -> AppResult<OidcAuthStartResponse> {
+        let registry = OidcRegistry::from_config(&self.config)?;
+        let provider = registry.get(provider_name)?;
+        let metadata = provider.metadata().await?;
+
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let oauth_state = OAuthState::new(
+            CsrfToken::new_random().secret().clone(),
+            provider.name.clone(),
+            CsrfToken::new_random().secret().clone(),
+            pkce_verifier.secret().clone(),
+            self.config.oauth_state_ttl_seconds as i64,
+            link_user_id.map(str::to_string),
+        );
+
+        // Drop states that can no longer be used before adding a new one
+        sqlx::query("DELETE FROM oauth_states WHERE expires_at < $1")
+            .bind(Utc::now())
+            .execute(&self.db)
+            .await?;
+
+        sqlx::query("INSERT INTO oauth_states (state, provider, nonce, code_verifier, created_at, expires_at, link_user_id) VALUES ($1, $2, $3, $4, $5, $6, $7)")
+            .bind(&oauth_state.state)
+            .bind(&oauth_state.provider)
+            .bind(&oauth_state.nonce)
+            .bind(&oauth_state.code_verifier)
+            .bind(oauth_state.created_at)
+            .bind(oauth_state.expires_at)
+            .bind(&oauth_state.link_user_id)
+            .execute(&self.db)
+            .await?;
+
+        let authorization_url = url::Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("client_id", provider.client_id.as_str()),
+                ("redirect_uri", provider.redirect_uri.as_str()),
+                ("response_type", "code"),
+                ("scope", provider.scopes.as_str()),
+                ("state", oauth_state.state.as_str()),
+                ("nonce", oauth_state.nonce.as_str()),
+                ("code_challenge", pkce_challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| {
+            AppError::Internal(format!("Invalid {} authorize endpoint: {}", provider.name, e))
+        })?;
+
+        Ok(OidcAuthStartResponse {
+            provider: provider.name.clone(),
+            authorization_url: authorization_url.to_string(),
+            state: oauth_state.state,
+            expires_at: oauth_state.expires_at.timestamp() as usize,
+        })
+    }
+
+    /// Consume an OAuth state exactly once. Unknown, replayed and expired
+    /// states are all rejected with `AppError::OAuthState`.
+    pub async fn consume_oauth_state(&self, state: &str) -> AppResult<OAuthState> {
+        let now = Utc::now();
+
+        // Mark as consumed first so two concurrent callbacks can't both succeed
+        let result = sqlx::query(
+            "UPDATE oauth_states SET consumed_at = $1 WHERE state = $2 AND consumed_at IS NULL",
+        )
+        .bind(now)
+        .bind(state)
+        .execute(&self.db)
+        .await?;
+
+        let oauth_state: Option<OAuthState> = sqlx::query_as(
+            "SELECT state, provider, nonce, code_verifier, created_at, expires_at, consumed_at, link_user_id FROM oauth_states WHERE state = $1",
+        )
+        .bind(state)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let oauth_state =
+            oauth_state.ok_or_else(|| AppError::OAuthState("Unknown OAuth state".to_string()))?;
+
+        if result.rows_affected() == 0 {
+            tracing::warn!("Replayed OAuth state rejected");
+            return Err(AppError::OAuthState(
+                "OAuth state has already been used".to_string(),
+            ));
+        }
+
+        if oauth_state.expires_at < now {
+            return Err(AppError::OAuthState("OAuth state has expired".to_string()));
+        }
+
+        Ok(oauth_state)
+    }
+
+    /// Google OAuth login; see `oidc_login`
+    pub async fn google_oauth_login(
+        &self,
+        payload: OidcAuthRequest,
+        state_cookie: Option<&str>,
+    ) -> AppResult<LoginOutcome> {
+        self.oidc_login(GOOGLE_PROVIDER, payload, state_cookie).await
     }
 
-    /// Google OAuth login implementation
-    pub async fn google_oauth_login(&self, payload: GoogleAuthRequest) -> AppResult<(LoginResponse, String)> {
+    /// Complete a login with an OIDC provider: exchange the code, verify the
+    /// ID token and find the user by the provider's subject, falling back to
+    /// an email the provider asserts is verified (which links the account)
+    /// and then to sign-up. `state_cookie` is the OAuth state cookie set when
+    /// the login started; it must hold the hash of the returned state.
+    pub async fn oidc_login(
+        &self,
+        provider_name: &str,
+        payload: OidcAuthRequest,
+        state_cookie: Option<&str>,
+    ) -> AppResult<LoginOutcome> {
+        let registry = OidcRegistry::from_config(&self.config)?;
+        let provider = registry.get(provider_name)?;
+        let (oauth_state, profile) = self
+            .finish_oidc_flow(provider, payload, state_cookie)
+            .await?;
+        if oauth_state.link_user_id.is_some() {
+            return Err(AppError::OAuthState(
+                "OAuth state was issued for linking an account".to_string(),
+            ));
+        }
+
+        // Check if user exists by provider subject, then by email
+        let user = match self.get_user_by_identity(&provider.name, &profile.id).await? {
+            Some(user) => user,
+            None => match self.get_user_by_email(&profile.email).await? {
+                // A trusted but unasserted email (Entra's is user-editable)
+                // must not hand over someone else's account
+                Some(existing_user) if profile.email_verified == Some(true) => existing_user,
+                Some(_) => {
+                    return Err(AppError::Conflict(format!(
+                        "An account already uses this email; sign in and link your {} account instead",
+                        provider.name
+                    )));
+                }
+                None => {
+                    return self
+                        .begin_login(
+                            self.create_user_with_identity(
+                                &provider.name,
+                                profile.id,
+                                profile.email,
+                                profile.name,
+                            )
+                            .await?,
+                        )
+                        .await;
+                }
+            },
+        };
+
+        self.link_identity(&user.id, &provider.name, &profile.id, Some(&profile.email))
+            .await?;
+        self.begin_login(user).await
+    }
+
+    /// Finish linking a provider account to the signed-in user who started
+    /// it with `start_identity_link`. The email plays no part; the user has
+    /// proven control of both accounts.
+    pub async fn link_oidc_identity(
+        &self,
+        user_id: &str,
+        provider_name: &str,
+        payload: OidcAuthRequest,
+        state_cookie: Option<&str>,
+    ) -> AppResult<()> {
+        let registry = OidcRegistry::from_config(&self.config)?;
+        let provider = registry.get(provider_name)?;
+        let (oauth_state, profile) = self
+            .finish_oidc_flow(provider, payload, state_cookie)
+            .await?;
+        if oauth_state.link_user_id.as_deref() != Some(user_id) {
+            return Err(AppError::OAuthState(
+                "OAuth state was not issued for linking this account".to_string(),
+            ));
+        }
+
+        if let Some(owner) = self.get_user_by_identity(&provider.name, &profile.id).await?
+            && owner.id != user_id
+        {
+            return Err(AppError::Conflict(format!(
+                "This {} account is linked to another user",
+                provider.name
+            )));
+        }
+
+        self.link_identity(user_id, &provider.name, &profile.id, Some(&profile.email))
+            .await?;
+        AuditService::new(self.db.clone())
+            .log_action(
+                user_id,
+                "identity_linked".to_string(),
+                Some(user_id.to_string()),
+                Some(serde_json::json!({ "provider": provider.name, "subject": profile.id })),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// The callback half shared by logins and links: check the state against
+    /// its cookie, exchange the code and verify the ID token
+    async fn finish_oidc_flow(
+        &self,
+        provider: &OidcProvider,
+        payload: OidcAuthRequest,
+        state_cookie: Option<&str>,
+    ) -> AppResult<(OAuthState, OidcUserInfo)> {
         // Validate the authorization code is present
         if payload.code.is_empty() {
             return Err(AppError::Auth("Authorization code is required".to_string()));
         }
         
-        // Validate state parameter if provided (CSRF protection)
-        if let Some(state) = &payload.state {
-            if state.is_empty() {
-                return Err(AppError::Auth("Invalid state parameter".to_string()));
-            }
-            // In production, verify state matches what was sent to Google
-            tracing::info!("OAuth state parameter received: {}", state);
+        // The state must be one we issued for this provider (CSRF protection)
+        let state = payload
+            .state
+            .as_deref()
+            .filter(|state| !state.is_empty())
+            .ok_or_else(|| AppError::OAuthState("OAuth state is required".to_string()))?;
+
+        // Only the browser that started the login may finish it, so a victim
+        // can't be sent someone else's code and state (login CSRF)
+        let bound = state_cookie
+            .is_some_and(|state_hash| constant_time_eq(state_hash, &hash_secret_token(state)));
+        if !bound {
+            return Err(AppError::OAuthState(
+                "OAuth state was not issued to this browser".to_string(),
+            ));
+        }
+
+        let oauth_state = self.consume_oauth_state(state).await?;
+        if oauth_state.provider != provider.name {
+            return Err(AppError::OAuthState(
+                "OAuth state was issued for another provider".to_string(),
+            ));
         }
         
         // Log the OAuth attempt for audit purposes
-        tracing::info!("Processing Google OAuth login with code: {}", &payload.code[..8.min(payload.code.len())]);
-        
-        // TODO: Implement actual Google OAuth flow
-        // For now, simulate the flow to demonstrate the architecture
-        // In a real implementation, you would:
-        // 1. Verify the authorization code with Google OAuth API
-        // 2. Exchange the code for an access token using payload.code
-        // 3. Get user info from Google using the access token
-        
-        // Simulated Google user info (in real implementation, this comes from Google API)
-        let google_id = format!("google_user_{}", &payload.code[..8.min(payload.code.len())]);
-        let email = "user@example.com";
-        let full_name = Some("Test User".to_string());
-        
-        // Check if user exists by Google ID
-        let user = self.get_user_by_google_id(&google_id).await?;
+        tracing::info!(
+            "Processing {} login with code: {}",
+            provider.name,
+            &payload.code[..8.min(payload.code.len())]
+        );
         
-        let user = if let Some(user) = user {
-            user
+        // Exchange the code, verify the ID token and resolve the profile
+        let metadata = provider.metadata().await?;
+        let tokens = self
+            .exchange_authorization_code(provider, &metadata, &payload.code, &oauth_state.code_verifier)
+            .await?;
+        let claims = self.verify_id_token(provider, &metadata, &tokens.id_token).await?;
+
+        // The ID token must be bound to the login we started
+        if claims.nonce.as_deref() != Some(oauth_state.nonce.as_str()) {
+            return Err(AppError::Auth("ID token nonce mismatch".to_string()));
+        }
+
+        let profile = self
+            .resolve_user_info(provider, &metadata, claims, &tokens.access_token)
+            .await?;
+        Ok((oauth_state, profile))
+    }
+
+    /// Issue a JWT session for an authenticated user
+    pub async fn create_login_session(&self, user: User) -> AppResult<(LoginResponse, String)> {
+        self.issue_session(user, None, false).await
+    }
+
+    /// Finish the first login step: issue the session, or a second-factor
+    /// challenge when the user has TOTP enabled or must enroll in it
+    pub async fn begin_login(&self, user: User) -> AppResult<LoginOutcome> {
+        if let Some(reason) = user.inactive_reason() {
+            return Err(AppError::Auth(reason.to_string()));
+        }
+
+        let totp = TotpService::new(self.db.clone(), self.config.clone());
+        let enrollment_required = if totp.is_enabled(&user.id).await? {
+            false
+        } else if totp.is_required_for(user.get_role()) {
+            true
+        } else {
+            let (response, cookie) = self.create_login_session(user).await?;
+            return Ok(LoginOutcome::Session(response, cookie));
+        };
+
+        let token = generate_secret_token();
+        let challenge = MfaChallenge::new(
+            user.id,
+            hash_secret_token(&token),
+            self.config.mfa_challenge_ttl_minutes as i64,
+        );
+
+        sqlx::query("INSERT INTO mfa_challenges (id, user_id, token_hash, created_at, expires_at, failed_attempts) VALUES ($1, $2, $3, $4, $5, 0)")
+            .bind(&challenge.id)
+            .bind(&challenge.user_id)
+            .bind(&challenge.token_hash)
+            .bind(challenge.created_at)
+            .bind(challenge.expires_at)
+            .execute(&self.db)
+            .await?;
+
+        Ok(LoginOutcome::MfaRequired(MfaChallengeResponse {
+            mfa_required: true,
+            challenge_token: token,
+            enrollment_required,
+            expires_at: challenge.expires_at.timestamp() as usize,
+        }))
+    }
+
+    /// Start TOTP enrollment for a user whose login is waiting on it
+    pub async fn start_challenge_enrollment(
+        &self,
+        totp: &TotpService,
+        challenge_token: &str,
+    ) -> AppResult<TotpEnrollmentResponse> {
+        let (_, user) = self.pending_challenge(challenge_token).await?;
+        totp.start_enrollment(&user.id, &user.email).await
+    }
+
+    /// Second login step: check the code (confirming enrollment if it was
+    /// pending) and issue the session. A challenge is single-use and void
+    /// after `MAX_MFA_ATTEMPTS` wrong codes.
+    pub async fn complete_mfa_login(
+        &self,
+        totp: &TotpService,
+        challenge_token: &str,
+        code: &str,
+    ) -> AppResult<(MfaLoginResponse, String)> {
+        let (challenge, user) = self.pending_challenge(challenge_token).await?;
+
+        // Codes are short, so cap guesses per user across challenges too
+        AuthRateLimiter::new(self.db.clone(), self.config.clone())?
+            .check_identity(&format!("mfa:{}", user.id))
+            .await?;
+
+        let verified = if totp.is_enabled(&user.id).await? {
+            totp.verify(&user.id, code).await.map(|_| None)
         } else {
-            // Check if user exists by email
-            if let Some(existing_user) = self.get_user_by_email(email).await? {
-                // Update existing user with Google ID
-                sqlx::query("UPDATE users SET google_id = ? WHERE id = ?")
-                    .bind(google_id)
-                    .bind(&existing_user.id)
+            totp.confirm_enrollment(&user.id, code).await.map(Some)
+        };
+        let recovery_codes = match verified {
+            Ok(recovery_codes) => recovery_codes,
+            Err(err) => {
+                sqlx::query("UPDATE mfa_challenges SET failed_attempts = failed_attempts + 1 WHERE id = $1")
+                    .bind(&challenge.id)
                     .execute(&self.db)
                     .await?;
-                existing_user
-            } else {
-                // Create new user with Google auth
-                self.create_user_with_google(
-                    email.to_string(),
-                    google_id.to_string(),
-                    full_name,
-                ).await?
+                return Err(err);
             }
         };
-        
-        // Create JWT token
-        let token = self.create_jwt_token(&user.id).await?;
-        let cookie = self.create_cookie_string(&token);
+
+        let consumed: Option<String> = sqlx::query_scalar(
+            "UPDATE mfa_challenges SET consumed_at = $1 WHERE id = $2 AND consumed_at IS NULL RETURNING id",
+        )
+        .bind(Utc::now())
+        .bind(&challenge.id)
+        .fetch_optional(&self.db)
+        .await?;
+        if consumed.is_none() {
+            return Err(AppError::Auth("Invalid or expired login challenge".to_string()));
+        }
+
+        // Enrolling here turns TOTP on, so earlier logins without it end
+        if recovery_codes.is_some() {
+            self.end_sessions_without_mfa(&user.id, None).await?;
+        }
+
+        let (session, cookie) = self.issue_session(user, None, true).await?;
+        Ok((
+            MfaLoginResponse {
+                session,
+                recovery_codes,
+            },
+            cookie,
+        ))
+    }
+
+    /// The unexpired, unused challenge for a token, and its user
+    async fn pending_challenge(&self, challenge_token: &str) -> AppResult<(MfaChallenge, User)> {
+        let challenge: MfaChallenge = sqlx::query_as(
+            "SELECT id, user_id, token_hash, created_at, expires_at, consumed_at, failed_attempts FROM mfa_challenges
+             WHERE token_hash = $1 AND consumed_at IS NULL AND expires_at > $2 AND failed_attempts < $3",
+        )
+        .bind(hash_secret_token(challenge_token))
+        .bind(Utc::now())
+        .bind(MAX_MFA_ATTEMPTS)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| AppError::Auth("Invalid or expired login challenge".to_string()))?;
+
+        let user: User = sqlx::query_as(
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE id = $1",
+        )
+        .bind(&challenge.user_id)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| AppError::Auth("User not found".to_string()))?;
+
+        if let Some(reason) = user.inactive_reason() {
+            return Err(AppError::Auth(reason.to_string()));
+        }
+        Ok((challenge, user))
+    }
+
+    /// Create an access JWT plus a refresh token (in `family_id` when rotating)
+    async fn issue_session(
+        &self,
+        user: User,
+        family_id: Option<String>,
+        mfa_verified: bool,
+    ) -> AppResult<(LoginResponse, String)> {
+        if let Some(reason) = user.inactive_reason() {
+            return Err(AppError::Auth(reason.to_string()));
+        }
+
+        let family_id = family_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        // Create JWT token and record it against the login
+        let (token, claims) = self.encode_access_token(&user.id)?;
+        self.record_session(&claims, &family_id).await?;
+        let refresh_token = self
+            .create_refresh_token(&user.id, &family_id, mfa_verified)
+            .await?;
+        let cookie = self.create_cookie_string(&token)?;
         
         let response = LoginResponse {
             token: token.clone(),
+            refresh_token,
+            csrf_token: self.csrf_token(&claims.jti),
             user: UserResponse {
                 id: user.id,
                 email: user.email,
                 role: user.role,
                 full_name: user.full_name,
+                permissions: None,
             },
-            expires_at: (chrono::Utc::now() + chrono::Duration::days(7)).timestamp() as usize,
+            expires_at: claims.exp, // Same instant as the JWT and cookie expiry
         };
         
         Ok((response, cookie))
     }
 
-    /// Passwordless login method - redirects to Google OAuth
-    pub async fn login(&self, payload: LoginRequest) -> AppResult<(LoginResponse, String)> {
+    /// Exchange an authorization code for tokens at the provider's token endpoint
+    async fn exchange_authorization_code(
+        &self,
+        provider: &OidcProvider,
+        metadata: &ProviderMetadata,
+        code: &str,
+        code_verifier: &str,
+    ) -> AppResult<OidcTokenResponse> {
+        let mut params = vec![
+            ("code", code),
+            ("client_id", provider.client_id.as_str()),
+            ("redirect_uri", provider.redirect_uri.as_str()),
+            ("grant_type", "authorization_code"),
+            ("code_verifier", code_verifier),
+        ];
+        // Public clients rely on PKCE alone
+        if let Some(client_secret) = &provider.client_secret {
+            params.push(("client_secret", client_secret.as_str()));
+        }
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(&metadata.token_endpoint)
+            .form(&params)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            tracing::warn!("{} token exchange failed: {}", provider.name, error_text);
+            return Err(AppError::Auth(
+                "Failed to exchange authorization code".to_string(),
+            ));
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Verify an ID token's signature, issuer, audience and expiry
+    pub async fn verify_id_token(
+        &self,
+        provider: &OidcProvider,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+    ) -> AppResult<IdTokenClaims> {
+        let header = jsonwebtoken::decode_header(id_token)
+            .map_err(|_| AppError::Auth("Malformed ID token".to_string()))?;
+        let kid = header
+            .kid
+            .ok_or_else(|| AppError::Auth("ID token is missing a key ID".to_string()))?;
+
+        let jwk = provider.signing_key(&metadata.jwks_uri, &kid).await?;
+        let decoding_key = jsonwebtoken::DecodingKey::from_jwk(&jwk)
+            .map_err(|_| AppError::Auth("Unsupported ID token signing key".to_string()))?;
+
+        // OIDC providers sign ID tokens with RS256; never trust the algorithm from the header
+        let mut validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::RS256);
+        validation.set_audience(&[provider.client_id.as_str()]);
+        validation.set_issuer(&provider.issuers());
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let token_data =
+            jsonwebtoken::decode::<IdTokenClaims>(id_token, &decoding_key, &validation)
+                .map_err(|e| AppError::Auth(format!("Invalid ID token: {}", e)))?;
+
+        Ok(token_data.claims)
+    }
+
+    /// Build the profile from verified claims, falling back to the userinfo
+    /// endpoint when the ID token doesn't carry the email. The email must be
+    /// verified, or come from a provider configured with `trust_email`.
+    async fn resolve_user_info(
+        &self,
+        provider: &OidcProvider,
+        metadata: &ProviderMetadata,
+        claims: IdTokenClaims,
+        access_token: &str,
+    ) -> AppResult<OidcUserInfo> {
+        let user_info = if let Some(email) = claims.email {
+            OidcUserInfo {
+                id: claims.sub,
+                email,
+                email_verified: claims.email_verified,
+                name: claims.name,
+                picture: claims.picture,
+            }
+        } else {
+            let userinfo_endpoint = metadata.userinfo_endpoint.as_deref().ok_or_else(|| {
+                AppError::Auth(format!("{} did not provide an email address", provider.name))
+            })?;
+
+            let client = reqwest::Client::new();
+            let response = client
+                .get(userinfo_endpoint)
+                .bearer_auth(access_token)
+                .send()
+                .await?;
+
+            if !response.status().is_success() {
+                return Err(AppError::Auth(format!(
+                    "Failed to fetch {} user info",
+                    provider.name
+                )));
+            }
+
+            let user_info: OidcUserInfo = response.json().await?;
+
+            // The userinfo response must describe the same account as the ID token
+            if user_info.id != claims.sub {
+                return Err(AppError::Auth(format!(
+                    "{} user info does not match ID token",
+                    provider.name
+                )));
+            }
+            OidcUserInfo {
+                email_verified: user_info.email_verified.or(claims.email_verified),
+                ..user_info
+            }
+        };
+
+        let verified = match user_info.email_verified {
+            Some(verified) => verified,
+            None => provider.trust_email,
+        };
+        if !verified {
+            return Err(AppError::Auth(format!(
+                "{} account email is not verified",
+                provider.name
+            )));
+        }
+
+        Ok(user_info)
+    }
+
+    /// Passwordless email login: send a single-use sign-in link. The mail is
+    /// sent in the background so SMTP latency doesn't reveal the account, and
+    /// delivery failures are only logged.
+    pub async fn login(&self, payload: LoginRequest, mailer: Arc<dyn MailSender>) -> AppResult<()> {
+        if let Some(message) = self.prepare_magic_link(payload).await? {
+            tokio::spawn(async move { deliver_magic_link(mailer.as_ref(), &message).await });
+        }
+        Ok(())
+    }
+
+    /// Issue a magic-link token and deliver it through the given mail sender
+    /// before returning. Delivery failures are logged, not returned.
+    pub async fn send_magic_link(
+        &self,
+        payload: LoginRequest,
+        mailer: &dyn MailSender,
+    ) -> AppResult<()> {
+        if let Some(message) = self.prepare_magic_link(payload).await? {
+            deliver_magic_link(mailer, &message).await;
+        }
+        Ok(())
+    }
+
+    /// Store a magic-link token for the email's account and build the email
+    /// carrying it; `None` when there is no active account to sign in to
+    async fn prepare_magic_link(&self, payload: LoginRequest) -> AppResult<Option<MailMessage>> {
+        let email = payload.email.trim();
+
         // Log the login attempt for audit purposes
-        tracing::info!("Passwordless login attempt for email: {}", payload.email);
+        tracing::info!("Magic link login attempt for email: {}", email);
         
         // Validate input fields are not empty
-        if payload.email.trim().is_empty() {
-            return Err(AppError::Auth("Email is required".to_string()));
+        if email.is_empty() {
+            return Err(AppError::Validation("Email is required".to_string()));
         }
-        
-        // In the passwordless system, all authentication should be done via Google OAuth
-        // Return error with instructions to use Google OAuth
-        Err(AppError::Auth(
-            "Password-based login is deprecated. Please use Google OAuth for authentication.".to_string(),
-        ))
+        if !email.contains('@') {
+            return Err(AppError::Validation("Invalid email address".to_string()));
+        }
+
+        // Respond the same way for unknown addresses so accounts can't be enumerated
+        let Some(user) = self.get_user_by_email(email).await? else {
+            tracing::info!("Magic link requested for unknown email, nothing sent");
+            return Ok(None);
+        };
+
+        if !user.is_active() {
+            tracing::info!("Magic link requested for inactive account, nothing sent");
+            return Ok(None);
+        }
+
+        let token = generate_secret_token();
+        let magic_link = MagicLinkToken::new(
+            user.id.clone(),
+            hash_secret_token(&token),
+            self.config.magic_link_ttl_minutes as i64,
+        );
+
+        sqlx::query("INSERT INTO magic_link_tokens (id, user_id, token_hash, created_at, expires_at) VALUES ($1, $2, $3, $4, $5)")
+            .bind(&magic_link.id)
+            .bind(&magic_link.user_id)
+            .bind(&magic_link.token_hash)
+            .bind(magic_link.created_at)
+            .bind(magic_link.expires_at)
+            .execute(&self.db)
+            .await?;
+
+        let link = url::Url::parse_with_params(&self.config.magic_link_url, &[("token", &token)])
+            .map_err(|e| AppError::Internal(format!("Invalid magic_link_url: {}", e)))?;
+
+        let message = MailMessage {
+            to: user.email,
+            subject: "Your LLA portal sign-in link".to_string(),
+            body: format!(
+                "Use the link below to sign in to the LLA portal:\n\n{}\n\nThe link expires in {} minutes and can only be used once. If you didn't request it, you can ignore this email.\n",
+                link, self.config.magic_link_ttl_minutes
+            ),
+        };
+
+        Ok(Some(message))
+    }
+
+    /// Exchange a magic-link token for a JWT session
+    pub async fn verify_magic_link(&self, token: &str) -> AppResult<LoginOutcome> {
+        if token.is_empty() {
+            return Err(AppError::Auth("Sign-in token is required".to_string()));
+        }
+
+        // Consume atomically so the same link can't be used twice
+        let user_id: Option<String> = sqlx::query_scalar(
+            "UPDATE magic_link_tokens SET consumed_at = $1 WHERE token_hash = $2 AND consumed_at IS NULL AND expires_at > $1 RETURNING user_id",
+        )
+        .bind(Utc::now())
+        .bind(hash_secret_token(token))
+        .fetch_optional(&self.db)
+        .await?;
+
+        let user_id =
+            user_id.ok_or_else(|| AppError::Auth("Invalid or expired sign-in link".to_string()))?;
+
+        let user: Option<User> = sqlx::query_as(
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE id = $1",
+        )
+        .bind(&user_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        let user = user.ok_or_else(|| AppError::Auth("User not found".to_string()))?;
+        self.begin_login(user).await
     }
 
     /// Verify session token (alias for verify_session_cookie for backward compatibility)
@@ -1,11 +1,49 @@
-use crate::audit::AuditService;
+use crate::audit::{AuditService, SYSTEM_USER_ID};
 use crate::error::{AppError, AppResult};
-use crate::kv::{BlogIndexEntry, BlogPostKv, KvStore};
-use crate::models::{BlogPost, CreateBlogPostRequest, User};
-use chrono::Utc;
+use crate::kv::{has_passed, BlogIndexEntry, BlogPostKv, KvStore};
+use crate::slug::{self, slugify};
+use crate::models::{
+    BlogIndexQuery, BlogIndexResponse, BlogPost, CreateBlogPostRequest, FieldChange, PostRevision, PostStatus, RevisionDiffResponse,
+    Tag, TagPageResponse, TagResponse, UpdateTagRequest, User,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
 use sqlx::SqlitePool;
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
+/// Blog index page size when the request doesn't give one, and the largest allowed
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// How many numbered slugs to try before giving up on a title
+const MAX_SLUG_ATTEMPTS: u32 = 100;
+
+/// Post fields shown in revision diffs
+const FIELDS_COMPARED: &[&str] = &[
+    "title",
+    "slug",
+    "summary",
+    "body_html",
+    "tags",
+    "visibility",
+    "status",
+    "publish_at",
+    "unpublish_at",
+    "date_published",
+    "cover_image",
+    "attachments",
+    "meta",
+];
+
+/// A row of `post_slug_moves`
+#[derive(sqlx::FromRow)]
+struct SlugMove {
+    post_id: String,
+    old_slug: String,
+    new_slug: String,
+    created_by: String,
+}
+
 pub struct BlogService {
     pub kv: KvStore,
     pub db: SqlitePool,
@@ -24,19 +62,18 @@ impl BlogService {
         author_id: String,
     ) -> AppResult<BlogPostKv> {
         // Validate input
-        self.validate_blog_post_request(&payload)?;
+        let status = self
+            .validate_blog_post_request(&payload)?
+            .unwrap_or(PostStatus::Published);
+        validate_publish_window(status, payload.publish_at, payload.unpublish_at)?;
         
-        // Create slug from title
-        let slug = self.create_slug(&payload.title);
-
-        // Check if post with same slug already exists
-        if let Some(_) = self.kv.get_blog_post(&slug).await? {
-            return Err(AppError::Conflict(format!("A blog post with slug '{}' already exists", slug)));
-        }
+        // Create a slug from the title that no other post uses
+        let id = Uuid::new_v4().to_string();
+        let slug = self.unique_slug(&slugify(&payload.title), &id).await?;
 
         // Create blog post for KV storage
-        let blog_post = BlogPostKv {
-            id: Uuid::new_v4().to_string(),
+        let mut blog_post = BlogPostKv {
+            id,
             title: payload.title,
             slug: slug.clone(),
             summary: payload.summary.unwrap_or_default(),
@@ -45,13 +82,17 @@ impl BlogService {
             tags: payload.tags,
             date_published: Utc::now().to_rfc3339(),
             visibility: payload.visibility,
+            status: PostStatus::Draft.as_str().to_string(),
+            publish_at: None,
+            unpublish_at: None,
             cover_image: payload.cover_image,
             attachments: payload.attachments,
             meta: None,
         };
+        apply_status(&mut blog_post, status, payload.publish_at, payload.unpublish_at);
 
         // Store in KV
-        self.kv.put_blog_post(&slug, &blog_post).await?;
+        self.save_post(&slug, &blog_post, &author_id, None).await?;
 
         // Log audit event
         self.log_audit(&author_id, "create_blog_post", Some(blog_post.id.clone()))
@@ -68,8 +109,8 @@ impl BlogService {
         let blog_post = self.kv.get_blog_post(slug).await?;
 
         match blog_post {
-            Some(post) if post.visibility == "public" => Ok(Some(post)),
-            Some(_) => Ok(None), // Private post, return None
+            Some(post) if post.is_live(Utc::now()) => Ok(Some(post)),
+            Some(_) => Ok(None), // Private, unpublished or outside its window
             None => Ok(None),
         }
     }
@@ -80,12 +121,17 @@ impl BlogService {
         payload: CreateBlogPostRequest,
         author_id: String,
     ) -> AppResult<BlogPostKv> {
+        let status = self.validate_blog_post_request(&payload)?;
+
         let mut blog_post = self
             .kv
             .get_blog_post(slug)
             .await?
             .ok_or_else(|| AppError::NotFound("Blog post not found".to_string()))?;
 
+        let (status, publish_at, unpublish_at) = updated_status(status, &payload, &blog_post);
+        validate_publish_window(status, publish_at, unpublish_at)?;
+
         // Update blog post fields
         blog_post.title = payload.title;
         blog_post.summary = payload.summary.unwrap_or_default();
@@ -94,9 +140,10 @@ impl BlogService {
         blog_post.visibility = payload.visibility;
         blog_post.cover_image = payload.cover_image;
         blog_post.attachments = payload.attachments;
+        apply_status(&mut blog_post, status, publish_at, unpublish_at);
 
         // Store updated post in KV
-        self.kv.put_blog_post(slug, &blog_post).await?;
+        self.save_post(slug, &blog_post, &author_id, None).await?;
 
         // Log audit event
         self.log_audit(&author_id, "update_blog_post", Some(blog_post.id.clone()))
@@ -115,6 +162,33 @@ impl BlogService {
         // Delete from KV
         self.kv.delete_blog_post(slug).await?;
 
+        // Old slugs would only redirect to a missing post
+        sqlx::query("DELETE FROM post_redirects WHERE post_id = $1")
+            .bind(&blog_post.id)
+            .execute(&self.db)
+            .await?;
+        sqlx::query("DELETE FROM post_slugs WHERE post_id = $1")
+            .bind(&blog_post.id)
+            .execute(&self.db)
+            .await?;
+
+        // Drop the copy an unfinished slug change may have left behind
+        let pending: Option<String> = sqlx::query_scalar(
+            "DELETE FROM post_slug_moves WHERE post_id = $1 RETURNING new_slug",
+        )
+        .bind(&blog_post.id)
+        .fetch_optional(&self.db)
+        .await?;
+        if let Some(pending) = pending.filter(|pending| pending != slug)
+            && self
+                .kv
+                .get_blog_post(&pending)
+                .await?
+                .is_some_and(|stored| stored.id == blog_post.id)
+        {
+            self.kv.delete_blog_post(&pending).await?;
+        }
+
         // Log audit event
         self.log_audit(&author_id, "delete_blog_post", Some(blog_post.id.clone()))
             .await?;
@@ -128,18 +202,702 @@ impl BlogService {
         if include_private {
             Ok(blog_index)
         } else {
-            // Filter out private posts for public access
+            // Only live posts for public access
+            let now = Utc::now();
             let public_posts: Vec<BlogIndexEntry> = blog_index
                 .into_iter()
-                .filter(|post| post.visibility == "public")
+                .filter(|post| post.is_live(now))
                 .collect();
             Ok(public_posts)
         }
     }
 
+    /// Move a post to a new slug. The old slug (and any slug that already
+    /// redirected to it) permanently redirects to the new one.
+    ///
+    /// The move is recorded in `post_slug_moves` before the KV writes, so if
+    /// it fails part-way, repeating the same change (or the next startup,
+    /// see `finish_slug_moves`) completes it.
+    pub async fn change_slug(
+        &self,
+        slug: &str,
+        new_slug: &str,
+        author_id: String,
+    ) -> AppResult<BlogPostKv> {
+        validate_slug(new_slug)?;
+        let blog_post = match self.kv.get_blog_post(slug).await? {
+            Some(blog_post) => blog_post,
+            // An earlier attempt got as far as removing the old key
+            None if self.find_slug_move(slug, new_slug).await?.is_some() => {
+                self.require_post(new_slug).await?
+            }
+            None => return Err(AppError::NotFound("Blog post not found".to_string())),
+        };
+        if new_slug == slug {
+            return Ok(blog_post);
+        }
+        let taken = match self.kv.get_blog_post(new_slug).await? {
+            Some(existing) => existing.id != blog_post.id,
+            None => false,
+        };
+        if taken || !self.claim_slug(new_slug, &blog_post.id).await? {
+            return Err(AppError::Conflict(format!("A blog post with slug '{}' already exists", new_slug)));
+        }
+
+        let pending: Option<String> =
+            sqlx::query_scalar("SELECT new_slug FROM post_slug_moves WHERE post_id = $1")
+                .bind(&blog_post.id)
+                .fetch_optional(&self.db)
+                .await?;
+        match pending {
+            Some(pending) if pending != new_slug => {
+                return Err(AppError::Conflict(format!(
+                    "An earlier move of this post to '{}' has not finished; retry that change first",
+                    pending
+                )));
+            }
+            Some(_) => {}
+            None => {
+                sqlx::query(
+                    "INSERT INTO post_slug_moves (post_id, old_slug, new_slug, created_by, created_at)
+                     VALUES ($1, $2, $3, $4, $5)",
+                )
+                .bind(&blog_post.id)
+                .bind(slug)
+                .bind(new_slug)
+                .bind(&author_id)
+                .bind(Utc::now())
+                .execute(&self.db)
+                .await?;
+            }
+        }
+
+        self.finish_slug_move(slug, blog_post, new_slug, &author_id)
+            .await
+    }
+
+    /// Complete slug changes left unfinished, e.g. by a crash between the KV
+    /// writes. Moves whose post is gone are dropped. Returns how many moves
+    /// were completed.
+    pub async fn finish_slug_moves(&self) -> AppResult<usize> {
+        let moves: Vec<SlugMove> = sqlx::query_as(
+            "SELECT post_id, old_slug, new_slug, created_by FROM post_slug_moves",
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut finished = 0;
+        for slug_move in moves {
+            let mut blog_post = None;
+            for slug in [&slug_move.old_slug, &slug_move.new_slug] {
+                if let Some(stored) = self.kv.get_blog_post(slug).await?
+                    && stored.id == slug_move.post_id
+                {
+                    blog_post = Some(stored);
+                    break;
+                }
+            }
+            match blog_post {
+                Some(blog_post) => {
+                    self.finish_slug_move(
+                        &slug_move.old_slug,
+                        blog_post,
+                        &slug_move.new_slug,
+                        &slug_move.created_by,
+                    )
+                    .await?;
+                    finished += 1;
+                }
+                None => {
+                    sqlx::query("DELETE FROM post_slug_moves WHERE post_id = $1")
+                        .bind(&slug_move.post_id)
+                        .execute(&self.db)
+                        .await?;
+                }
+            }
+        }
+        Ok(finished)
+    }
+
+    async fn find_slug_move(&self, old_slug: &str, new_slug: &str) -> AppResult<Option<String>> {
+        let post_id = sqlx::query_scalar(
+            "SELECT post_id FROM post_slug_moves WHERE old_slug = $1 AND new_slug = $2",
+        )
+        .bind(old_slug)
+        .bind(new_slug)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(post_id)
+    }
+
+    /// The steps of a recorded move. Each can be repeated, so a move that
+    /// failed part-way is finished by running them all again.
+    async fn finish_slug_move(
+        &self,
+        slug: &str,
+        mut blog_post: BlogPostKv,
+        new_slug: &str,
+        author_id: &str,
+    ) -> AppResult<BlogPostKv> {
+        blog_post.slug = new_slug.to_string();
+        self.snapshot_untracked_post(slug, &blog_post.id).await?;
+        self.kv.rename_blog_post(slug, &blog_post).await?;
+
+        // A retry after the revision was saved must not save it twice
+        let last_slug: Option<String> = sqlx::query_scalar(
+            "SELECT slug FROM post_revisions WHERE post_id = $1 ORDER BY revision DESC LIMIT 1",
+        )
+        .bind(&blog_post.id)
+        .fetch_optional(&self.db)
+        .await?;
+        if last_slug.as_deref() != Some(new_slug) {
+            self.record_revision(new_slug, &blog_post, author_id, None)
+                .await?;
+        }
+
+        let mut tx = self.db.begin().await?;
+
+        // Collapse chains so every old slug redirects in one hop
+        sqlx::query("UPDATE post_redirects SET new_slug = $1 WHERE new_slug = $2")
+            .bind(new_slug)
+            .bind(slug)
+            .execute(&mut *tx)
+            .await?;
+
+        // The new slug is live again if the post once moved away from it
+        sqlx::query("DELETE FROM post_redirects WHERE old_slug = $1 AND post_id = $2")
+            .bind(new_slug)
+            .bind(&blog_post.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO post_redirects (old_slug, new_slug, post_id, created_by, created_at)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT(old_slug) DO UPDATE SET
+                 new_slug = excluded.new_slug,
+                 post_id = excluded.post_id,
+                 created_by = excluded.created_by,
+                 created_at = excluded.created_at",
+        )
+        .bind(slug)
+        .bind(new_slug)
+        .bind(&blog_post.id)
+        .bind(author_id)
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM post_slug_moves WHERE post_id = $1")
+            .bind(&blog_post.id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        self.audit
+            .log_action(
+                author_id,
+                "change_blog_post_slug".to_string(),
+                Some(blog_post.id.clone()),
+                Some(serde_json::json!({
+                    "before": { "slug": slug },
+                    "after": { "slug": new_slug },
+                })),
+            )
+            .await?;
+
+        Ok(blog_post)
+    }
+
+    /// Current slug of a live post that used to be at `slug`. Redirects to
+    /// posts readers can't see are not revealed.
+    pub async fn get_public_redirect(&self, slug: &str) -> AppResult<Option<String>> {
+        let new_slug: Option<String> =
+            sqlx::query_scalar("SELECT new_slug FROM post_redirects WHERE old_slug = $1")
+                .bind(slug)
+                .fetch_optional(&self.db)
+                .await?;
+
+        match new_slug {
+            Some(new_slug) if self.get_public_post(&new_slug).await?.is_some() => Ok(Some(new_slug)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Saved versions of a post, newest first
+    pub async fn list_revisions(&self, slug: &str) -> AppResult<Vec<PostRevision>> {
+        let blog_post = self.require_post(slug).await?;
+        let revisions = sqlx::query_as(
+            "SELECT id, post_id, revision, slug, author_id, created_at, restored_from, payload
+             FROM post_revisions WHERE post_id = $1 ORDER BY revision DESC",
+        )
+        .bind(&blog_post.id)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(revisions)
+    }
+
+    pub async fn get_revision(&self, slug: &str, revision: i64) -> AppResult<PostRevision> {
+        let blog_post = self.require_post(slug).await?;
+        let revision: Option<PostRevision> = sqlx::query_as(
+            "SELECT id, post_id, revision, slug, author_id, created_at, restored_from, payload
+             FROM post_revisions WHERE post_id = $1 AND revision = $2",
+        )
+        .bind(&blog_post.id)
+        .bind(revision)
+        .fetch_optional(&self.db)
+        .await?;
+        revision.ok_or_else(|| AppError::NotFound("Revision not found".to_string()))
+    }
+
+    /// Fields that differ between two revisions of a post, in field order
+    pub async fn diff_revisions(&self, slug: &str, from: i64, to: i64) -> AppResult<RevisionDiffResponse> {
+        let before: serde_json::Value =
+            serde_json::from_str(&self.get_revision(slug, from).await?.payload)?;
+        let after: serde_json::Value =
+            serde_json::from_str(&self.get_revision(slug, to).await?.payload)?;
+
+        let changes = FIELDS_COMPARED
+            .iter()
+            .filter(|field| before.get(**field) != after.get(**field))
+            .map(|field| FieldChange {
+                field: field.to_string(),
+                before: before.get(*field).cloned().unwrap_or_default(),
+                after: after.get(*field).cloned().unwrap_or_default(),
+            })
+            .collect();
+
+        Ok(RevisionDiffResponse { from, to, changes })
+    }
+
+    /// Put an old revision's content back as a new revision. The post keeps
+    /// its slug, visibility, status and publish window; only the content is
+    /// restored.
+    pub async fn restore_revision(
+        &self,
+        slug: &str,
+        revision: i64,
+        author_id: String,
+    ) -> AppResult<(BlogPostKv, i64)> {
+        let mut blog_post = self.require_post(slug).await?;
+        let old: BlogPostKv = serde_json::from_str(&self.get_revision(slug, revision).await?.payload)?;
+
+        blog_post.title = old.title;
+        blog_post.summary = old.summary;
+        blog_post.body_html = old.body_html;
+        blog_post.tags = old.tags;
+        blog_post.cover_image = old.cover_image;
+        blog_post.attachments = old.attachments;
+        blog_post.meta = old.meta;
+
+        let new_revision = self
+            .save_post(slug, &blog_post, &author_id, Some(revision))
+            .await?;
+
+        self.audit
+            .log_action(
+                &author_id,
+                "restore_blog_post_revision".to_string(),
+                Some(blog_post.id.clone()),
+                Some(serde_json::json!({
+                    "slug": slug,
+                    "restored_from": revision,
+                    "revision": new_revision,
+                })),
+            )
+            .await?;
+
+        Ok((blog_post, new_revision))
+    }
+
+    /// Write a post to KV and record the written version as its next
+    /// revision, returning the revision number
+    async fn save_post(
+        &self,
+        slug: &str,
+        blog_post: &BlogPostKv,
+        author_id: &str,
+        restored_from: Option<i64>,
+    ) -> AppResult<i64> {
+        self.snapshot_untracked_post(slug, &blog_post.id).await?;
+        self.kv.put_blog_post(slug, blog_post).await?;
+        self.register_tags(&blog_post.tags).await?;
+        self.record_revision(slug, blog_post, author_id, restored_from)
+            .await
+    }
+
+    // Add tags the registry hasn't seen, named as first written
+    async fn register_tags(&self, tags: &[String]) -> AppResult<()> {
+        for tag in tags {
+            sqlx::query(
+                "INSERT INTO tags (slug, name, created_at, updated_at) VALUES ($1, $2, $3, $3)
+                 ON CONFLICT(slug) DO NOTHING",
+            )
+            .bind(slugify(tag))
+            .bind(tag.trim())
+            .bind(Utc::now())
+            .execute(&self.db)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Posts written before revisions were kept have none; record the
+    /// stored copy as revision 1, under its own author, before the first
+    /// save overwrites it
+    async fn snapshot_untracked_post(&self, slug: &str, post_id: &str) -> AppResult<()> {
+        let has_revisions: bool =
+            sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM post_revisions WHERE post_id = $1)")
+                .bind(post_id)
+                .fetch_one(&self.db)
+                .await?;
+        if has_revisions {
+            return Ok(());
+        }
+        let Some(stored) = self.kv.get_blog_post(slug).await? else {
+            return Ok(()); // A new post
+        };
+        if stored.id != post_id {
+            return Ok(());
+        }
+
+        // Only ever revision 1: a concurrent first save may have won the race
+        sqlx::query(
+            "INSERT INTO post_revisions (id, post_id, revision, slug, author_id, created_at, restored_from, payload)
+             SELECT $1, $2, 1, $3, $4, $5, NULL, $6
+             WHERE NOT EXISTS (SELECT 1 FROM post_revisions WHERE post_id = $2)",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(post_id)
+        .bind(slug)
+        .bind(&stored.author_id)
+        .bind(Utc::now())
+        .bind(serde_json::to_string(&stored)?)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn record_revision(
+        &self,
+        slug: &str,
+        blog_post: &BlogPostKv,
+        author_id: &str,
+        restored_from: Option<i64>,
+    ) -> AppResult<i64> {
+        // Numbered in the insert itself so concurrent saves can't share a number
+        let revision: i64 = sqlx::query_scalar(
+            "INSERT INTO post_revisions (id, post_id, revision, slug, author_id, created_at, restored_from, payload)
+             SELECT $1, $2, COALESCE(MAX(revision), 0) + 1, $3, $4, $5, $6, $7
+             FROM post_revisions WHERE post_id = $2
+             RETURNING revision",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&blog_post.id)
+        .bind(slug)
+        .bind(author_id)
+        .bind(Utc::now())
+        .bind(restored_from)
+        .bind(serde_json::to_string(blog_post)?)
+        .fetch_one(&self.db)
+        .await?;
+        Ok(revision)
+    }
+
+    async fn require_post(&self, slug: &str) -> AppResult<BlogPostKv> {
+        self.kv
+            .get_blog_post(slug)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Blog post not found".to_string()))
+    }
+
+    /// One page of the blog index, filtered and sorted. Shared by the public
+    /// index (live posts only) and the admin listing; `base_path` is the
+    /// endpoint the next/prev links point at.
+    pub async fn query_posts(
+        &self,
+        query: &BlogIndexQuery,
+        include_private: bool,
+        base_path: &str,
+    ) -> AppResult<BlogIndexResponse> {
+        let page = query.page.unwrap_or(1).max(1);
+        let per_page = query
+            .per_page
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+
+        let sort = query.sort.as_deref().unwrap_or("newest");
+        if !["newest", "oldest", "title"].contains(&sort) {
+            return Err(AppError::Validation(
+                "Sort must be 'newest', 'oldest' or 'title'".to_string(),
+            ));
+        }
+        let status = match query.status.as_deref().filter(|s| !s.is_empty()) {
+            Some(status) => Some(
+                PostStatus::parse(status)
+                    .ok_or_else(|| AppError::Validation(format!("Invalid status: {}", status)))?,
+            ),
+            None => None,
+        };
+        let tag = query
+            .tag
+            .as_deref()
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(slugify);
+        let author = query.author.as_deref().filter(|author| !author.is_empty());
+
+        let mut posts: Vec<BlogIndexEntry> = self
+            .list_posts(include_private)
+            .await?
+            .into_iter()
+            .filter(|post| {
+                tag.as_ref()
+                    .is_none_or(|tag| post.tags.iter().any(|t| slugify(t) == *tag))
+            })
+            .filter(|post| author.is_none_or(|author| post.author_id == author))
+            .filter(|post| status.is_none_or(|status| post.status == status.as_str()))
+            .filter(|post| published_in_range(&post.date_published, query.from, query.to))
+            .collect();
+
+        match sort {
+            "oldest" => posts.sort_by(|a, b| a.date_published.cmp(&b.date_published)),
+            "title" => posts.sort_by_key(|post| post.title.to_lowercase()),
+            _ => posts.sort_by(|a, b| b.date_published.cmp(&a.date_published)),
+        }
+
+        let total = posts.len() as u64;
+        let total_pages = total.div_ceil(per_page as u64) as u32;
+        let posts = posts
+            .into_iter()
+            .skip(((page - 1) as usize).saturating_mul(per_page as usize))
+            .take(per_page as usize)
+            .collect();
+
+        let next = (page < total_pages).then(|| page_link(base_path, query, page + 1, per_page));
+        let prev = (page > 1)
+            .then(|| page_link(base_path, query, (page - 1).min(total_pages.max(1)), per_page));
+
+        Ok(BlogIndexResponse {
+            posts,
+            page,
+            per_page,
+            total,
+            total_pages,
+            next,
+            prev,
+        })
+    }
+
+    /// Tags with how many posts use them, by slug. The public list only has
+    /// tags on live posts; the admin list counts every post and includes
+    /// registered tags no post uses any more.
+    pub async fn list_tags(&self, include_private: bool) -> AppResult<Vec<TagResponse>> {
+        let registered: Vec<Tag> = sqlx::query_as(
+            "SELECT slug, name, description FROM tags",
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut tags: BTreeMap<String, TagResponse> = registered
+            .into_iter()
+            .map(|tag| {
+                let response = TagResponse {
+                    slug: tag.slug.clone(),
+                    name: tag.name,
+                    description: tag.description,
+                    post_count: 0,
+                };
+                (tag.slug, response)
+            })
+            .collect();
+
+        for post in self.list_posts(include_private).await? {
+            let mut counted: Vec<String> = Vec::new();
+            for name in &post.tags {
+                let slug = slugify(name);
+                if counted.contains(&slug) {
+                    continue;
+                }
+                // Posts saved before the registry existed may use unregistered tags
+                tags.entry(slug.clone())
+                    .or_insert_with(|| TagResponse {
+                        slug: slug.clone(),
+                        name: name.trim().to_string(),
+                        description: None,
+                        post_count: 0,
+                    })
+                    .post_count += 1;
+                counted.push(slug);
+            }
+        }
+
+        Ok(tags
+            .into_values()
+            .filter(|tag| include_private || tag.post_count > 0)
+            .collect())
+    }
+
+    pub async fn get_tag(&self, slug: &str, include_private: bool) -> AppResult<TagResponse> {
+        self.list_tags(include_private)
+            .await?
+            .into_iter()
+            .find(|tag| tag.slug == slug)
+            .ok_or_else(|| AppError::NotFound("Tag not found".to_string()))
+    }
+
+    /// A tag with one page of its live public posts. `slug` may also be the
+    /// tag's name, as in links built from a post's tags.
+    pub async fn tag_page(&self, slug: &str, query: BlogIndexQuery) -> AppResult<TagPageResponse> {
+        let tag = self.get_tag(&slugify(slug), false).await?;
+        let query = BlogIndexQuery {
+            tag: Some(tag.slug.clone()),
+            status: None,
+            ..query
+        };
+        let page = self
+            .query_posts(&query, false, &format!("/api/blog/tags/{}", tag.slug))
+            .await?;
+        Ok(TagPageResponse { tag, page })
+    }
+
+    /// Rename a tag and set its description. Every post using the tag is
+    /// rewritten with the new name; a name whose slug another tag already
+    /// has is a conflict (merge the tags instead).
+    pub async fn update_tag(
+        &self,
+        slug: &str,
+        payload: UpdateTagRequest,
+        author_id: String,
+    ) -> AppResult<TagResponse> {
+        validate_tag(&payload.name)?;
+        let tags = self.list_tags(true).await?;
+        let before = tags
+            .iter()
+            .find(|tag| tag.slug == slug)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound("Tag not found".to_string()))?;
+        let name = payload.name.trim().to_string();
+        let new_slug = slugify(&name);
+        if new_slug != slug && tags.iter().any(|tag| tag.slug == new_slug) {
+            return Err(AppError::Conflict(format!(
+                "A tag with slug '{}' already exists; merge the tags instead",
+                new_slug
+            )));
+        }
+        let description = payload
+            .description
+            .map(|description| description.trim().to_string())
+            .filter(|description| !description.is_empty());
+
+        // Registered first so re-saved posts don't register the old name
+        let mut tx = self.db.begin().await?;
+        sqlx::query("DELETE FROM tags WHERE slug = $1")
+            .bind(slug)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO tags (slug, name, description, created_at, updated_at) VALUES ($1, $2, $3, $4, $4)",
+        )
+        .bind(&new_slug)
+        .bind(&name)
+        .bind(&description)
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let posts_changed = self.retag_posts(slug, &name, &author_id).await?;
+
+        self.audit
+            .log_action(
+                &author_id,
+                "update_tag".to_string(),
+                Some(new_slug.clone()),
+                Some(serde_json::json!({
+                    "before": { "slug": before.slug, "name": before.name, "description": before.description },
+                    "after": { "slug": new_slug, "name": name, "description": description },
+                    "posts_changed": posts_changed,
+                })),
+            )
+            .await?;
+
+        self.get_tag(&new_slug, true).await
+    }
+
+    /// Fold one tag into another: posts using `slug` get the target tag
+    /// instead, and `slug` leaves the registry
+    pub async fn merge_tag(&self, slug: &str, into: &str, author_id: String) -> AppResult<TagResponse> {
+        if slug == into {
+            return Err(AppError::Validation("Cannot merge a tag into itself".to_string()));
+        }
+        self.get_tag(slug, true).await?;
+        let target = self.get_tag(into, true).await?;
+
+        let posts_changed = self.retag_posts(slug, &target.name, &author_id).await?;
+
+        sqlx::query("DELETE FROM tags WHERE slug = $1")
+            .bind(slug)
+            .execute(&self.db)
+            .await?;
+
+        self.audit
+            .log_action(
+                &author_id,
+                "merge_tags".to_string(),
+                Some(target.slug.clone()),
+                Some(serde_json::json!({
+                    "from": slug,
+                    "into": target.slug,
+                    "posts_changed": posts_changed,
+                })),
+            )
+            .await?;
+
+        self.get_tag(&target.slug, true).await
+    }
+
+    // Replace tag `slug` with `name` on every post using it, dropping
+    // duplicates, and return how many posts changed
+    async fn retag_posts(&self, slug: &str, name: &str, author_id: &str) -> AppResult<usize> {
+        let tagged: Vec<String> = self
+            .kv
+            .get_blog_index()
+            .await?
+            .into_iter()
+            .filter(|entry| entry.tags.iter().any(|tag| slugify(tag) == slug))
+            .map(|entry| entry.slug)
+            .collect();
+
+        let mut changed = 0;
+        for post_slug in tagged {
+            // The index may be stale; the post record decides
+            let Some(mut blog_post) = self.kv.get_blog_post(&post_slug).await? else {
+                continue;
+            };
+            let mut tags: Vec<String> = Vec::new();
+            for tag in &blog_post.tags {
+                let tag = if slugify(tag) == slug { name } else { tag.as_str() };
+                if !tags.iter().any(|t| slugify(t) == slugify(tag)) {
+                    tags.push(tag.to_string());
+                }
+            }
+            if tags == blog_post.tags {
+                continue;
+            }
+
+            blog_post.tags = tags;
+            self.save_post(&post_slug, &blog_post, author_id, None).await?;
+            changed += 1;
+        }
+
+        Ok(changed)
+    }
+
     pub async fn get_user(&self, user_id: &str) -> AppResult<Option<User>> {
         let user: Option<User> = sqlx::query_as(
-            "SELECT id, email, role, google_id, full_name, created_at FROM users WHERE id = $1",
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE id = $1",
         )
         .bind(user_id)
         .fetch_optional(&self.db)
@@ -149,7 +907,7 @@ impl BlogService {
 
     pub async fn get_user_by_email(&self, email: &str) -> AppResult<Option<User>> {
         let user: Option<User> = sqlx::query_as(
-            "SELECT id, email, role, google_id, full_name, created_at FROM users WHERE email = $1"
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE email = $1"
         )
         .bind(email)
         .fetch_optional(&self.db)
@@ -157,7 +915,58 @@ impl BlogService {
         Ok(user)
     }
 
-    fn validate_blog_post_request(&self, payload: &CreateBlogPostRequest) -> AppResult<()> {
+    /// Publish scheduled posts whose `publish_at` has passed and archive
+    /// published posts past their `unpublish_at`. Run by the publish scheduler;
+    /// returns how many posts changed.
+    pub async fn publish_due_posts(&self, now: DateTime<Utc>) -> AppResult<usize> {
+        let due: Vec<String> = self
+            .kv
+            .get_blog_index()
+            .await?
+            .into_iter()
+            .filter(|entry| is_due(&entry.status, &entry.publish_at, &entry.unpublish_at, now))
+            .map(|entry| entry.slug)
+            .collect();
+
+        let mut changed = 0;
+        for slug in due {
+            // The index may be stale; the post record decides
+            let Some(mut blog_post) = self.kv.get_blog_post(&slug).await? else {
+                continue;
+            };
+            if !is_due(&blog_post.status, &blog_post.publish_at, &blog_post.unpublish_at, now) {
+                continue;
+            }
+
+            let before = blog_post.status.clone();
+            let action = if blog_post.status == PostStatus::Scheduled.as_str() {
+                blog_post.status = PostStatus::Published.as_str().to_string();
+                "publish_scheduled_post"
+            } else {
+                blog_post.status = PostStatus::Archived.as_str().to_string();
+                "archive_expired_post"
+            };
+            self.save_post(&slug, &blog_post, SYSTEM_USER_ID, None).await?;
+
+            self.audit
+                .log_action(
+                    SYSTEM_USER_ID,
+                    action.to_string(),
+                    Some(blog_post.id.clone()),
+                    Some(serde_json::json!({
+                        "slug": slug,
+                        "before": { "status": before },
+                        "after": { "status": blog_post.status },
+                    })),
+                )
+                .await?;
+            changed += 1;
+        }
+
+        Ok(changed)
+    }
+
+    fn validate_blog_post_request(&self, payload: &CreateBlogPostRequest) -> AppResult<Option<PostStatus>> {
         // Validate title
         if payload.title.trim().is_empty() {
             return Err(AppError::Validation("Title cannot be empty".to_string()));
@@ -180,6 +989,15 @@ impl BlogService {
         if !["public", "private"].contains(&payload.visibility.as_str()) {
             return Err(AppError::Validation("Visibility must be 'public' or 'private'".to_string()));
         }
+
+        // Validate status; the publish window is checked once the status
+        // an update ends up with is known
+        let status = payload.post_status();
+        if payload.status.is_some() && status.is_none() {
+            return Err(AppError::Validation(
+                "Status must be one of draft, in_review, scheduled, published, archived".to_string(),
+            ));
+        }
         
         // Validate tags
         if payload.tags.len() > 10 {
@@ -187,67 +1005,78 @@ impl BlogService {
         }
         
         for tag in &payload.tags {
-            if tag.trim().is_empty() {
-                return Err(AppError::Validation("Tags cannot be empty".to_string()));
-            }
-            if tag.len() > 50 {
-                return Err(AppError::Validation("Tag cannot exceed 50 characters".to_string()));
-            }
+            validate_tag(tag)?;
         }
         
-        Ok(())
+        Ok(status)
     }
 
-    fn create_slug(&self, title: &str) -> String {
-        title
-            .to_lowercase()
-            .replace(" ", "-")
-            .chars()
-            .map(|c| {
-                if c.is_alphanumeric() || c == '-' {
-                    c
-                } else {
-                    '-'
-                }
-            })
-            .collect::<String>()
-            .trim_matches('-')
-            .to_string()
+    /// `base`, or the first of `base-2`, `base-3`... that no post uses,
+    /// claimed for `post_id`. Old slugs that redirect to a moved post count
+    /// as used.
+    async fn unique_slug(&self, base: &str, post_id: &str) -> AppResult<String> {
+        for n in 1..=MAX_SLUG_ATTEMPTS {
+            let candidate = slug::numbered(base, n);
+            if !self.slug_in_use(&candidate).await? && self.claim_slug(&candidate, post_id).await? {
+                return Ok(candidate);
+            }
+        }
+        Err(AppError::Conflict(format!("Too many blog posts with slug '{}'", base)))
+    }
+
+    /// Atomically reserve `slug` for `post_id`; false when another post
+    /// holds it. Posts stored before claims existed are only visible to
+    /// `slug_in_use`.
+    async fn claim_slug(&self, slug: &str, post_id: &str) -> AppResult<bool> {
+        sqlx::query(
+            "INSERT INTO post_slugs (slug, post_id, created_at) VALUES ($1, $2, $3)
+             ON CONFLICT(slug) DO NOTHING",
+        )
+        .bind(slug)
+        .bind(post_id)
+        .bind(Utc::now())
+        .execute(&self.db)
+        .await?;
+        let owner: String = sqlx::query_scalar("SELECT post_id FROM post_slugs WHERE slug = $1")
+            .bind(slug)
+            .fetch_one(&self.db)
+            .await?;
+        Ok(owner == post_id)
     }
 
-    /// Create a blog post using the BlogPost model constructor
+    async fn slug_in_use(&self, slug: &str) -> AppResult<bool> {
+        if self.kv.get_blog_post(slug).await?.is_some() {
+            return Ok(true);
+        }
+        let redirected: Option<String> =
+            sqlx::query_scalar("SELECT old_slug FROM post_redirects WHERE old_slug = $1")
+                .bind(slug)
+                .fetch_optional(&self.db)
+                .await?;
+        Ok(redirected.is_some())
+    }
+
+    /// Create a blog post using the BlogPost model constructor. The
+    /// request's status and window apply as in `create_post`.
     pub async fn create_post_with_model(
         &self,
-        title: String,
-        content: String,
-        excerpt: String,
+        payload: CreateBlogPostRequest,
         author_id: String,
-        tags: Vec<String>,
-        visibility: String,
-        cover_image: Option<String>,
-        inline_images: Vec<String>,
-        attachments: Vec<String>,
     ) -> AppResult<BlogPost> {
+        let status = self
+            .validate_blog_post_request(&payload)?
+            .unwrap_or(PostStatus::Published);
+        validate_publish_window(status, payload.publish_at, payload.unpublish_at)?;
+        let (publish_at, unpublish_at) = (payload.publish_at, payload.unpublish_at);
+
         // Use BlogPost::new to create the post
-        let blog_post = BlogPost::new(
-            title,
-            content,
-            excerpt,
-            author_id.clone(),
-            tags,
-            visibility,
-            cover_image,
-            inline_images,
-            attachments,
-        );
-
-        // Check if post with same slug already exists
-        if let Some(_) = self.kv.get_blog_post(&blog_post.slug).await? {
-            return Err(AppError::Conflict(format!("A blog post with slug '{}' already exists", blog_post.slug)));
-        }
+        let mut blog_post = BlogPost::new(payload.into(), author_id.clone());
+
+        // Number the slug if another post already uses it
+        blog_post.slug = self.unique_slug(&blog_post.slug, &blog_post.id).await?;
 
         // Convert to KV format for storage
-        let blog_post_kv = BlogPostKv {
+        let mut blog_post_kv = BlogPostKv {
             id: blog_post.id.clone(),
             title: blog_post.title.clone(),
             slug: blog_post.slug.clone(),
@@ -257,13 +1086,18 @@ impl BlogService {
             tags: blog_post.tags.clone(),
             date_published: blog_post.created_at.to_rfc3339(),
             visibility: blog_post.visibility.clone(),
+            status: PostStatus::Draft.as_str().to_string(),
+            publish_at: None,
+            unpublish_at: None,
             cover_image: blog_post.cover_image.clone(),
             attachments: blog_post.attachments.clone(),
             meta: None,
         };
+        apply_status(&mut blog_post_kv, status, publish_at, unpublish_at);
 
         // Store in KV
-        self.kv.put_blog_post(&blog_post.slug, &blog_post_kv).await?;
+        self.save_post(&blog_post.slug, &blog_post_kv, &author_id, None)
+            .await?;
 
         // Log audit event
         self.log_audit(&author_id, "create_blog_post_with_model", Some(blog_post.id.clone()))
@@ -272,20 +1106,16 @@ impl BlogService {
         Ok(blog_post)
     }
 
-    /// Update a blog post using the BlogPost model update method
+    /// Update a blog post using the BlogPost model update method. A request
+    /// without a status keeps the stored one, as in `update_post`.
     pub async fn update_post_with_model(
         &self,
         slug: &str,
-        title: String,
-        content: String,
-        excerpt: String,
-        tags: Vec<String>,
-        visibility: String,
-        cover_image: Option<String>,
-        inline_images: Vec<String>,
-        attachments: Vec<String>,
+        payload: CreateBlogPostRequest,
         author_id: String,
     ) -> AppResult<BlogPost> {
+        let status = self.validate_blog_post_request(&payload)?;
+
         // Get existing post from KV
         let existing_kv = self
             .kv
@@ -293,6 +1123,12 @@ impl BlogService {
             .await?
             .ok_or_else(|| AppError::NotFound("Blog post not found".to_string()))?;
 
+        let (status, publish_at, unpublish_at) = updated_status(status, &payload, &existing_kv);
+        validate_publish_window(status, publish_at, unpublish_at)?;
+        let stored_status = existing_kv.status.clone();
+        let stored_publish_at = existing_kv.publish_at.clone();
+        let stored_unpublish_at = existing_kv.unpublish_at.clone();
+
         // Convert KV post to BlogPost model
         let mut blog_post = BlogPost {
             id: existing_kv.id,
@@ -313,19 +1149,10 @@ impl BlogService {
         };
 
         // Use BlogPost::update method to update the post
-        blog_post.update(
-            title,
-            content,
-            excerpt,
-            tags,
-            visibility,
-            cover_image,
-            inline_images,
-            attachments,
-        );
+        blog_post.update(payload.into());
 
         // Convert back to KV format for storage
-        let blog_post_kv = BlogPostKv {
+        let mut blog_post_kv = BlogPostKv {
             id: blog_post.id.clone(),
             title: blog_post.title.clone(),
             slug: blog_post.slug.clone(),
@@ -335,13 +1162,17 @@ impl BlogService {
             tags: blog_post.tags.clone(),
             date_published: blog_post.created_at.to_rfc3339(),
             visibility: blog_post.visibility.clone(),
+            status: stored_status,
+            publish_at: stored_publish_at,
+            unpublish_at: stored_unpublish_at,
             cover_image: blog_post.cover_image.clone(),
             attachments: blog_post.attachments.clone(),
             meta: None,
         };
+        apply_status(&mut blog_post_kv, status, publish_at, unpublish_at);
 
         // Store updated post in KV
-        self.kv.put_blog_post(slug, &blog_post_kv).await?;
+        self.save_post(slug, &blog_post_kv, &author_id, None).await?;
 
         // Log audit event
         self.log_audit(&author_id, "update_blog_post_with_model", Some(blog_post.id.clone()))
@@ -361,3 +1192,149 @@ impl BlogService {
             .await
     }
 }
+
+// Whether a post's `date_published` falls in [from, to)
+fn published_in_range(
+    date_published: &str,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> bool {
+    if from.is_none() && to.is_none() {
+        return true;
+    }
+    let Ok(published) = DateTime::parse_from_rfc3339(date_published) else {
+        return false;
+    };
+    from.is_none_or(|from| published >= from) && to.is_none_or(|to| published < to)
+}
+
+// Link to another page of the index, keeping the request's filters
+fn page_link(base_path: &str, query: &BlogIndexQuery, page: u32, per_page: u32) -> String {
+    let mut params = url::form_urlencoded::Serializer::new(String::new());
+    params.append_pair("page", &page.to_string());
+    params.append_pair("per_page", &per_page.to_string());
+    if let Some(tag) = &query.tag {
+        params.append_pair("tag", tag);
+    }
+    if let Some(author) = &query.author {
+        params.append_pair("author", author);
+    }
+    if let Some(from) = query.from {
+        params.append_pair("from", &from.to_rfc3339_opts(SecondsFormat::Secs, true));
+    }
+    if let Some(to) = query.to {
+        params.append_pair("to", &to.to_rfc3339_opts(SecondsFormat::Secs, true));
+    }
+    if let Some(sort) = &query.sort {
+        params.append_pair("sort", sort);
+    }
+    if let Some(status) = &query.status {
+        params.append_pair("status", status);
+    }
+    format!("{}?{}", base_path, params.finish())
+}
+
+fn validate_tag(tag: &str) -> AppResult<()> {
+    if tag.trim().is_empty() {
+        return Err(AppError::Validation("Tags cannot be empty".to_string()));
+    }
+    if tag.len() > 50 {
+        return Err(AppError::Validation("Tag cannot exceed 50 characters".to_string()));
+    }
+    // A tag is addressed by its slug, so it needs letters or digits to make one
+    if slug::try_slugify(tag).is_none() {
+        return Err(AppError::Validation(format!(
+            "Tag '{}' needs at least one letter or digit",
+            tag
+        )));
+    }
+    Ok(())
+}
+
+/// Slugs set by hand are lowercase ASCII letters and digits separated by
+/// single dashes. They may be longer than generated ones.
+fn validate_slug(slug: &str) -> AppResult<()> {
+    if !slug::is_valid(slug) {
+        return Err(AppError::Validation(format!(
+            "Invalid slug '{}': use lowercase letters, digits and single dashes, at most {} characters",
+            slug,
+            slug::MAX_CUSTOM_SLUG_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// The status and window an update ends up with. Without a status the post
+/// keeps its own, and its window unless the request moves it.
+fn updated_status(
+    status: Option<PostStatus>,
+    payload: &CreateBlogPostRequest,
+    stored: &BlogPostKv,
+) -> (PostStatus, Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+    match status {
+        Some(status) => (status, payload.publish_at, payload.unpublish_at),
+        None => (
+            PostStatus::parse(&stored.status).unwrap_or(PostStatus::Published),
+            payload.publish_at.or(parse_time(stored.publish_at.as_deref())),
+            payload.unpublish_at.or(parse_time(stored.unpublish_at.as_deref())),
+        ),
+    }
+}
+
+fn validate_publish_window(
+    status: PostStatus,
+    publish_at: Option<DateTime<Utc>>,
+    unpublish_at: Option<DateTime<Utc>>,
+) -> AppResult<()> {
+    if status == PostStatus::Scheduled && publish_at.is_none() {
+        return Err(AppError::Validation("Scheduled posts need a publish_at time".to_string()));
+    }
+    if let (Some(publish_at), Some(unpublish_at)) = (publish_at, unpublish_at)
+        && unpublish_at <= publish_at
+    {
+        return Err(AppError::Validation("unpublish_at must be after publish_at".to_string()));
+    }
+    Ok(())
+}
+
+fn parse_time(at: Option<&str>) -> Option<DateTime<Utc>> {
+    at.and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+        .map(|at| at.with_timezone(&Utc))
+}
+
+/// Set a post's status and publish window. `date_published` becomes the
+/// go-live time when a post is scheduled or first published.
+fn apply_status(
+    blog_post: &mut BlogPostKv,
+    status: PostStatus,
+    publish_at: Option<DateTime<Utc>>,
+    unpublish_at: Option<DateTime<Utc>>,
+) {
+    let was_published = blog_post.status == PostStatus::Published.as_str();
+    match status {
+        PostStatus::Scheduled => {
+            if let Some(publish_at) = publish_at {
+                blog_post.date_published = publish_at.to_rfc3339();
+            }
+        }
+        PostStatus::Published if !was_published => {
+            blog_post.date_published = publish_at.unwrap_or_else(Utc::now).to_rfc3339();
+        }
+        _ => {}
+    }
+
+    blog_post.status = status.as_str().to_string();
+    blog_post.publish_at = publish_at.map(|at| at.to_rfc3339());
+    blog_post.unpublish_at = unpublish_at.map(|at| at.to_rfc3339());
+}
+
+// Scheduled and past publish_at, or published and past unpublish_at
+fn is_due(
+    status: &str,
+    publish_at: &Option<String>,
+    unpublish_at: &Option<String>,
+    now: DateTime<Utc>,
+) -> bool {
+    (status == PostStatus::Scheduled.as_str() && has_passed(publish_at.as_deref(), now))
+        || (status == PostStatus::Published.as_str() && has_passed(unpublish_at.as_deref(), now))
+}
@@ -4,7 +4,31 @@ use std::env;
 #[derive(Debug, Deserialize, Clone)]
 pub struct AppConfig {
     pub database_url: String,
-    pub jwt_secret: String,
+    pub jwt_secret: String, // HS256 key used when `jwt_keys` is empty
+    pub jwt_keys: Vec<String>, // "kid:alg:path[:not_after]"; alg is HS256, RS256 or EdDSA
+    pub jwt_active_kid: String, // Key that signs new tokens
+    pub access_token_ttl_minutes: u32, // Also the session cookie Max-Age
+    pub refresh_token_ttl_days: u32,
+    pub impersonation_ttl_minutes: u32,
+    // Two-factor authentication
+    pub admin_totp_required: bool, // Admins must enroll in TOTP before getting a session
+    pub totp_issuer: String, // Shown by authenticator apps
+    pub mfa_challenge_ttl_minutes: u32, // Time to complete the second login step
+    // Session cookie policy; unset values follow `environment`
+    pub session_cookie_name: String,
+    pub cookie_domain: Option<String>, // Empty for a host-only cookie
+    pub cookie_path: String,
+    pub cookie_same_site: Option<String>, // "Strict", "Lax" or "None"
+    pub cookie_secure: Option<bool>,
+    pub cookie_host_prefix: bool, // Use __Host- names: Secure, Path=/, no Domain
+    // Throttling of the authentication endpoints
+    pub rate_limit_backend: String, // "memory" or "sqlite" (shared across instances)
+    pub auth_rate_limit_ip_window_seconds: u32,
+    pub auth_rate_limit_ip_burst: u32, // Attempts allowed per IP per window
+    pub auth_rate_limit_identity_window_seconds: u32,
+    pub auth_rate_limit_identity_burst: u32, // Attempts allowed per email, user or token per window
+    pub auth_lockout_seconds: u32, // Minimum lockout once a burst is exceeded
+    pub trusted_proxies: Vec<String>, // IPs or CIDR ranges whose forwarding headers name the client
     pub server_port: u16,
     pub upload_dir: String,
     pub environment: String,
@@ -17,6 +41,29 @@ pub struct AppConfig {
     pub google_client_id: Option<String>,
     pub google_client_secret: Option<String>,
     pub google_redirect_uri: String,
+    pub google_authorize_endpoint: String,
+    pub google_token_endpoint: String,
+    pub google_jwks_uri: String,
+    pub google_userinfo_endpoint: String,
+    pub oauth_state_ttl_seconds: u32,
+    // Further OpenID Connect providers (Microsoft Entra, ...), from OIDC_PROVIDERS
+    #[serde(default)]
+    pub oidc_providers: Vec<OidcProviderConfig>,
+    // Sign-up policy for first-time Google logins
+    pub signup_mode: String, // "open" or "closed"
+    pub signup_allowed_domains: Vec<String>, // Empty allows any domain
+    pub signup_role_rules: Vec<String>, // "@domain=role" or "email=role"
+    pub signup_default_role: String,
+    // Magic-link login and outgoing mail
+    pub magic_link_url: String, // Frontend page that receives ?token=
+    pub magic_link_ttl_minutes: u32,
+    pub mail_transport: String, // "smtp" or "file"
+    pub mail_from: String,
+    pub mail_outbox_dir: String,
+    pub smtp_host: Option<String>,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
     // SharePoint/MS Graph configuration
     pub sharepoint_tenant_id: Option<String>,
     pub sharepoint_client_id: Option<String>,
@@ -27,6 +74,11 @@ pub struct AppConfig {
     pub backup_enabled: bool,
     pub backup_schedule: String, // Cron expression
     pub backup_retention_days: u32,
+    // Revocation list maintenance
+    pub revocation_prune_schedule: String, // Cron expression
+
+    // Blog publishing
+    pub publish_schedule: String, // Cron expression for going live/archiving posts
 }
 
 impl Default for AppConfig {
@@ -34,6 +86,27 @@ impl Default for AppConfig {
         Self {
             database_url: "sqlite:cms.db".to_string(),
             jwt_secret: "your-super-secret-jwt-key-change-in-production".to_string(),
+            jwt_keys: Vec::new(),
+            jwt_active_kid: "default".to_string(),
+            access_token_ttl_minutes: 15,
+            refresh_token_ttl_days: 30,
+            impersonation_ttl_minutes: 30,
+            admin_totp_required: false,
+            totp_issuer: "LLA Portal".to_string(),
+            mfa_challenge_ttl_minutes: 5,
+            session_cookie_name: "session".to_string(),
+            cookie_domain: None,
+            cookie_path: "/".to_string(),
+            cookie_same_site: None,
+            cookie_secure: None,
+            cookie_host_prefix: false,
+            rate_limit_backend: "memory".to_string(),
+            auth_rate_limit_ip_window_seconds: 60,
+            auth_rate_limit_ip_burst: 20,
+            auth_rate_limit_identity_window_seconds: 15 * 60,
+            auth_rate_limit_identity_burst: 5,
+            auth_lockout_seconds: 15 * 60,
+            trusted_proxies: Vec::new(),
             server_port: 3001,
             upload_dir: "uploads".to_string(),
             environment: "development".to_string(),
@@ -45,6 +118,26 @@ impl Default for AppConfig {
             google_client_id: None,
             google_client_secret: None,
             google_redirect_uri: "http://localhost:3001/auth/google/callback".to_string(),
+            google_authorize_endpoint: "https://accounts.google.com/o/oauth2/v2/auth".to_string(),
+            google_token_endpoint: "https://oauth2.googleapis.com/token".to_string(),
+            google_jwks_uri: "https://www.googleapis.com/oauth2/v3/certs".to_string(),
+            google_userinfo_endpoint: "https://openidconnect.googleapis.com/v1/userinfo"
+                .to_string(),
+            oauth_state_ttl_seconds: 600, // 10 minutes
+            oidc_providers: Vec::new(),
+            signup_mode: "open".to_string(),
+            signup_allowed_domains: Vec::new(),
+            signup_role_rules: Vec::new(),
+            signup_default_role: "student".to_string(),
+            magic_link_url: "http://localhost:5173/login/verify".to_string(),
+            magic_link_ttl_minutes: 15,
+            mail_transport: "file".to_string(),
+            mail_from: "LLA Portal <no-reply@llacademy.ng>".to_string(),
+            mail_outbox_dir: "mail_outbox".to_string(),
+            smtp_host: None,
+            smtp_port: 587,
+            smtp_username: None,
+            smtp_password: None,
             sharepoint_tenant_id: None,
             sharepoint_client_id: None,
             sharepoint_client_secret: None,
@@ -53,6 +146,8 @@ impl Default for AppConfig {
             backup_enabled: false,
             backup_schedule: "0 0 2 * * *".to_string(), // Daily at 2 AM
             backup_retention_days: 30,
+            revocation_prune_schedule: "0 15 * * * *".to_string(), // Hourly
+            publish_schedule: "0 * * * * *".to_string(),            // Every minute
         }
     }
 }
@@ -65,6 +160,24 @@ impl AppConfig {
                 "jwt_secret",
                 "your-super-secret-jwt-key-change-in-production",
             )?
+            .set_default("jwt_keys", Vec::<String>::new())?
+            .set_default("jwt_active_kid", "default")?
+            .set_default("access_token_ttl_minutes", 15)?
+            .set_default("refresh_token_ttl_days", 30)?
+            .set_default("impersonation_ttl_minutes", 30)?
+            .set_default("admin_totp_required", false)?
+            .set_default("totp_issuer", "LLA Portal")?
+            .set_default("mfa_challenge_ttl_minutes", 5)?
+            .set_default("session_cookie_name", "session")?
+            .set_default("cookie_path", "/")?
+            .set_default("cookie_host_prefix", false)?
+            .set_default("rate_limit_backend", "memory")?
+            .set_default("auth_rate_limit_ip_window_seconds", 60)?
+            .set_default("auth_rate_limit_ip_burst", 20)?
+            .set_default("auth_rate_limit_identity_window_seconds", 900)?
+            .set_default("auth_rate_limit_identity_burst", 5)?
+            .set_default("auth_lockout_seconds", 900)?
+            .set_default("trusted_proxies", Vec::<String>::new())?
             .set_default("server_port", 3001)?
             .set_default("upload_dir", "uploads")?
             .set_default("environment", "development")?
@@ -78,9 +191,35 @@ impl AppConfig {
             )?
             .set_default("media_domain", "media.llacademy.ng")?
             .set_default("google_redirect_uri", "http://localhost:3001/auth/google/callback")?
+            .set_default(
+                "google_authorize_endpoint",
+                "https://accounts.google.com/o/oauth2/v2/auth",
+            )?
+            .set_default("google_token_endpoint", "https://oauth2.googleapis.com/token")?
+            .set_default(
+                "google_jwks_uri",
+                "https://www.googleapis.com/oauth2/v3/certs",
+            )?
+            .set_default(
+                "google_userinfo_endpoint",
+                "https://openidconnect.googleapis.com/v1/userinfo",
+            )?
+            .set_default("oauth_state_ttl_seconds", 600)?
+            .set_default("signup_mode", "open")?
+            .set_default("signup_allowed_domains", Vec::<String>::new())?
+            .set_default("signup_role_rules", Vec::<String>::new())?
+            .set_default("signup_default_role", "student")?
+            .set_default("magic_link_url", "http://localhost:5173/login/verify")?
+            .set_default("magic_link_ttl_minutes", 15)?
+            .set_default("mail_transport", "file")?
+            .set_default("mail_from", "LLA Portal <no-reply@llacademy.ng>")?
+            .set_default("mail_outbox_dir", "mail_outbox")?
+            .set_default("smtp_port", 587)?
             .set_default("backup_enabled", false)?
             .set_default("backup_schedule", "0 0 2 * * *")?
-            .set_default("backup_retention_days", 30)?;
+            .set_default("backup_retention_days", 30)?
+            .set_default("revocation_prune_schedule", "0 15 * * * *")?
+            .set_default("publish_schedule", "0 * * * * *")?;
 
         // Override with environment variables if they exist
         if let Ok(db_url) = env::var("DATABASE_URL") {
@@ -89,10 +228,104 @@ impl AppConfig {
         if let Ok(jwt_secret) = env::var("JWT_SECRET") {
             builder = builder.set_override("jwt_secret", jwt_secret)?;
         }
-        if let Ok(port) = env::var("PORT") {
-            if let Ok(port_num) = port.parse::<u16>() {
-                builder = builder.set_override("server_port", port_num)?;
-            }
+        if let Ok(jwt_keys) = env::var("JWT_KEYS") {
+            builder = builder.set_override("jwt_keys", split_list(&jwt_keys))?;
+        }
+        if let Ok(jwt_active_kid) = env::var("JWT_ACTIVE_KID") {
+            builder = builder.set_override("jwt_active_kid", jwt_active_kid)?;
+        }
+        if let Ok(access_ttl) = env::var("ACCESS_TOKEN_TTL_MINUTES")
+            && let Ok(minutes) = access_ttl.parse::<u32>()
+        {
+            builder = builder.set_override("access_token_ttl_minutes", minutes)?;
+        }
+        if let Ok(refresh_ttl) = env::var("REFRESH_TOKEN_TTL_DAYS")
+            && let Ok(days) = refresh_ttl.parse::<u32>()
+        {
+            builder = builder.set_override("refresh_token_ttl_days", days)?;
+        }
+        if let Ok(impersonation_ttl) = env::var("IMPERSONATION_TTL_MINUTES")
+            && let Ok(minutes) = impersonation_ttl.parse::<u32>()
+        {
+            builder = builder.set_override("impersonation_ttl_minutes", minutes)?;
+        }
+
+        // Two-factor authentication
+        if let Ok(admin_totp_required) = env::var("ADMIN_TOTP_REQUIRED")
+            && let Ok(required) = admin_totp_required.parse::<bool>()
+        {
+            builder = builder.set_override("admin_totp_required", required)?;
+        }
+        if let Ok(totp_issuer) = env::var("TOTP_ISSUER") {
+            builder = builder.set_override("totp_issuer", totp_issuer)?;
+        }
+        if let Ok(mfa_challenge_ttl) = env::var("MFA_CHALLENGE_TTL_MINUTES")
+            && let Ok(minutes) = mfa_challenge_ttl.parse::<u32>()
+        {
+            builder = builder.set_override("mfa_challenge_ttl_minutes", minutes)?;
+        }
+
+        // Session cookie policy
+        if let Ok(cookie_name) = env::var("SESSION_COOKIE_NAME") {
+            builder = builder.set_override("session_cookie_name", cookie_name)?;
+        }
+        if let Ok(cookie_domain) = env::var("COOKIE_DOMAIN") {
+            builder = builder.set_override("cookie_domain", cookie_domain)?;
+        }
+        if let Ok(cookie_path) = env::var("COOKIE_PATH") {
+            builder = builder.set_override("cookie_path", cookie_path)?;
+        }
+        if let Ok(same_site) = env::var("COOKIE_SAME_SITE") {
+            builder = builder.set_override("cookie_same_site", same_site)?;
+        }
+        if let Ok(cookie_secure) = env::var("COOKIE_SECURE")
+            && let Ok(secure) = cookie_secure.parse::<bool>()
+        {
+            builder = builder.set_override("cookie_secure", secure)?;
+        }
+        if let Ok(host_prefix) = env::var("COOKIE_HOST_PREFIX")
+            && let Ok(enabled) = host_prefix.parse::<bool>()
+        {
+            builder = builder.set_override("cookie_host_prefix", enabled)?;
+        }
+
+        // Authentication rate limits
+        if let Ok(backend) = env::var("RATE_LIMIT_BACKEND") {
+            builder = builder.set_override("rate_limit_backend", backend)?;
+        }
+        if let Ok(rate_limit_ip_window_seconds) = env::var("AUTH_RATE_LIMIT_IP_WINDOW_SECONDS")
+            && let Ok(seconds) = rate_limit_ip_window_seconds.parse::<u32>()
+        {
+            builder = builder.set_override("auth_rate_limit_ip_window_seconds", seconds)?;
+        }
+        if let Ok(rate_limit_ip_burst) = env::var("AUTH_RATE_LIMIT_IP_BURST")
+            && let Ok(burst) = rate_limit_ip_burst.parse::<u32>()
+        {
+            builder = builder.set_override("auth_rate_limit_ip_burst", burst)?;
+        }
+        if let Ok(rate_limit_identity_window_seconds) = env::var("AUTH_RATE_LIMIT_IDENTITY_WINDOW_SECONDS")
+            && let Ok(seconds) = rate_limit_identity_window_seconds.parse::<u32>()
+        {
+            builder = builder.set_override("auth_rate_limit_identity_window_seconds", seconds)?;
+        }
+        if let Ok(rate_limit_identity_burst) = env::var("AUTH_RATE_LIMIT_IDENTITY_BURST")
+            && let Ok(burst) = rate_limit_identity_burst.parse::<u32>()
+        {
+            builder = builder.set_override("auth_rate_limit_identity_burst", burst)?;
+        }
+        if let Ok(lockout_seconds) = env::var("AUTH_LOCKOUT_SECONDS")
+            && let Ok(seconds) = lockout_seconds.parse::<u32>()
+        {
+            builder = builder.set_override("auth_lockout_seconds", seconds)?;
+        }
+        if let Ok(trusted_proxies) = env::var("TRUSTED_PROXIES") {
+            builder = builder.set_override("trusted_proxies", split_list(&trusted_proxies))?;
+        }
+
+        if let Ok(port) = env::var("PORT")
+            && let Ok(port_num) = port.parse::<u16>()
+        {
+            builder = builder.set_override("server_port", port_num)?;
         }
         if let Ok(upload_dir) = env::var("UPLOAD_DIR") {
             builder = builder.set_override("upload_dir", upload_dir)?;
@@ -126,6 +359,70 @@ impl AppConfig {
         if let Ok(google_redirect_uri) = env::var("GOOGLE_REDIRECT_URI") {
             builder = builder.set_override("google_redirect_uri", google_redirect_uri)?;
         }
+        if let Ok(google_authorize_endpoint) = env::var("GOOGLE_AUTHORIZE_ENDPOINT") {
+            builder = builder.set_override("google_authorize_endpoint", google_authorize_endpoint)?;
+        }
+        if let Ok(google_token_endpoint) = env::var("GOOGLE_TOKEN_ENDPOINT") {
+            builder = builder.set_override("google_token_endpoint", google_token_endpoint)?;
+        }
+        if let Ok(google_jwks_uri) = env::var("GOOGLE_JWKS_URI") {
+            builder = builder.set_override("google_jwks_uri", google_jwks_uri)?;
+        }
+        if let Ok(google_userinfo_endpoint) = env::var("GOOGLE_USERINFO_ENDPOINT") {
+            builder = builder.set_override("google_userinfo_endpoint", google_userinfo_endpoint)?;
+        }
+        if let Ok(oauth_state_ttl) = env::var("OAUTH_STATE_TTL_SECONDS")
+            && let Ok(seconds) = oauth_state_ttl.parse::<u32>()
+        {
+            builder = builder.set_override("oauth_state_ttl_seconds", seconds)?;
+        }
+
+        // Sign-up policy; lists are comma-separated
+        if let Ok(signup_mode) = env::var("SIGNUP_MODE") {
+            builder = builder.set_override("signup_mode", signup_mode)?;
+        }
+        if let Ok(allowed_domains) = env::var("SIGNUP_ALLOWED_DOMAINS") {
+            builder = builder.set_override("signup_allowed_domains", split_list(&allowed_domains))?;
+        }
+        if let Ok(role_rules) = env::var("SIGNUP_ROLE_RULES") {
+            builder = builder.set_override("signup_role_rules", split_list(&role_rules))?;
+        }
+        if let Ok(default_role) = env::var("SIGNUP_DEFAULT_ROLE") {
+            builder = builder.set_override("signup_default_role", default_role)?;
+        }
+
+        // Magic-link and mail configuration
+        if let Ok(magic_link_url) = env::var("MAGIC_LINK_URL") {
+            builder = builder.set_override("magic_link_url", magic_link_url)?;
+        }
+        if let Ok(magic_link_ttl) = env::var("MAGIC_LINK_TTL_MINUTES")
+            && let Ok(minutes) = magic_link_ttl.parse::<u32>()
+        {
+            builder = builder.set_override("magic_link_ttl_minutes", minutes)?;
+        }
+        if let Ok(mail_transport) = env::var("MAIL_TRANSPORT") {
+            builder = builder.set_override("mail_transport", mail_transport)?;
+        }
+        if let Ok(mail_from) = env::var("MAIL_FROM") {
+            builder = builder.set_override("mail_from", mail_from)?;
+        }
+        if let Ok(mail_outbox_dir) = env::var("MAIL_OUTBOX_DIR") {
+            builder = builder.set_override("mail_outbox_dir", mail_outbox_dir)?;
+        }
+        if let Ok(smtp_host) = env::var("SMTP_HOST") {
+            builder = builder.set_override("smtp_host", smtp_host)?;
+        }
+        if let Ok(smtp_port) = env::var("SMTP_PORT")
+            && let Ok(port) = smtp_port.parse::<u16>()
+        {
+            builder = builder.set_override("smtp_port", port)?;
+        }
+        if let Ok(smtp_username) = env::var("SMTP_USERNAME") {
+            builder = builder.set_override("smtp_username", smtp_username)?;
+        }
+        if let Ok(smtp_password) = env::var("SMTP_PASSWORD") {
+            builder = builder.set_override("smtp_password", smtp_password)?;
+        }
 
         // SharePoint configuration
         if let Ok(sharepoint_tenant_id) = env::var("SHAREPOINT_TENANT_ID") {
@@ -145,20 +442,79 @@ impl AppConfig {
         }
 
         // Backup configuration
-        if let Ok(backup_enabled) = env::var("BACKUP_ENABLED") {
-            if let Ok(enabled) = backup_enabled.parse::<bool>() {
-                builder = builder.set_override("backup_enabled", enabled)?;
-            }
+        if let Ok(backup_enabled) = env::var("BACKUP_ENABLED")
+            && let Ok(enabled) = backup_enabled.parse::<bool>()
+        {
+            builder = builder.set_override("backup_enabled", enabled)?;
         }
         if let Ok(backup_schedule) = env::var("BACKUP_SCHEDULE") {
             builder = builder.set_override("backup_schedule", backup_schedule)?;
         }
-        if let Ok(backup_retention_days) = env::var("BACKUP_RETENTION_DAYS") {
-            if let Ok(days) = backup_retention_days.parse::<u32>() {
-                builder = builder.set_override("backup_retention_days", days)?;
-            }
+        if let Ok(backup_retention_days) = env::var("BACKUP_RETENTION_DAYS")
+            && let Ok(days) = backup_retention_days.parse::<u32>()
+        {
+            builder = builder.set_override("backup_retention_days", days)?;
         }
 
-        builder.build()?.try_deserialize()
+        // Revocation list maintenance
+        if let Ok(revocation_prune_schedule) = env::var("REVOCATION_PRUNE_SCHEDULE") {
+            builder = builder.set_override("revocation_prune_schedule", revocation_prune_schedule)?;
+        }
+
+        // Blog publishing
+        if let Ok(publish_schedule) = env::var("PUBLISH_SCHEDULE") {
+            builder = builder.set_override("publish_schedule", publish_schedule)?;
+        }
+
+        let mut config: AppConfig = builder.build()?.try_deserialize()?;
+        config.oidc_providers = oidc_providers_from_env();
+        Ok(config)
     }
 }
+
+/// An OpenID Connect provider whose endpoints come from its discovery document
+#[derive(Debug, Deserialize, Clone)]
+pub struct OidcProviderConfig {
+    pub name: String, // Used in the login URLs, e.g. /api/auth/oidc/microsoft
+    pub issuer: String, // Discovery document is at {issuer}/.well-known/openid-configuration
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+    pub scopes: String, // Space-separated; must include "openid"
+    pub trust_email: bool, // Treat the email as verified when the provider sends no email_verified
+}
+
+/// Read `OIDC_PROVIDERS=name,...` and each provider's `OIDC_<NAME>_*` settings
+fn oidc_providers_from_env() -> Vec<OidcProviderConfig> {
+    let Ok(names) = env::var("OIDC_PROVIDERS") else {
+        return Vec::new();
+    };
+
+    split_list(&names)
+        .into_iter()
+        .map(|name| {
+            let prefix = format!("OIDC_{}_", name.to_uppercase().replace('-', "_"));
+            let var = |key: &str| env::var(format!("{}{}", prefix, key)).ok();
+            OidcProviderConfig {
+                issuer: var("ISSUER").unwrap_or_default(),
+                client_id: var("CLIENT_ID").unwrap_or_default(),
+                client_secret: var("CLIENT_SECRET"),
+                redirect_uri: var("REDIRECT_URI").unwrap_or_default(),
+                scopes: var("SCOPES").unwrap_or_else(|| "openid email profile".to_string()),
+                trust_email: var("TRUST_EMAIL")
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(false),
+                name: name.to_lowercase(),
+            }
+        })
+        .collect()
+}
+
+/// Split a comma-separated environment value, dropping empty items
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
@@ -0,0 +1,219 @@
+use crate::config::AppConfig;
+use crate::error::{AppError, AppResult};
+
+/// Base name of the cookie carrying the opaque refresh token
+pub const REFRESH_COOKIE: &str = "refresh_token";
+
+/// Base name of the readable cookie carrying the CSRF token
+pub const CSRF_COOKIE: &str = "csrf_token";
+
+/// Base name of the cookie binding an OAuth state to the browser that
+/// started the login
+pub const OAUTH_STATE_COOKIE: &str = "oauth_state";
+
+/// The refresh token and OAuth state are only ever sent to the auth endpoints
+const REFRESH_COOKIE_PATH: &str = "/api/auth";
+
+/// Production cookies are shared with the SvelteKit site on the apex domain
+const PRODUCTION_COOKIE_DOMAIN: &str = ".llacademy.ng";
+
+/// Names and attributes of the cookies set at login, from the `cookie_*`
+/// settings. Unset attributes follow the environment: development gets
+/// host-only `SameSite=Lax` cookies over plain HTTP, everything else gets
+/// `Secure; SameSite=None` cookies on `.llacademy.ng`.
+#[derive(Debug, Clone)]
+pub struct CookiePolicy {
+    session_name: String,
+    refresh_name: String,
+    csrf_name: String,
+    oauth_state_name: String,
+    domain: Option<String>,
+    path: String,
+    same_site: &'static str,
+    secure: bool,
+    access_max_age: i64,  // Matches the access JWT lifetime
+    refresh_max_age: i64, // Matches the refresh token lifetime
+}
+
+impl CookiePolicy {
+    /// Build the policy from the cookie settings, rejecting combinations
+    /// browsers would silently drop
+    pub fn from_config(config: &AppConfig) -> AppResult<Self> {
+        let development = config.environment == "development";
+
+        let same_site = match config.cookie_same_site.as_deref() {
+            None if development => "Lax",
+            None => "None",
+            Some(value) => match value.to_ascii_lowercase().as_str() {
+                "strict" => "Strict",
+                "lax" => "Lax",
+                "none" => "None",
+                _ => return Err(config_error(format!("Invalid cookie_same_site: {}", value))),
+            },
+        };
+        let secure = config.cookie_secure.unwrap_or(!development);
+        let domain = match config.cookie_domain.as_deref().map(str::trim) {
+            None if development => None,
+            None => Some(PRODUCTION_COOKIE_DOMAIN.to_string()),
+            Some("") => None,
+            Some(domain) => Some(domain.to_string()),
+        };
+
+        if config.session_cookie_name.is_empty() {
+            return Err(config_error("session_cookie_name must not be empty".to_string()));
+        }
+        if !config.cookie_path.starts_with('/') {
+            return Err(config_error(format!(
+                "cookie_path must start with '/': {}",
+                config.cookie_path
+            )));
+        }
+        if same_site == "None" && !secure {
+            return Err(config_error(
+                "cookie_same_site=None requires cookie_secure".to_string(),
+            ));
+        }
+
+        if config.cookie_host_prefix {
+            // __Host- cookies must be Secure, host-only and scoped to "/"
+            if !secure {
+                return Err(config_error("cookie_host_prefix requires cookie_secure".to_string()));
+            }
+            if config.cookie_path != "/" {
+                return Err(config_error("cookie_host_prefix requires cookie_path=/".to_string()));
+            }
+            if config.cookie_domain.as_deref().is_some_and(|d| !d.trim().is_empty()) {
+                return Err(config_error(
+                    "cookie_host_prefix cannot be combined with cookie_domain".to_string(),
+                ));
+            }
+
+            return Ok(Self {
+                session_name: format!("__Host-{}", config.session_cookie_name),
+                // Scoped to the auth endpoints, so only eligible for __Secure-
+                refresh_name: format!("__Secure-{}", REFRESH_COOKIE),
+                csrf_name: format!("__Host-{}", CSRF_COOKIE),
+                oauth_state_name: format!("__Secure-{}", OAUTH_STATE_COOKIE),
+                domain: None,
+                path: "/".to_string(),
+                same_site,
+                secure,
+                access_max_age: access_max_age(config),
+                refresh_max_age: refresh_max_age(config),
+            });
+        }
+
+        Ok(Self {
+            session_name: config.session_cookie_name.clone(),
+            refresh_name: REFRESH_COOKIE.to_string(),
+            csrf_name: CSRF_COOKIE.to_string(),
+            oauth_state_name: OAUTH_STATE_COOKIE.to_string(),
+            domain,
+            path: config.cookie_path.clone(),
+            same_site,
+            secure,
+            access_max_age: access_max_age(config),
+            refresh_max_age: refresh_max_age(config),
+        })
+    }
+
+    /// Name of the cookie carrying the access JWT
+    pub fn session_name(&self) -> &str {
+        &self.session_name
+    }
+
+    pub fn refresh_name(&self) -> &str {
+        &self.refresh_name
+    }
+
+    pub fn csrf_name(&self) -> &str {
+        &self.csrf_name
+    }
+
+    pub fn oauth_state_name(&self) -> &str {
+        &self.oauth_state_name
+    }
+
+    pub fn session_cookie(&self, token: &str) -> String {
+        self.build(&self.session_name, token, &self.path, true, self.access_max_age)
+    }
+
+    pub fn refresh_cookie(&self, token: &str) -> String {
+        self.build(&self.refresh_name, token, REFRESH_COOKIE_PATH, true, self.refresh_max_age)
+    }
+
+    /// Not HttpOnly: the frontend reads it and echoes it in `X-CSRF-Token`.
+    /// Kept as long as the refresh cookie, since refreshing needs it too.
+    pub fn csrf_cookie(&self, csrf_token: &str) -> String {
+        self.build(&self.csrf_name, csrf_token, &self.path, false, self.refresh_max_age)
+    }
+
+    /// Hash of the OAuth state, checked on the callback. Always
+    /// `SameSite=Lax` so it survives the redirect back from the provider.
+    pub fn oauth_state_cookie(&self, state_hash: &str, max_age: i64) -> String {
+        self.build_with_same_site(
+            &self.oauth_state_name,
+            state_hash,
+            REFRESH_COOKIE_PATH,
+            true,
+            "Lax",
+            max_age,
+        )
+    }
+
+    pub fn clear_oauth_state_cookie(&self) -> String {
+        self.build_with_same_site(&self.oauth_state_name, "", REFRESH_COOKIE_PATH, true, "Lax", 0)
+    }
+
+    pub fn clear_session_cookie(&self) -> String {
+        self.build(&self.session_name, "", &self.path, true, 0)
+    }
+
+    pub fn clear_refresh_cookie(&self) -> String {
+        self.build(&self.refresh_name, "", REFRESH_COOKIE_PATH, true, 0)
+    }
+
+    pub fn clear_csrf_cookie(&self) -> String {
+        self.build(&self.csrf_name, "", &self.path, false, 0)
+    }
+
+    fn build(&self, name: &str, value: &str, path: &str, http_only: bool, max_age: i64) -> String {
+        self.build_with_same_site(name, value, path, http_only, self.same_site, max_age)
+    }
+
+    fn build_with_same_site(
+        &self,
+        name: &str,
+        value: &str,
+        path: &str,
+        http_only: bool,
+        same_site: &str,
+        max_age: i64,
+    ) -> String {
+        let mut cookie = format!("{}={}", name, value);
+        if let Some(domain) = &self.domain {
+            cookie.push_str(&format!("; Domain={}", domain));
+        }
+        cookie.push_str(&format!("; Path={}", path));
+        if self.secure {
+            cookie.push_str("; Secure");
+        }
+        if http_only {
+            cookie.push_str("; HttpOnly");
+        }
+        cookie.push_str(&format!("; SameSite={}; Max-Age={}", same_site, max_age));
+        cookie
+    }
+}
+
+fn access_max_age(config: &AppConfig) -> i64 {
+    config.access_token_ttl_minutes as i64 * 60
+}
+
+fn refresh_max_age(config: &AppConfig) -> i64 {
+    config.refresh_token_ttl_days as i64 * 24 * 60 * 60
+}
+
+fn config_error(message: String) -> AppError {
+    AppError::Config(config::ConfigError::Message(message))
+}
@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -38,12 +38,30 @@ pub enum AppError {
     #[error("Migration error: {0}")]
     Migration(#[from] sqlx::migrate::MigrateError),
 
+    #[error("Missing permission: {0}")]
+    MissingPermission(String),
+
+    #[error("Not allowed while impersonating: {0}")]
+    Impersonation(String),
+
+    #[error("Sign-up rejected: {0}")]
+    SignupRejected(String),
+
     #[error("Not found: {0}")]
     NotFound(String),
 
     #[error("Conflict: {0}")]
     Conflict(String),
 
+    #[error("Too many requests; retry after {0} seconds")]
+    RateLimited(u64), // Seconds until the client may retry
+
+    #[error("CSRF error: {0}")]
+    Csrf(String),
+
+    #[error("OAuth state error: {0}")]
+    OAuthState(String),
+
     #[error("Internal server error")]
     Internal(String),
 
@@ -68,8 +86,14 @@ impl IntoResponse for AppError {
             AppError::Config(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Configuration error"),
             AppError::Io(_) => (StatusCode::INTERNAL_SERVER_ERROR, "IO error"),
             AppError::Migration(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Migration error"),
+            AppError::MissingPermission(_) => (StatusCode::FORBIDDEN, "Insufficient permissions"),
+            AppError::Impersonation(_) => (StatusCode::FORBIDDEN, "Not allowed while impersonating"),
+            AppError::SignupRejected(msg) => (StatusCode::FORBIDDEN, msg.as_str()),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.as_str()),
             AppError::Conflict(msg) => (StatusCode::CONFLICT, msg.as_str()),
+            AppError::RateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, "Too many requests"),
+            AppError::Csrf(_) => (StatusCode::FORBIDDEN, "Invalid CSRF token"),
+            AppError::OAuthState(_) => (StatusCode::BAD_REQUEST, "Invalid or expired OAuth state"),
             AppError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.as_str()),
             AppError::Anyhow(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Internal error"),
             AppError::Reqwest(_) => (StatusCode::INTERNAL_SERVER_ERROR, "HTTP request error"),
@@ -85,6 +109,10 @@ impl IntoResponse for AppError {
             }
         }));
 
+        if let AppError::RateLimited(retry_after) = self {
+            return (status, [(header::RETRY_AFTER, retry_after.to_string())], body).into_response();
+        }
+
         (status, body).into_response()
     }
 }
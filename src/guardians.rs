@@ -0,0 +1,184 @@
+use crate::audit::AuditService;
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    GuardianRelationship, Guardianship, LinkGuardianRequest, LinkedUserResponse, User, UserRole,
+};
+use chrono::Utc;
+use serde_json::json;
+use sqlx::SqlitePool;
+
+/// Service for the parent-student links that scope portal data to a family.
+/// Links are made and removed by admins and audited under them.
+pub struct GuardianService {
+    pub db: SqlitePool,
+    pub audit: AuditService,
+}
+
+impl GuardianService {
+    pub fn new(db: SqlitePool) -> Self {
+        let audit = AuditService::new(db.clone());
+        Self { db, audit }
+    }
+
+    /// Link a parent to a student, or update the existing link. Making a
+    /// guardian the primary contact takes the flag from any other guardian.
+    pub async fn link(
+        &self,
+        payload: LinkGuardianRequest,
+        actor_id: &str,
+    ) -> AppResult<Guardianship> {
+        let relationship =
+            GuardianRelationship::parse(&payload.relationship).ok_or_else(|| {
+                AppError::Validation(format!("Invalid relationship: {}", payload.relationship))
+            })?;
+        self.ensure_role(&payload.guardian_id, UserRole::Parent)
+            .await?;
+        self.ensure_role(&payload.student_id, UserRole::Student)
+            .await?;
+
+        let before = self
+            .get_link(&payload.guardian_id, &payload.student_id)
+            .await?;
+        let link = Guardianship {
+            guardian_id: payload.guardian_id,
+            student_id: payload.student_id,
+            relationship: relationship.as_str().to_string(),
+            is_primary_contact: payload.is_primary_contact,
+            created_by: before
+                .as_ref()
+                .map_or_else(|| actor_id.to_string(), |b| b.created_by.clone()),
+            created_at: before.as_ref().map_or_else(Utc::now, |b| b.created_at),
+        };
+
+        let mut tx = self.db.begin().await?;
+
+        if link.is_primary_contact {
+            sqlx::query("UPDATE guardianships SET is_primary_contact = FALSE WHERE student_id = $1 AND guardian_id != $2")
+                .bind(&link.student_id)
+                .bind(&link.guardian_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        sqlx::query(
+            "INSERT INTO guardianships (guardian_id, student_id, relationship, is_primary_contact, created_by, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             ON CONFLICT(guardian_id, student_id) DO UPDATE SET
+                 relationship = excluded.relationship,
+                 is_primary_contact = excluded.is_primary_contact",
+        )
+        .bind(&link.guardian_id)
+        .bind(&link.student_id)
+        .bind(&link.relationship)
+        .bind(link.is_primary_contact)
+        .bind(&link.created_by)
+        .bind(link.created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        self.audit
+            .log_action(
+                actor_id,
+                "link_guardian".to_string(),
+                Some(link.student_id.clone()),
+                Some(json!({ "before": before, "after": link })),
+            )
+            .await?;
+
+        Ok(link)
+    }
+
+    pub async fn unlink(
+        &self,
+        guardian_id: &str,
+        student_id: &str,
+        actor_id: &str,
+    ) -> AppResult<Guardianship> {
+        let before = self
+            .get_link(guardian_id, student_id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Guardianship not found".to_string()))?;
+
+        sqlx::query("DELETE FROM guardianships WHERE guardian_id = $1 AND student_id = $2")
+            .bind(guardian_id)
+            .bind(student_id)
+            .execute(&self.db)
+            .await?;
+
+        self.audit
+            .log_action(
+                actor_id,
+                "unlink_guardian".to_string(),
+                Some(student_id.to_string()),
+                Some(json!({ "before": before, "after": null })),
+            )
+            .await?;
+
+        Ok(before)
+    }
+
+    /// Students a parent is linked to, by name
+    pub async fn children_of(&self, guardian_id: &str) -> AppResult<Vec<LinkedUserResponse>> {
+        let children = sqlx::query_as(
+            "SELECT u.id, u.email, u.full_name, g.relationship, g.is_primary_contact, g.created_at AS linked_at
+             FROM guardianships g JOIN users u ON u.id = g.student_id
+             WHERE g.guardian_id = $1
+             ORDER BY COALESCE(u.full_name, u.email)",
+        )
+        .bind(guardian_id)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(children)
+    }
+
+    /// Guardians of a student, primary contact first
+    pub async fn guardians_of(&self, student_id: &str) -> AppResult<Vec<LinkedUserResponse>> {
+        let guardians = sqlx::query_as(
+            "SELECT u.id, u.email, u.full_name, g.relationship, g.is_primary_contact, g.created_at AS linked_at
+             FROM guardianships g JOIN users u ON u.id = g.guardian_id
+             WHERE g.student_id = $1
+             ORDER BY g.is_primary_contact DESC, COALESCE(u.full_name, u.email)",
+        )
+        .bind(student_id)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(guardians)
+    }
+
+    async fn get_link(
+        &self,
+        guardian_id: &str,
+        student_id: &str,
+    ) -> AppResult<Option<Guardianship>> {
+        let link = sqlx::query_as(
+            "SELECT guardian_id, student_id, relationship, is_primary_contact, created_by, created_at
+             FROM guardianships WHERE guardian_id = $1 AND student_id = $2",
+        )
+        .bind(guardian_id)
+        .bind(student_id)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(link)
+    }
+
+    async fn ensure_role(&self, user_id: &str, role: UserRole) -> AppResult<()> {
+        let user: User = sqlx::query_as(
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("User not found: {}", user_id)))?;
+
+        if user.get_role().as_ref() != Some(&role) {
+            return Err(AppError::Validation(format!(
+                "User {} is not a {}",
+                user_id,
+                role.as_str()
+            )));
+        }
+        Ok(())
+    }
+}
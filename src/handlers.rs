@@ -1,66 +1,238 @@
 use axum::{
-    extract::{Extension, Path, State},
+    extract::{Extension, Path, Query, State},
     http::{header, HeaderMap, StatusCode},
     middleware,
-    response::{IntoResponse, Json},
-    routing::{get, post, put},
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, patch, post, put},
     Router,
 };
 use tower_http::cors::CorsLayer;
 
+use crate::api_keys::ApiKeyService;
 use crate::audit::AuditService;
-use crate::auth::AuthService;
+use crate::auth::{hash_secret_token, AuthService, CSRF_HEADER};
 use crate::backup::BackupService;
 use crate::blog::BlogService;
 use crate::error::{AppError, AppResult};
-use crate::kv::{BlogIndexEntry, BlogPostKv};
-use crate::middleware::{admin_middleware, auth_middleware, AuthUser};
+use crate::guardians::GuardianService;
+use crate::keyring::Keyring;
+use crate::kv::BlogPostKv;
+use crate::middleware::{
+    auth_middleware, auth_middleware_allowing_impersonation, extract_cookie,
+    rate_limit_middleware, require_permission, session_auth_middleware, AuthClaims, AuthUser, Impersonator,
+};
 use crate::models::{
-    AuditAction, CreateBlogPostRequest, GoogleAuthRequest, LoginRequest, User, UserResponse,
+    ApiKeyResponse, AuditAction, BlogIndexQuery, ClientInfo, BlogIndexResponse, ChangeSlugRequest, CreateApiKeyRequest, CreateBlogPostRequest, CreateUserRequest,
+    CreatedApiKeyResponse, Guardianship, ImpersonateRequest, ImpersonationResponse, LinkGuardianRequest, LinkedUserResponse, LoginOutcome,
+    LoginRequest, LoginResponse, MagicLinkVerifyRequest, MergeTagRequest, MfaChallengeRequest, MfaVerifyRequest,
+    OidcAuthRequest, OidcAuthStartResponse, OidcProvidersResponse, Permission, PostRevisionResponse,
+    PostStatus, RevisionDiffQuery, RevisionDiffResponse, RecoveryCodesResponse, RefreshTokenRequest,
+    RolePermissionsRequest, RolePermissionsResponse, SessionResponse, SuspendUserRequest,
+    TagPageResponse, TagResponse, TotpCodeRequest, TotpEnrollmentResponse, UpdateTagRequest, UpdateUserRequest, User,
+    UserListQuery, UserListResponse, UserResponse, UserRole,
 };
+use crate::oidc::OidcRegistry;
+use crate::rate_limit::AuthRateLimiter;
+use crate::revocation::{RevocationCacheMetrics, REVOCATION_CACHE};
 use crate::storage::MediaUploader;
+use crate::totp::TotpService;
+use crate::users::UserService;
 use crate::AppState;
 
 pub fn create_router(state: AppState) -> Router {
-    // Create admin routes with authentication middleware
+    // Create admin routes; each declares the permission it needs
     let admin_routes = Router::new()
         .route(
             "/api/admin/posts",
-            get(admin_list_posts).post(admin_create_post),
+            get(admin_list_posts)
+                .post(admin_create_post)
+                .route_layer(require_permission(&state, Permission::PostsWrite)),
         )
         .route(
             "/api/admin/posts/{slug}",
             get(admin_get_post)
                 .put(admin_update_post)
-                .delete(admin_delete_post),
+                .delete(admin_delete_post)
+                .route_layer(require_permission(&state, Permission::PostsWrite)),
+        )
+        .route(
+            "/api/admin/posts/{slug}/slug",
+            put(admin_change_post_slug)
+                .route_layer(require_permission(&state, Permission::PostsWrite)),
+        )
+        .route(
+            "/api/admin/posts/{slug}/revisions",
+            get(admin_list_post_revisions)
+                .route_layer(require_permission(&state, Permission::PostsWrite)),
+        )
+        .route(
+            "/api/admin/posts/{slug}/revisions/{revision}",
+            get(admin_get_post_revision)
+                .route_layer(require_permission(&state, Permission::PostsWrite)),
+        )
+        .route(
+            "/api/admin/posts/{slug}/revisions/{revision}/restore",
+            post(admin_restore_post_revision)
+                .route_layer(require_permission(&state, Permission::PostsWrite)),
+        )
+        .route(
+            "/api/admin/posts/{slug}/diff",
+            get(admin_diff_post_revisions)
+                .route_layer(require_permission(&state, Permission::PostsWrite)),
         )
         .route(
             "/api/admin/audit/logs/{user_id}",
-            get(admin_get_user_audit_logs),
+            get(admin_get_user_audit_logs)
+                .route_layer(require_permission(&state, Permission::AuditRead)),
         )
         .route(
             "/api/admin/audit/cleanup",
-            post(admin_cleanup_old_audit_tables),
+            post(admin_cleanup_old_audit_tables)
+                .route_layer(require_permission(&state, Permission::AuditManage)),
+        )
+        .route(
+            "/api/admin/backup/restore",
+            post(admin_restore_database)
+                .route_layer(require_permission(&state, Permission::BackupRestore)),
+        )
+        .route(
+            "/api/admin/metrics/revocations",
+            get(admin_revocation_metrics)
+                .route_layer(require_permission(&state, Permission::AuditRead)),
+        )
+        .route(
+            "/api/admin/api-keys",
+            get(admin_list_api_keys)
+                .post(admin_create_api_key)
+                .route_layer(require_permission(&state, Permission::ApiKeysManage)),
+        )
+        .route(
+            "/api/admin/api-keys/{key_id}",
+            delete(admin_revoke_api_key)
+                .route_layer(require_permission(&state, Permission::ApiKeysManage)),
+        )
+        .route(
+            "/api/admin/permissions",
+            get(admin_list_role_permissions)
+                .route_layer(require_permission(&state, Permission::UsersRead)),
+        )
+        .route(
+            "/api/admin/roles/{role}/permissions",
+            put(admin_set_role_permissions)
+                .route_layer(require_permission(&state, Permission::UsersManage)),
+        )
+        .route(
+            "/api/admin/users",
+            get(admin_list_users)
+                .route_layer(require_permission(&state, Permission::UsersRead))
+                .merge(
+                    post(admin_create_user)
+                        .route_layer(require_permission(&state, Permission::UsersManage)),
+                ),
+        )
+        .route(
+            "/api/admin/users/{user_id}",
+            get(admin_get_user)
+                .route_layer(require_permission(&state, Permission::UsersRead))
+                .merge(
+                    patch(admin_update_user)
+                        .delete(admin_delete_user)
+                        .route_layer(require_permission(&state, Permission::UsersManage)),
+                ),
+        )
+        .route(
+            "/api/admin/users/{user_id}/deactivate",
+            post(admin_deactivate_user)
+                .route_layer(require_permission(&state, Permission::UsersManage)),
+        )
+        .route(
+            "/api/admin/users/{user_id}/reactivate",
+            post(admin_reactivate_user)
+                .route_layer(require_permission(&state, Permission::UsersManage)),
+        )
+        .route(
+            "/api/admin/users/{user_id}/suspend",
+            post(admin_suspend_user)
+                .route_layer(require_permission(&state, Permission::UsersManage)),
+        )
+        .route(
+            "/api/admin/users/{user_id}/reinstate",
+            post(admin_reinstate_user)
+                .route_layer(require_permission(&state, Permission::UsersManage)),
+        )
+        .route(
+            "/api/admin/users/{user_id}/sessions",
+            delete(admin_revoke_user_sessions)
+                .route_layer(require_permission(&state, Permission::UsersManage)),
+        )
+        .route(
+            "/api/admin/users/{user_id}/impersonate",
+            post(admin_impersonate_user)
+                .route_layer(require_permission(&state, Permission::UsersImpersonate)),
+        )
+        .route(
+            "/api/admin/users/{user_id}/guardians",
+            get(admin_list_guardians)
+                .route_layer(require_permission(&state, Permission::UsersRead)),
+        )
+        .route(
+            "/api/admin/guardianships",
+            post(admin_link_guardian)
+                .route_layer(require_permission(&state, Permission::UsersManage)),
+        )
+        .route(
+            "/api/admin/guardianships/{guardian_id}/{student_id}",
+            delete(admin_unlink_guardian)
+                .route_layer(require_permission(&state, Permission::UsersManage)),
         )
-        .route("/api/admin/backup/restore", post(admin_restore_database))
-        .route("/api/admin/users/{user_id}", get(admin_get_user))
         .route(
             "/api/admin/users/email/{email}",
-            get(admin_get_user_by_email),
+            get(admin_get_user_by_email)
+                .route_layer(require_permission(&state, Permission::UsersRead)),
         )
         .route(
             "/api/admin/users/{user_id}/role/{role}",
-            get(admin_check_user_role),
+            get(admin_check_user_role)
+                .route_layer(require_permission(&state, Permission::UsersRead)),
+        )
+        .route(
+            "/api/admin/tags",
+            get(admin_list_tags).route_layer(require_permission(&state, Permission::PostsWrite)),
+        )
+        // Renames and merges rewrite live posts, so they need `posts:publish`
+        .route(
+            "/api/admin/tags/{slug}",
+            put(admin_update_tag).route_layer(require_permission(&state, Permission::PostsPublish)),
+        )
+        .route(
+            "/api/admin/tags/{slug}/merge",
+            post(admin_merge_tag).route_layer(require_permission(&state, Permission::PostsPublish)),
+        )
+        .route(
+            "/api/admin/posts/model",
+            post(admin_create_post_with_model)
+                .route_layer(require_permission(&state, Permission::PostsWrite)),
         )
-        .route("/api/admin/posts/model", post(admin_create_post_with_model))
         .route(
             "/api/admin/posts/model/{slug}",
-            put(admin_update_post_with_model),
+            put(admin_update_post_with_model)
+                .route_layer(require_permission(&state, Permission::PostsWrite)),
+        )
+        .route(
+            "/api/admin/upload/image",
+            post(admin_upload_image)
+                .route_layer(require_permission(&state, Permission::MediaUpload)),
+        )
+        .route(
+            "/api/admin/upload/file",
+            post(admin_upload_file)
+                .route_layer(require_permission(&state, Permission::MediaUpload)),
+        )
+        .route(
+            "/api/admin/upload/multipart",
+            post(admin_upload_multipart)
+                .route_layer(require_permission(&state, Permission::MediaUpload)),
         )
-        .route("/api/admin/upload/image", post(admin_upload_image))
-        .route("/api/admin/upload/file", post(admin_upload_file))
-        .route("/api/admin/upload/multipart", post(admin_upload_multipart))
-        .layer(middleware::from_fn(admin_middleware))
         .layer(middleware::from_fn_with_state(
             state.clone(),
             auth_middleware,
@@ -69,25 +241,78 @@ pub fn create_router(state: AppState) -> Router {
     // Create protected routes with authentication middleware
     let protected_routes = Router::new()
         .route("/api/users/me", get(verify_session))
+        .route("/api/auth/csrf", get(csrf_token))
+        .route(
+            "/api/users/me/sessions",
+            get(list_my_sessions).delete(revoke_all_my_sessions),
+        )
+        .route("/api/users/me/sessions/{jti}", delete(revoke_my_session))
+        .route("/api/users/me/children", get(list_my_children))
+        .route(
+            "/api/users/me/identities/{provider}/start",
+            post(start_identity_link),
+        )
+        .route("/api/users/me/identities/{provider}", post(link_identity))
+        .route(
+            "/api/users/me/totp",
+            post(start_totp_enrollment).delete(disable_totp),
+        )
+        .route("/api/users/me/totp/confirm", post(confirm_totp_enrollment))
+        .route(
+            "/api/users/me/totp/recovery-codes",
+            post(regenerate_recovery_codes),
+        )
         .layer(middleware::from_fn_with_state(
             state.clone(),
-            auth_middleware,
+            session_auth_middleware,
         ));
 
-    Router::new()
-        .route("/", get(root))
-        .route("/healthz", get(health_check))
-        // Auth routes (no middleware needed)
+    // Routes that must keep working for impersonation tokens, writes included
+    let impersonation_routes = Router::new()
+        .route(
+            "/api/users/me/impersonation",
+            get(current_impersonation).delete(end_impersonation),
+        )
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth_middleware_allowing_impersonation,
+        ));
+
+    // Credential-accepting auth routes, throttled per client IP
+    let rate_limited_routes = Router::new()
         .route("/api/auth/login", post(login))
+        .route("/api/auth/login/verify", post(verify_magic_link))
         .route("/api/auth/google", post(google_oauth_login))
+        .route("/api/auth/oidc/{provider}", post(oidc_login))
+        .route("/api/auth/mfa/enroll", post(mfa_enroll))
+        .route("/api/auth/mfa/verify", post(mfa_verify))
         .route("/api/auth/logout", post(logout))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit_middleware,
+        ));
+
+    Router::new()
+        .route("/", get(root))
+        .route("/healthz", get(health_check))
+        // Public keys for verifying our JWTs at the edge
+        .route("/.well-known/jwks.json", get(jwks))
+        // Auth routes (no auth middleware needed)
+        .route("/api/auth/google/start", get(google_oauth_start))
+        .route("/api/auth/oidc/providers", get(oidc_providers))
+        .route("/api/auth/oidc/{provider}/start", get(oidc_start))
+        .route("/api/auth/refresh", post(refresh_session))
+        .merge(rate_limited_routes)
         // Public blog endpoints for SvelteKit SSR (no middleware needed)
         .route("/api/blog/index", get(get_blog_index))
+        .route("/api/blog/tags", get(get_blog_tags))
+        .route("/api/blog/tags/{slug}", get(get_blog_tag))
         .route("/api/blog/post/{slug}", get(get_public_blog_post))
         .route("/api/blog/public/{slug}", get(get_public_post_direct))
         // Merge protected routes
         .merge(admin_routes)
         .merge(protected_routes)
+        .merge(impersonation_routes)
         .layer(
             CorsLayer::new()
                 .allow_origin([
@@ -114,6 +339,7 @@ pub fn create_router(state: AppState) -> Router {
                     header::ACCEPT,
                     header::COOKIE,
                     header::SET_COOKIE,
+                    header::HeaderName::from_static(CSRF_HEADER),
                 ])
                 .allow_credentials(true),
         )
@@ -132,55 +358,347 @@ async fn health_check() -> Json<serde_json::Value> {
     }))
 }
 
+async fn jwks(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
+    let keyring = Keyring::for_config(&state.config)?;
+    // Short cache so a newly activated key is picked up soon after a rotation
+    Ok((
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+        Json(keyring.jwks()),
+    ))
+}
+
+/// Set-Cookie headers for a freshly issued access + refresh token pair and
+/// the CSRF token that goes with it
+fn session_cookie_headers(
+    auth_service: &AuthService,
+    response: &LoginResponse,
+    access_cookie: &str,
+) -> AppResult<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    headers.append(header::SET_COOKIE, access_cookie.parse().unwrap());
+    headers.append(
+        header::SET_COOKIE,
+        auth_service
+            .create_refresh_cookie_string(&response.refresh_token)?
+            .parse()
+            .unwrap(),
+    );
+    headers.append(
+        header::SET_COOKIE,
+        auth_service
+            .create_csrf_cookie_string(&response.csrf_token)?
+            .parse()
+            .unwrap(),
+    );
+    Ok(headers)
+}
+
 async fn login(
     State(state): State<AppState>,
     Json(payload): Json<LoginRequest>,
 ) -> AppResult<impl IntoResponse> {
+    // Throttle per email too, so one account cannot be flooded from many IPs
+    AuthRateLimiter::new(state.db.clone(), state.config.clone())?
+        .check_identity(&payload.email)
+        .await?;
+
     let auth_service = AuthService::new(state.db.clone(), state.config.clone());
-    let (response, cookie_value) = auth_service.login(payload).await?;
+    auth_service.login(payload, state.mailer.clone()).await?;
+
+    // Same response whether or not the email has an account
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "status": "success",
+            "message": "If an account exists for this email, a sign-in link has been sent"
+        })),
+    ))
+}
 
-    let mut headers = HeaderMap::new();
-    headers.insert(header::SET_COOKIE, cookie_value.parse().unwrap());
+async fn verify_magic_link(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(payload): Json<MagicLinkVerifyRequest>,
+) -> AppResult<impl IntoResponse> {
+    AuthRateLimiter::new(state.db.clone(), state.config.clone())?
+        .check_identity(&format!("magic-link:{}", hash_secret_token(&payload.token)))
+        .await?;
 
-    Ok((headers, Json(response)))
+    let auth_service =
+        AuthService::new(state.db.clone(), state.config.clone()).with_client_info(client);
+    let outcome = auth_service.verify_magic_link(&payload.token).await?;
+    login_outcome_response(&auth_service, outcome)
+}
+
+async fn google_oauth_start(State(state): State<AppState>) -> AppResult<impl IntoResponse> {
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    let response = auth_service.start_google_oauth().await?;
+    oauth_start_response(&auth_service, response)
 }
 
 async fn google_oauth_login(
     State(state): State<AppState>,
-    Json(payload): Json<GoogleAuthRequest>,
+    client: ClientInfo,
+    headers: HeaderMap,
+    Json(payload): Json<OidcAuthRequest>,
+) -> AppResult<impl IntoResponse> {
+    let auth_service =
+        AuthService::new(state.db.clone(), state.config.clone()).with_client_info(client);
+    let state_cookie = extract_cookie(&headers, auth_service.cookie_policy()?.oauth_state_name());
+    let outcome = auth_service
+        .google_oauth_login(payload, state_cookie.as_deref())
+        .await?;
+    oauth_login_response(&auth_service, outcome)
+}
+
+// Login providers configured for the sign-in page
+async fn oidc_providers(State(state): State<AppState>) -> AppResult<Json<OidcProvidersResponse>> {
+    let registry = OidcRegistry::from_config(&state.config)?;
+    Ok(Json(OidcProvidersResponse {
+        providers: registry.names(),
+    }))
+}
+
+async fn oidc_start(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
 ) -> AppResult<impl IntoResponse> {
     let auth_service = AuthService::new(state.db.clone(), state.config.clone());
-    let (response, cookie_value) = auth_service.google_oauth_login(payload).await?;
+    let response = auth_service.start_oidc_login(&provider).await?;
+    oauth_start_response(&auth_service, response)
+}
 
-    let mut headers = HeaderMap::new();
-    headers.insert(header::SET_COOKIE, cookie_value.parse().unwrap());
+async fn oidc_login(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    client: ClientInfo,
+    headers: HeaderMap,
+    Json(payload): Json<OidcAuthRequest>,
+) -> AppResult<impl IntoResponse> {
+    let auth_service =
+        AuthService::new(state.db.clone(), state.config.clone()).with_client_info(client);
+    let state_cookie = extract_cookie(&headers, auth_service.cookie_policy()?.oauth_state_name());
+    let outcome = auth_service
+        .oidc_login(&provider, payload, state_cookie.as_deref())
+        .await?;
+    oauth_login_response(&auth_service, outcome)
+}
+
+// Link a provider account to the signed-in user, e.g. when its email
+// matches an existing account but the provider doesn't assert it verified
+async fn start_identity_link(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    user: AuthUser,
+) -> AppResult<impl IntoResponse> {
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    let response = auth_service
+        .start_identity_link(&user.0.id, &provider)
+        .await?;
+    oauth_start_response(&auth_service, response)
+}
+
+async fn link_identity(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    user: AuthUser,
+    headers: HeaderMap,
+    Json(payload): Json<OidcAuthRequest>,
+) -> AppResult<impl IntoResponse> {
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    let state_cookie = extract_cookie(&headers, auth_service.cookie_policy()?.oauth_state_name());
+    auth_service
+        .link_oidc_identity(&user.0.id, &provider, payload, state_cookie.as_deref())
+        .await?;
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(
+            header::SET_COOKIE,
+            auth_service.create_clear_oauth_state_cookie_string()?,
+        )],
+    ))
+}
+
+/// The authorization URL, with the cookie binding its state to this browser
+fn oauth_start_response(
+    auth_service: &AuthService,
+    response: OidcAuthStartResponse,
+) -> AppResult<Response> {
+    let cookie = auth_service.create_oauth_state_cookie_string(&response.state)?;
+    Ok(([(header::SET_COOKIE, cookie)], Json(response)).into_response())
+}
+
+/// The login outcome, clearing the now-used OAuth state cookie
+fn oauth_login_response(auth_service: &AuthService, outcome: LoginOutcome) -> AppResult<Response> {
+    let mut response = login_outcome_response(auth_service, outcome)?;
+    response.headers_mut().append(
+        header::SET_COOKIE,
+        auth_service
+            .create_clear_oauth_state_cookie_string()?
+            .parse()
+            .unwrap(),
+    );
+    Ok(response)
+}
+
+/// The session with its cookies, or the second-factor challenge (no cookies)
+fn login_outcome_response(auth_service: &AuthService, outcome: LoginOutcome) -> AppResult<Response> {
+    match outcome {
+        LoginOutcome::Session(response, cookie_value) => {
+            let headers = session_cookie_headers(auth_service, &response, &cookie_value)?;
+            Ok((headers, Json(response)).into_response())
+        }
+        LoginOutcome::MfaRequired(challenge) => Ok(Json(challenge).into_response()),
+    }
+}
+
+// TOTP setup for a login that requires it before a session is issued
+async fn mfa_enroll(
+    State(state): State<AppState>,
+    Json(payload): Json<MfaChallengeRequest>,
+) -> AppResult<Json<TotpEnrollmentResponse>> {
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    let totp_service = TotpService::new(state.db.clone(), state.config.clone());
+    let response = auth_service
+        .start_challenge_enrollment(&totp_service, &payload.challenge_token)
+        .await?;
+    Ok(Json(response))
+}
+
+// Second login step: exchange the challenge and a code for the session
+async fn mfa_verify(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(payload): Json<MfaVerifyRequest>,
+) -> AppResult<impl IntoResponse> {
+    let auth_service =
+        AuthService::new(state.db.clone(), state.config.clone()).with_client_info(client);
+    let totp_service = TotpService::new(state.db.clone(), state.config.clone());
+    let (response, cookie_value) = auth_service
+        .complete_mfa_login(&totp_service, &payload.challenge_token, &payload.code)
+        .await?;
+    let headers = session_cookie_headers(&auth_service, &response.session, &cookie_value)?;
+
+    Ok((headers, Json(response)))
+}
+
+async fn refresh_session(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    headers: HeaderMap,
+    payload: Option<Json<RefreshTokenRequest>>,
+) -> AppResult<impl IntoResponse> {
+    let auth_service =
+        AuthService::new(state.db.clone(), state.config.clone()).with_client_info(client);
+
+    // Browsers send the refresh cookie; API clients may post the token instead
+    let cookies = auth_service.cookie_policy()?;
+    let refresh_token = match extract_cookie(&headers, cookies.refresh_name()) {
+        Some(refresh_token) => {
+            ensure_csrf_echoed(&auth_service, &headers)?;
+            refresh_token
+        }
+        None => payload
+            .map(|Json(body)| body.refresh_token)
+            .ok_or_else(|| AppError::Auth("Refresh token is required".to_string()))?,
+    };
+
+    AuthRateLimiter::new(state.db.clone(), state.config.clone())?
+        .check_identity(&format!("refresh:{}", hash_secret_token(&refresh_token)))
+        .await?;
+
+    let (response, cookie_value) = auth_service.refresh_session(&refresh_token).await?;
+    let headers = session_cookie_headers(&auth_service, &response, &cookie_value)?;
 
     Ok((headers, Json(response)))
 }
 
 async fn logout(State(state): State<AppState>, headers: HeaderMap) -> AppResult<impl IntoResponse> {
     let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    let cookies = auth_service.cookie_policy()?;
+    let mut response_headers = HeaderMap::new();
+    let session_value = extract_cookie(&headers, cookies.session_name());
+    let refresh_value = extract_cookie(&headers, cookies.refresh_name());
+
+    // Another site must not be able to log the user out
+    if session_value.is_some() || refresh_value.is_some() {
+        ensure_csrf_echoed(&auth_service, &headers)?;
+        response_headers.append(
+            header::SET_COOKIE,
+            auth_service
+                .create_logout_csrf_cookie_string()?
+                .parse()
+                .unwrap(),
+        );
+    }
 
-    // Extract session cookie
-    if let Some(cookie_header) = headers.get(header::COOKIE) {
-        if let Ok(cookie_str) = cookie_header.to_str() {
-            // Parse session cookie
-            for cookie in cookie_str.split(';') {
-                let cookie = cookie.trim();
-                if cookie.starts_with("session=") {
-                    let session_value = &cookie[8..];
-                    let clear_cookie = auth_service.logout_with_cookie(session_value).await?;
-
-                    let mut response_headers = HeaderMap::new();
-                    response_headers.insert(header::SET_COOKIE, clear_cookie.parse().unwrap());
-
-                    return Ok((response_headers, StatusCode::NO_CONTENT));
-                }
-            }
-        }
+    // Revoke the access token and clear its cookie
+    if let Some(session_value) = session_value {
+        let clear_cookie = auth_service.logout_with_cookie(&session_value).await?;
+        response_headers.append(header::SET_COOKIE, clear_cookie.parse().unwrap());
     }
 
-    Ok((HeaderMap::new(), StatusCode::NO_CONTENT))
+    // Revoke the refresh token family and clear its cookie
+    if let Some(refresh_value) = refresh_value {
+        let clear_cookie = auth_service
+            .logout_with_refresh_token(&refresh_value)
+            .await?;
+        response_headers.append(header::SET_COOKIE, clear_cookie.parse().unwrap());
+    }
+
+    Ok((response_headers, StatusCode::NO_CONTENT))
+}
+
+/// Refresh and logout authenticate with cookies but sit outside the auth
+/// middleware, and the access token may already have expired. They take the
+/// `csrf_token` cookie echoed in `X-CSRF-Token` instead, which a cross-site
+/// form can neither read nor set.
+fn ensure_csrf_echoed(auth_service: &AuthService, headers: &HeaderMap) -> AppResult<()> {
+    let cookies = auth_service.cookie_policy()?;
+    let cookie = extract_cookie(headers, cookies.csrf_name()).unwrap_or_default();
+    let echoed = headers
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    auth_service.verify_csrf_echo(&cookie, echoed)
+}
+
+/// Making a post public needs `posts:publish` on top of `posts:write`.
+/// `current` is the stored post on updates, whose status a request without
+/// one keeps.
+async fn ensure_can_publish(
+    state: &AppState,
+    user: &UserResponse,
+    payload: &CreateBlogPostRequest,
+    current: Option<&BlogPostKv>,
+) -> AppResult<()> {
+    // Drafts and posts in review may be public-to-be without the permission
+    let status = match (&payload.status, current) {
+        (None, Some(post)) => PostStatus::parse(&post.status),
+        _ => payload.post_status(),
+    };
+    let goes_live = status.is_none_or(|status| status.goes_live());
+    if payload.visibility == "public" && goes_live {
+        let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+        auth_service
+            .ensure_user_permission(user, Permission::PostsPublish)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Edits to a live or scheduled post (taking it down included) need
+/// `posts:publish`, as do edits that would make it live
+async fn ensure_can_update_post(
+    state: &AppState,
+    blog_service: &BlogService,
+    user: &UserResponse,
+    slug: &str,
+    payload: &CreateBlogPostRequest,
+) -> AppResult<()> {
+    ensure_can_change_live_post(state, blog_service, user, slug).await?;
+    let current = blog_service.get_post(slug).await?;
+    ensure_can_publish(state, user, payload, current.as_ref()).await
 }
 
 // Legacy handlers - these should be removed and replaced with admin handlers
@@ -188,11 +706,14 @@ async fn logout(State(state): State<AppState>, headers: HeaderMap) -> AppResult<
 // Admin handlers (protected routes)
 async fn admin_list_posts(
     State(state): State<AppState>,
+    Query(query): Query<BlogIndexQuery>,
     _user: AuthUser,
-) -> AppResult<Json<Vec<BlogIndexEntry>>> {
+) -> AppResult<Json<BlogIndexResponse>> {
     // User is already verified by middleware
     let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
-    let posts = blog_service.list_posts(true).await?; // Include private posts
+    let posts = blog_service
+        .query_posts(&query, true, "/api/admin/posts") // Include private posts
+        .await?;
     Ok(Json(posts))
 }
 
@@ -201,6 +722,7 @@ async fn admin_create_post(
     user: AuthUser,
     Json(payload): Json<CreateBlogPostRequest>,
 ) -> AppResult<Json<BlogPostKv>> {
+    ensure_can_publish(&state, &user.0, &payload, None).await?;
     let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
     let blog_post = blog_service.create_post(payload, user.0.id.clone()).await?;
     Ok(Json(blog_post))
@@ -228,6 +750,7 @@ async fn admin_update_post(
     Json(payload): Json<CreateBlogPostRequest>,
 ) -> AppResult<Json<BlogPostKv>> {
     let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
+    ensure_can_update_post(&state, &blog_service, &user.0, &slug, &payload).await?;
     let blog_post = blog_service
         .update_post(&slug, payload, user.0.id.clone())
         .await?;
@@ -240,52 +763,385 @@ async fn admin_delete_post(
     user: AuthUser,
 ) -> AppResult<StatusCode> {
     let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
+    ensure_can_delete_post(&state, &blog_service, &user.0, &slug).await?;
     blog_service.delete_post(&slug, user.0.id.clone()).await?;
     Ok(StatusCode::NO_CONTENT)
 }
 
+// Saved versions of a post, newest first (without their content)
+async fn admin_list_post_revisions(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+) -> AppResult<Json<Vec<PostRevisionResponse>>> {
+    let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
+    let revisions = blog_service
+        .list_revisions(&slug)
+        .await?
+        .iter()
+        .map(|revision| revision.to_response(false))
+        .collect::<Result<_, _>>()?;
+    Ok(Json(revisions))
+}
+
+async fn admin_get_post_revision(
+    State(state): State<AppState>,
+    Path((slug, revision)): Path<(String, i64)>,
+) -> AppResult<Json<PostRevisionResponse>> {
+    let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
+    let revision = blog_service.get_revision(&slug, revision).await?;
+    Ok(Json(revision.to_response(true)?))
+}
+
+async fn admin_diff_post_revisions(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    Query(query): Query<RevisionDiffQuery>,
+) -> AppResult<Json<RevisionDiffResponse>> {
+    let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
+    let diff = blog_service
+        .diff_revisions(&slug, query.from, query.to)
+        .await?;
+    Ok(Json(diff))
+}
+
+async fn admin_restore_post_revision(
+    State(state): State<AppState>,
+    Path((slug, revision)): Path<(String, i64)>,
+    user: AuthUser,
+) -> AppResult<Json<BlogPostKv>> {
+    let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
+
+    ensure_can_change_live_post(&state, &blog_service, &user.0, &slug).await?;
+
+    let (blog_post, _) = blog_service
+        .restore_revision(&slug, revision, user.0.id.clone())
+        .await?;
+    Ok(Json(blog_post))
+}
+
+// Move a post to a new slug; the old one redirects permanently
+async fn admin_change_post_slug(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    user: AuthUser,
+    Json(payload): Json<ChangeSlugRequest>,
+) -> AppResult<Json<BlogPostKv>> {
+    let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
+    ensure_can_change_live_post(&state, &blog_service, &user.0, &slug).await?;
+
+    let blog_post = blog_service
+        .change_slug(&slug, &payload.slug, user.0.id.clone())
+        .await?;
+    Ok(Json(blog_post))
+}
+
+// Every tag with its post count, including private posts and unused tags
+async fn admin_list_tags(State(state): State<AppState>) -> AppResult<Json<Vec<TagResponse>>> {
+    let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
+    let tags = blog_service.list_tags(true).await?;
+    Ok(Json(tags))
+}
+
+// Rename a tag (and its slug) on every post, or change its description
+async fn admin_update_tag(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    user: AuthUser,
+    Json(payload): Json<UpdateTagRequest>,
+) -> AppResult<Json<TagResponse>> {
+    let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
+    let tag = blog_service
+        .update_tag(&slug, payload, user.0.id.clone())
+        .await?;
+    Ok(Json(tag))
+}
+
+async fn admin_merge_tag(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    user: AuthUser,
+    Json(payload): Json<MergeTagRequest>,
+) -> AppResult<Json<TagResponse>> {
+    let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
+    let tag = blog_service
+        .merge_tag(&slug, &payload.into, user.0.id.clone())
+        .await?;
+    Ok(Json(tag))
+}
+
+// Changing a post that is (or will be) live to readers needs `posts:publish`
+async fn ensure_can_change_live_post(
+    state: &AppState,
+    blog_service: &BlogService,
+    user: &UserResponse,
+    slug: &str,
+) -> AppResult<()> {
+    let current = blog_service
+        .get_post(slug)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Blog post not found".to_string()))?;
+    if is_live_or_scheduled(&current) {
+        require_publish_permission(state, user).await?;
+    }
+    Ok(())
+}
+
+// Deleting a live post, or another author's post, needs `posts:publish`
+async fn ensure_can_delete_post(
+    state: &AppState,
+    blog_service: &BlogService,
+    user: &UserResponse,
+    slug: &str,
+) -> AppResult<()> {
+    let current = blog_service
+        .get_post(slug)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Blog post not found".to_string()))?;
+    if is_live_or_scheduled(&current) || current.author_id != user.id {
+        require_publish_permission(state, user).await?;
+    }
+    Ok(())
+}
+
+// Public and published or scheduled
+fn is_live_or_scheduled(post: &BlogPostKv) -> bool {
+    let goes_live = PostStatus::parse(&post.status).is_some_and(|status| status.goes_live());
+    post.visibility == "public" && goes_live
+}
+
+async fn require_publish_permission(state: &AppState, user: &UserResponse) -> AppResult<()> {
+    AuthService::new(state.db.clone(), state.config.clone())
+        .ensure_user_permission(user, Permission::PostsPublish)
+        .await
+}
+
 // Session verification - user is already verified by middleware
 async fn verify_session(user: AuthUser) -> AppResult<Json<UserResponse>> {
     Ok(Json(user.0))
 }
 
+// Students linked to the current user as their guardian
+async fn list_my_children(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> AppResult<Json<Vec<LinkedUserResponse>>> {
+    let guardian_service = GuardianService::new(state.db.clone());
+    let children = guardian_service.children_of(&user.0.id).await?;
+    Ok(Json(children))
+}
+
+async fn start_totp_enrollment(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> AppResult<Json<TotpEnrollmentResponse>> {
+    let totp_service = TotpService::new(state.db.clone(), state.config.clone());
+    let response = totp_service
+        .start_enrollment(&user.0.id, &user.0.email)
+        .await?;
+    Ok(Json(response))
+}
+
+// Enable TOTP with the first code from the app; returns the recovery codes
+async fn confirm_totp_enrollment(
+    State(state): State<AppState>,
+    user: AuthUser,
+    claims: Option<AuthClaims>,
+    Json(payload): Json<TotpCodeRequest>,
+) -> AppResult<Json<RecoveryCodesResponse>> {
+    let totp_service = TotpService::new(state.db.clone(), state.config.clone());
+    let recovery_codes = totp_service
+        .confirm_enrollment(&user.0.id, &payload.code)
+        .await?;
+
+    // Logins elsewhere never passed the new second factor
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    auth_service
+        .end_sessions_without_mfa(&user.0.id, claims.as_ref().map(|claims| claims.0.jti.as_str()))
+        .await?;
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<TotpCodeRequest>,
+) -> AppResult<Json<RecoveryCodesResponse>> {
+    let totp_service = TotpService::new(state.db.clone(), state.config.clone());
+    let recovery_codes = totp_service
+        .regenerate_recovery_codes(&user.0.id, &payload.code)
+        .await?;
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+async fn disable_totp(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Json(payload): Json<TotpCodeRequest>,
+) -> AppResult<StatusCode> {
+    let totp_service = TotpService::new(state.db.clone(), state.config.clone());
+    totp_service
+        .disable(&user.0.id, UserRole::from_str(&user.0.role), &payload.code)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Who the current impersonation token acts as, and for whom
+async fn current_impersonation(
+    user: AuthUser,
+    claims: Option<AuthClaims>,
+    impersonator: Option<Extension<Impersonator>>,
+) -> AppResult<Json<serde_json::Value>> {
+    let (Some(Extension(claims)), Some(Extension(Impersonator(actor)))) = (claims, impersonator)
+    else {
+        return Err(AppError::NotFound("Not impersonating".to_string()));
+    };
+
+    Ok(Json(serde_json::json!({
+        "user": user.0,
+        "actor": actor,
+        "expires_at": claims.exp,
+    })))
+}
+
+// Revoke the impersonation token making this request
+async fn end_impersonation(
+    State(state): State<AppState>,
+    claims: Option<AuthClaims>,
+) -> AppResult<StatusCode> {
+    let Some(Extension(claims)) = claims else {
+        return Err(AppError::Validation("Not impersonating".to_string()));
+    };
+
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    auth_service.end_impersonation(&claims).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// CSRF token for the current access token, for clients that cannot read the cookie
+async fn csrf_token(
+    State(state): State<AppState>,
+    claims: Option<AuthClaims>, // Absent for API-key requests
+) -> AppResult<Json<serde_json::Value>> {
+    let Some(Extension(claims)) = claims else {
+        return Err(AppError::Validation(
+            "CSRF tokens are only issued for user sessions".to_string(),
+        ));
+    };
+
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    Ok(Json(serde_json::json!({
+        "csrf_token": auth_service.csrf_token(&claims.jti)
+    })))
+}
+
+// Logins of the current user, flagging the one making this request
+async fn list_my_sessions(
+    State(state): State<AppState>,
+    user: AuthUser,
+    claims: Option<AuthClaims>, // Absent for API-key requests
+) -> AppResult<Json<Vec<SessionResponse>>> {
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    let current_jti = claims.map(|Extension(claims)| claims.jti);
+    let sessions = auth_service
+        .list_sessions(&user.0.id, current_jti.as_deref())
+        .await?;
+    Ok(Json(sessions))
+}
+
+async fn revoke_my_session(
+    State(state): State<AppState>,
+    Path(jti): Path<String>,
+    user: AuthUser,
+) -> AppResult<StatusCode> {
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    auth_service.revoke_session(&user.0.id, &jti).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Sign out everywhere, including the session making this request
+async fn revoke_all_my_sessions(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> AppResult<Json<serde_json::Value>> {
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    let revoked = auth_service.revoke_all_sessions(&user.0.id).await?;
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "sessions_revoked": revoked
+    })))
+}
+
 // Public blog endpoints for SvelteKit SSR
-async fn get_blog_index(State(state): State<AppState>) -> AppResult<Json<Vec<BlogIndexEntry>>> {
+async fn get_blog_index(
+    State(state): State<AppState>,
+    Query(query): Query<BlogIndexQuery>,
+) -> AppResult<Json<BlogIndexResponse>> {
     let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
-    let public_posts = blog_service.list_posts(false).await?; // Only public posts
+    let public_posts = blog_service
+        .query_posts(&query, false, "/api/blog/index") // Only live posts
+        .await?;
     Ok(Json(public_posts))
 }
 
+// Tags on live public posts, with their post counts
+async fn get_blog_tags(State(state): State<AppState>) -> AppResult<Json<Vec<TagResponse>>> {
+    let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
+    let tags = blog_service.list_tags(false).await?;
+    Ok(Json(tags))
+}
+
+async fn get_blog_tag(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    Query(query): Query<BlogIndexQuery>,
+) -> AppResult<Json<TagPageResponse>> {
+    let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
+    let tag_page = blog_service.tag_page(&slug, query).await?;
+    Ok(Json(tag_page))
+}
+
 async fn get_public_blog_post(
     State(state): State<AppState>,
     Path(slug): Path<String>,
-) -> AppResult<Json<BlogPostKv>> {
+) -> AppResult<Response> {
     let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
-    let blog_post = blog_service
-        .get_post(&slug)
-        .await?
-        .ok_or_else(|| AppError::NotFound("Blog post not found".to_string()))?;
+    if let Some(blog_post) = blog_service.get_public_post(&slug).await? {
+        return Ok(Json(blog_post).into_response());
+    }
 
-    // Check if post is public
-    if blog_post.visibility != "public" {
-        return Err(AppError::NotFound("Blog post not found".to_string()));
+    // A renamed post answers its old slug with a permanent redirect
+    match blog_service.get_public_redirect(&slug).await? {
+        Some(new_slug) => Ok(permanent_redirect("/api/blog/post", &new_slug)),
+        None => Err(AppError::NotFound("Blog post not found".to_string())),
     }
+}
 
-    Ok(Json(blog_post))
+// 301 to the post's current slug. The body carries the slug so the blog
+// page loader (blog/[slug]/+page.server.ts) can redirect the reader as well.
+fn permanent_redirect(base_path: &str, new_slug: &str) -> Response {
+    (
+        StatusCode::MOVED_PERMANENTLY,
+        [(header::LOCATION, format!("{}/{}", base_path, new_slug))],
+        Json(serde_json::json!({ "slug": new_slug })),
+    )
+        .into_response()
 }
 
 // Direct public post endpoint using BlogService.get_public_post method
 async fn get_public_post_direct(
     State(state): State<AppState>,
     Path(slug): Path<String>,
-) -> AppResult<Json<BlogPostKv>> {
+) -> AppResult<Response> {
     let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
-    let blog_post = blog_service
-        .get_public_post(&slug)
-        .await?
-        .ok_or_else(|| AppError::NotFound("Blog post not found".to_string()))?;
+    if let Some(blog_post) = blog_service.get_public_post(&slug).await? {
+        return Ok(Json(blog_post).into_response());
+    }
 
-    Ok(Json(blog_post))
+    match blog_service.get_public_redirect(&slug).await? {
+        Some(new_slug) => Ok(permanent_redirect("/api/blog/public", &new_slug)),
+        None => Err(AppError::NotFound("Blog post not found".to_string())),
+    }
 }
 
 // Admin audit handlers
@@ -362,6 +1218,179 @@ async fn admin_get_user(
     Ok(Json(user))
 }
 
+async fn admin_list_users(
+    State(state): State<AppState>,
+    Query(query): Query<UserListQuery>,
+    _admin_user: AuthUser,
+) -> AppResult<Json<UserListResponse>> {
+    let user_service = UserService::new(state.db.clone());
+    let users = user_service.list_users(query).await?;
+    Ok(Json(users))
+}
+
+async fn admin_create_user(
+    State(state): State<AppState>,
+    admin_user: AuthUser,
+    Json(payload): Json<CreateUserRequest>,
+) -> AppResult<(StatusCode, Json<User>)> {
+    let user_service = UserService::new(state.db.clone());
+    let user = user_service.create_user(payload, &admin_user.0.id).await?;
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+// Change role and/or full name
+async fn admin_update_user(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    admin_user: AuthUser,
+    Json(payload): Json<UpdateUserRequest>,
+) -> AppResult<Json<User>> {
+    let user_service = UserService::new(state.db.clone());
+    let user = user_service
+        .update_user(&user_id, payload, &admin_user.0.id)
+        .await?;
+    Ok(Json(user))
+}
+
+// Block sign-in and end every session of the user
+async fn admin_deactivate_user(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    admin_user: AuthUser,
+) -> AppResult<Json<User>> {
+    let user_service = UserService::new(state.db.clone());
+    let user = user_service
+        .deactivate_user(&user_id, &admin_user.0.id)
+        .await?;
+
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    auth_service.revoke_all_sessions(&user_id).await?;
+
+    Ok(Json(user))
+}
+
+async fn admin_reactivate_user(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    admin_user: AuthUser,
+) -> AppResult<Json<User>> {
+    let user_service = UserService::new(state.db.clone());
+    let user = user_service
+        .reactivate_user(&user_id, &admin_user.0.id)
+        .await?;
+    Ok(Json(user))
+}
+
+// Lock a user out without ending their sessions; they resume on reinstatement
+async fn admin_suspend_user(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    admin_user: AuthUser,
+    Json(payload): Json<SuspendUserRequest>,
+) -> AppResult<Json<User>> {
+    let user_service = UserService::new(state.db.clone());
+    let user = user_service
+        .suspend_user(&user_id, &payload.reason, &admin_user.0.id)
+        .await?;
+    Ok(Json(user))
+}
+
+async fn admin_reinstate_user(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    admin_user: AuthUser,
+) -> AppResult<Json<User>> {
+    let user_service = UserService::new(state.db.clone());
+    let user = user_service
+        .reinstate_user(&user_id, &admin_user.0.id)
+        .await?;
+    Ok(Json(user))
+}
+
+async fn admin_delete_user(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    admin_user: AuthUser,
+) -> AppResult<StatusCode> {
+    // Sessions and refresh tokens are removed with the user
+    let user_service = UserService::new(state.db.clone());
+    user_service.delete_user(&user_id, &admin_user.0.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Force-logout every session of a user
+async fn admin_revoke_user_sessions(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    admin_user: AuthUser,
+) -> AppResult<Json<serde_json::Value>> {
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    let revoked = auth_service.revoke_all_sessions(&user_id).await?;
+
+    let audit_service = AuditService::new(state.db.clone());
+    audit_service
+        .log_action(
+            &admin_user.0.id,
+            "force_logout_user".to_string(),
+            Some(user_id.clone()),
+            Some(serde_json::json!({ "sessions_revoked": revoked })),
+        )
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "user_id": user_id,
+        "sessions_revoked": revoked
+    })))
+}
+
+async fn admin_impersonate_user(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    admin_user: AuthUser,
+    payload: Option<Json<ImpersonateRequest>>,
+) -> AppResult<Json<ImpersonationResponse>> {
+    let Json(request) = payload.unwrap_or_default();
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    let response = auth_service
+        .impersonate(&admin_user.0, &user_id, request.reason)
+        .await?;
+    Ok(Json(response))
+}
+
+async fn admin_list_guardians(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    _admin_user: AuthUser,
+) -> AppResult<Json<Vec<LinkedUserResponse>>> {
+    let guardian_service = GuardianService::new(state.db.clone());
+    let guardians = guardian_service.guardians_of(&user_id).await?;
+    Ok(Json(guardians))
+}
+
+// Link a parent to a student, or change an existing link
+async fn admin_link_guardian(
+    State(state): State<AppState>,
+    admin_user: AuthUser,
+    Json(payload): Json<LinkGuardianRequest>,
+) -> AppResult<Json<Guardianship>> {
+    let guardian_service = GuardianService::new(state.db.clone());
+    let link = guardian_service.link(payload, &admin_user.0.id).await?;
+    Ok(Json(link))
+}
+
+async fn admin_unlink_guardian(
+    State(state): State<AppState>,
+    Path((guardian_id, student_id)): Path<(String, String)>,
+    admin_user: AuthUser,
+) -> AppResult<StatusCode> {
+    let guardian_service = GuardianService::new(state.db.clone());
+    guardian_service
+        .unlink(&guardian_id, &student_id, &admin_user.0.id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 async fn admin_get_user_by_email(
     State(state): State<AppState>,
     Path(email): Path<String>,
@@ -382,27 +1411,10 @@ async fn admin_create_post_with_model(
     user: AuthUser,
     Json(payload): Json<CreateBlogPostRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
+    ensure_can_publish(&state, &user.0, &payload, None).await?;
     let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
-
-    // Extract fields from CreateBlogPostRequest for the model method
-    let excerpt = payload.summary.clone().unwrap_or_else(|| {
-        // Generate excerpt from body_html (first 150 chars)
-        let plain_text = payload.body_html.chars().take(150).collect::<String>();
-        format!("{}...", plain_text)
-    });
-
     let blog_post = blog_service
-        .create_post_with_model(
-            payload.title,
-            payload.body_html,
-            excerpt,
-            user.0.id.clone(),
-            payload.tags,
-            payload.visibility,
-            payload.cover_image,
-            vec![], // inline_images not in CreateBlogPostRequest
-            payload.attachments,
-        )
+        .create_post_with_model(payload, user.0.id.clone())
         .await?;
 
     Ok(Json(serde_json::json!({
@@ -426,27 +1438,9 @@ async fn admin_update_post_with_model(
     Json(payload): Json<CreateBlogPostRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
     let blog_service = BlogService::new(state.kv.clone(), state.db.clone());
-
-    // Extract fields from CreateBlogPostRequest for the model method
-    let excerpt = payload.summary.clone().unwrap_or_else(|| {
-        // Generate excerpt from body_html (first 150 chars)
-        let plain_text = payload.body_html.chars().take(150).collect::<String>();
-        format!("{}...", plain_text)
-    });
-
+    ensure_can_update_post(&state, &blog_service, &user.0, &slug, &payload).await?;
     let blog_post = blog_service
-        .update_post_with_model(
-            &slug,
-            payload.title,
-            payload.body_html,
-            excerpt,
-            payload.tags,
-            payload.visibility,
-            payload.cover_image,
-            vec![], // inline_images not in CreateBlogPostRequest
-            payload.attachments,
-            user.0.id.clone(),
-        )
+        .update_post_with_model(&slug, payload, user.0.id.clone())
         .await?;
 
     Ok(Json(serde_json::json!({
@@ -463,6 +1457,116 @@ async fn admin_update_post_with_model(
     })))
 }
 
+// Revocation cache hit rate and pruning totals since startup
+async fn admin_revocation_metrics(_admin_user: AuthUser) -> Json<RevocationCacheMetrics> {
+    Json(REVOCATION_CACHE.metrics())
+}
+
+async fn admin_list_api_keys(
+    State(state): State<AppState>,
+    _admin_user: AuthUser,
+) -> AppResult<Json<Vec<ApiKeyResponse>>> {
+    let api_key_service = ApiKeyService::new(state.db.clone());
+    let keys = api_key_service.list_keys().await?;
+    Ok(Json(keys.into_iter().map(ApiKeyResponse::from).collect()))
+}
+
+// The plaintext key is only returned here
+async fn admin_create_api_key(
+    State(state): State<AppState>,
+    admin_user: AuthUser,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> AppResult<(StatusCode, Json<CreatedApiKeyResponse>)> {
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    let owner_permissions = auth_service.user_permissions(&admin_user.0).await?;
+
+    let api_key_service = ApiKeyService::new(state.db.clone());
+    let (key, api_key) = api_key_service
+        .create_key(payload, &admin_user.0, &owner_permissions)
+        .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedApiKeyResponse {
+            key,
+            api_key: api_key.into(),
+        }),
+    ))
+}
+
+async fn admin_revoke_api_key(
+    State(state): State<AppState>,
+    Path(key_id): Path<String>,
+    admin_user: AuthUser,
+) -> AppResult<StatusCode> {
+    let api_key_service = ApiKeyService::new(state.db.clone());
+    api_key_service.revoke_key(&key_id, &admin_user.0.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Role -> permission mapping for every role
+async fn admin_list_role_permissions(
+    State(state): State<AppState>,
+    _admin_user: AuthUser,
+) -> AppResult<Json<Vec<RolePermissionsResponse>>> {
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+
+    let mut roles = Vec::new();
+    for role in [
+        UserRole::Admin,
+        UserRole::Teacher,
+        UserRole::Parent,
+        UserRole::Student,
+    ] {
+        let permissions = auth_service.role_permissions(&role).await?;
+        roles.push(RolePermissionsResponse {
+            role: role.as_str().to_string(),
+            permissions,
+        });
+    }
+
+    Ok(Json(roles))
+}
+
+async fn admin_set_role_permissions(
+    State(state): State<AppState>,
+    Path(role_str): Path<String>,
+    admin_user: AuthUser,
+    Json(payload): Json<RolePermissionsRequest>,
+) -> AppResult<Json<RolePermissionsResponse>> {
+    let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+    let role = UserRole::from_str(&role_str)
+        .ok_or_else(|| AppError::Validation(format!("Invalid user role: {}", role_str)))?;
+
+    let permissions = payload
+        .permissions
+        .iter()
+        .map(|p| {
+            Permission::parse(p)
+                .ok_or_else(|| AppError::Validation(format!("Unknown permission: {}", p)))
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+
+    let before = auth_service.role_permissions(&role).await?;
+    auth_service.set_role_permissions(&role, &permissions).await?;
+    let after = auth_service.role_permissions(&role).await?;
+
+    let audit_service = AuditService::new(state.db.clone());
+    audit_service
+        .log_action(
+            &admin_user.0.id,
+            "update_role_permissions".to_string(),
+            Some(role_str.clone()),
+            Some(serde_json::json!({ "before": before, "after": after })),
+        )
+        .await?;
+
+    Ok(Json(RolePermissionsResponse {
+        role: role_str,
+        permissions: after,
+    }))
+}
+
 // Admin role checking handler
 async fn admin_check_user_role(
     State(state): State<AppState>,
@@ -0,0 +1,294 @@
+use crate::config::AppConfig;
+use crate::error::{AppError, AppResult};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::Utc;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use ring::signature::KeyPair;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
+
+/// Key id of the HS256 key built from `jwt_secret`. Tokens signed before key
+/// ids existed carry no `kid` and are looked up under this id.
+pub const DEFAULT_KID: &str = "default";
+
+/// Parsed keyrings keyed by the settings they were built from. `AuthService`
+/// is built per request, so like the Google JWKS cache this lives at module
+/// level and key files are only read once.
+static KEYRINGS: LazyLock<RwLock<HashMap<String, Arc<Keyring>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Keys for signing and verifying our own JWTs. Exactly one key is active
+/// for signing; the others only verify tokens issued before a rotation and
+/// are dropped once their `not_after` has passed.
+pub struct Keyring {
+    keys: Vec<SigningKey>,
+    active: usize,
+}
+
+struct SigningKey {
+    kid: String,
+    algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    jwk: Option<Jwk>, // Public key for the JWKS; None for shared secrets
+    not_after: Option<i64>, // Unix timestamp after which the key is ignored
+}
+
+impl Keyring {
+    /// Keyring for these settings, parsed on first use and cached afterwards
+    pub fn for_config(config: &AppConfig) -> AppResult<Arc<Self>> {
+        let cache_key = format!(
+            "{}\n{}\n{}",
+            config.jwt_active_kid,
+            config.jwt_keys.join(","),
+            config.jwt_secret
+        );
+
+        if let Some(keyring) = KEYRINGS.read().unwrap().get(&cache_key) {
+            return Ok(keyring.clone());
+        }
+
+        let keyring = Arc::new(Self::from_config(config)?);
+        KEYRINGS
+            .write()
+            .unwrap()
+            .insert(cache_key, keyring.clone());
+        Ok(keyring)
+    }
+
+    /// Build the keyring from `jwt_keys` and `jwt_active_kid`, rejecting
+    /// malformed entries. Without `jwt_keys` the keyring is a single HS256
+    /// key made from `jwt_secret`.
+    pub fn from_config(config: &AppConfig) -> AppResult<Self> {
+        let keys = if config.jwt_keys.is_empty() {
+            vec![SigningKey::from_secret(
+                DEFAULT_KID.to_string(),
+                config.jwt_secret.as_bytes(),
+                None,
+            )]
+        } else {
+            config
+                .jwt_keys
+                .iter()
+                .map(|entry| SigningKey::parse(entry))
+                .collect::<AppResult<Vec<_>>>()?
+        };
+
+        for (i, key) in keys.iter().enumerate() {
+            if keys[..i].iter().any(|other| other.kid == key.kid) {
+                return Err(config_error(format!("Duplicate JWT key id: {}", key.kid)));
+            }
+        }
+
+        let active = keys
+            .iter()
+            .position(|key| key.kid == config.jwt_active_kid)
+            .ok_or_else(|| {
+                config_error(format!(
+                    "jwt_active_kid {} is not in the keyring",
+                    config.jwt_active_kid
+                ))
+            })?;
+        if !keys[active].is_live() {
+            return Err(config_error(format!(
+                "Active JWT key {} has passed its not_after",
+                config.jwt_active_kid
+            )));
+        }
+
+        Ok(Self { keys, active })
+    }
+
+    pub fn active_kid(&self) -> &str {
+        &self.keys[self.active].kid
+    }
+
+    /// Sign claims with the active key, naming it in the `kid` header. Once
+    /// the active key passes its `not_after` nothing is signed until
+    /// `jwt_active_kid` is rotated, since no one could verify the tokens.
+    pub fn encode<T: Serialize>(&self, claims: &T) -> AppResult<String> {
+        let key = &self.keys[self.active];
+        if !key.is_live() {
+            return Err(config_error(format!(
+                "Active JWT key {} has passed its not_after; rotate jwt_active_kid",
+                key.kid
+            )));
+        }
+        let mut header = Header::new(key.algorithm);
+        header.kid = Some(key.kid.clone());
+        Ok(jsonwebtoken::encode(&header, claims, &key.encoding)?)
+    }
+
+    /// Verify a token against the key named by its `kid`. The algorithm is
+    /// taken from the key, never from the token header.
+    pub fn decode<T: DeserializeOwned>(&self, token: &str) -> AppResult<TokenData<T>> {
+        let header = jsonwebtoken::decode_header(token)?;
+        let kid = header.kid.as_deref().unwrap_or(DEFAULT_KID);
+
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.kid == kid && key.is_live())
+            .ok_or_else(|| AppError::Auth(format!("Unknown or expired signing key: {}", kid)))?;
+
+        Ok(jsonwebtoken::decode::<T>(
+            token,
+            &key.decoding,
+            &Validation::new(key.algorithm),
+        )?)
+    }
+
+    /// Public keys that may still verify tokens, for `/.well-known/jwks.json`.
+    /// HS256 secrets are never published.
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .keys
+                .iter()
+                .filter(|key| key.is_live())
+                .filter_map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+}
+
+impl SigningKey {
+    /// Parse `kid:alg:path[:not_after]`, where `path` is a PEM private key
+    /// (or a file holding the secret for HS256) and `not_after` a Unix timestamp
+    fn parse(entry: &str) -> AppResult<Self> {
+        let parts: Vec<&str> = entry.split(':').map(str::trim).collect();
+        let (kid, algorithm, path, not_after) = match parts.as_slice() {
+            [kid, algorithm, path] => (*kid, *algorithm, *path, None),
+            [kid, algorithm, path, not_after] => {
+                let not_after = not_after.parse::<i64>().map_err(|_| {
+                    config_error(format!("Invalid not_after in JWT key entry: {}", entry))
+                })?;
+                (*kid, *algorithm, *path, Some(not_after))
+            }
+            _ => return Err(config_error(format!("Invalid JWT key entry: {}", entry))),
+        };
+        if kid.is_empty() {
+            return Err(config_error(format!("Missing key id in JWT key entry: {}", entry)));
+        }
+
+        let material = std::fs::read(path).map_err(|e| {
+            config_error(format!("Cannot read JWT key {} from {}: {}", kid, path, e))
+        })?;
+
+        match algorithm {
+            "HS256" => {
+                let secret = String::from_utf8_lossy(&material).trim().to_string();
+                if secret.is_empty() {
+                    return Err(config_error(format!("JWT key {} has an empty secret", kid)));
+                }
+                Ok(Self::from_secret(kid.to_string(), secret.as_bytes(), not_after))
+            }
+            "RS256" => Self::from_rsa_pem(kid, &material, not_after),
+            "EdDSA" => Self::from_ed_pem(kid, &material, not_after),
+            other => Err(config_error(format!(
+                "Unsupported JWT algorithm {} for key {} (use HS256, RS256 or EdDSA)",
+                other, kid
+            ))),
+        }
+    }
+
+    fn from_secret(kid: String, secret: &[u8], not_after: Option<i64>) -> Self {
+        Self {
+            kid,
+            algorithm: Algorithm::HS256,
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+            jwk: None,
+            not_after,
+        }
+    }
+
+    fn from_rsa_pem(kid: &str, pem_bytes: &[u8], not_after: Option<i64>) -> AppResult<Self> {
+        let invalid = || config_error(format!("JWT key {} is not an RSA private key", kid));
+
+        let pem = pem::parse(pem_bytes).map_err(|_| invalid())?;
+        let key_pair = match pem.tag() {
+            "RSA PRIVATE KEY" => ring::signature::RsaKeyPair::from_der(pem.contents()),
+            "PRIVATE KEY" => ring::signature::RsaKeyPair::from_pkcs8(pem.contents()),
+            _ => return Err(invalid()),
+        }
+        .map_err(|_| invalid())?;
+
+        let public = ring::rsa::PublicKeyComponents::<Vec<u8>>::from(key_pair.public());
+        let jwk = public_jwk(
+            kid,
+            KeyAlgorithm::RS256,
+            AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(&public.n),
+                e: URL_SAFE_NO_PAD.encode(&public.e),
+            }),
+        );
+
+        Ok(Self {
+            kid: kid.to_string(),
+            algorithm: Algorithm::RS256,
+            encoding: EncodingKey::from_rsa_pem(pem_bytes).map_err(|_| invalid())?,
+            decoding: DecodingKey::from_jwk(&jwk).map_err(|_| invalid())?,
+            jwk: Some(jwk),
+            not_after,
+        })
+    }
+
+    fn from_ed_pem(kid: &str, pem_bytes: &[u8], not_after: Option<i64>) -> AppResult<Self> {
+        let invalid = || config_error(format!("JWT key {} is not an Ed25519 private key", kid));
+
+        let pem = pem::parse(pem_bytes).map_err(|_| invalid())?;
+        if pem.tag() != "PRIVATE KEY" {
+            return Err(invalid());
+        }
+        let key_pair = ring::signature::Ed25519KeyPair::from_pkcs8_maybe_unchecked(pem.contents())
+            .map_err(|_| invalid())?;
+
+        let jwk = public_jwk(
+            kid,
+            KeyAlgorithm::EdDSA,
+            AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref()),
+            }),
+        );
+
+        Ok(Self {
+            kid: kid.to_string(),
+            algorithm: Algorithm::EdDSA,
+            encoding: EncodingKey::from_ed_pem(pem_bytes).map_err(|_| invalid())?,
+            decoding: DecodingKey::from_jwk(&jwk).map_err(|_| invalid())?,
+            jwk: Some(jwk),
+            not_after,
+        })
+    }
+
+    fn is_live(&self) -> bool {
+        self.not_after
+            .is_none_or(|not_after| Utc::now().timestamp() < not_after)
+    }
+}
+
+fn public_jwk(kid: &str, algorithm: KeyAlgorithm, parameters: AlgorithmParameters) -> Jwk {
+    Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(algorithm),
+            key_id: Some(kid.to_string()),
+            ..Default::default()
+        },
+        algorithm: parameters,
+    }
+}
+
+fn config_error(message: String) -> AppError {
+    AppError::Config(config::ConfigError::Message(message))
+}
@@ -2,6 +2,7 @@ use std::fs;
 use std::path::Path;
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use reqwest::Client;
 
 // KV storage implementation that can work with both local development
@@ -27,6 +28,12 @@ pub struct BlogPostKv {
     pub tags: Vec<String>,
     pub date_published: String,
     pub visibility: String, // "public" | "private"
+    #[serde(default = "default_post_status")]
+    pub status: String, // "draft" | "in_review" | "scheduled" | "published" | "archived"
+    #[serde(default)]
+    pub publish_at: Option<String>, // RFC 3339; when a scheduled post goes live
+    #[serde(default)]
+    pub unpublish_at: Option<String>, // RFC 3339; when a published post comes down
     pub cover_image: Option<String>,
     pub attachments: Vec<String>,
     pub meta: Option<serde_json::Value>, // optional extra metadata
@@ -37,10 +44,69 @@ pub struct BlogIndexEntry {
     pub slug: String,
     pub title: String,
     pub summary: String,
+    #[serde(default)] // Written before it was indexed; filled in at startup
+    pub author_id: String,
     pub cover_image: Option<String>,
     pub date_published: String,
     pub tags: Vec<String>,
     pub visibility: String,
+    #[serde(default = "default_post_status")]
+    pub status: String,
+    #[serde(default)]
+    pub publish_at: Option<String>,
+    #[serde(default)]
+    pub unpublish_at: Option<String>,
+}
+
+// Posts stored before the editorial status existed were live
+fn default_post_status() -> String {
+    "published".to_string()
+}
+
+/// Whether an optional RFC 3339 time is set and not after `now`
+pub fn has_passed(at: Option<&str>, now: DateTime<Utc>) -> bool {
+    at.and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+        .is_some_and(|at| at <= now)
+}
+
+// Public, published and inside the publish window
+fn is_live(
+    visibility: &str,
+    status: &str,
+    publish_at: Option<&str>,
+    unpublish_at: Option<&str>,
+    now: DateTime<Utc>,
+) -> bool {
+    visibility == "public"
+        && status == "published"
+        && (publish_at.is_none() || has_passed(publish_at, now))
+        && !has_passed(unpublish_at, now)
+}
+
+impl BlogPostKv {
+    /// Whether the post may be served by the public endpoints at `now`
+    pub fn is_live(&self, now: DateTime<Utc>) -> bool {
+        is_live(
+            &self.visibility,
+            &self.status,
+            self.publish_at.as_deref(),
+            self.unpublish_at.as_deref(),
+            now,
+        )
+    }
+}
+
+impl BlogIndexEntry {
+    /// Whether the post may be listed publicly at `now`
+    pub fn is_live(&self, now: DateTime<Utc>) -> bool {
+        is_live(
+            &self.visibility,
+            &self.status,
+            self.publish_at.as_deref(),
+            self.unpublish_at.as_deref(),
+            now,
+        )
+    }
 }
 
 impl KvStore {
@@ -221,6 +287,21 @@ impl KvStore {
         Ok(())
     }
 
+    /// Move a post to `post.slug`. The new key is written before the old one
+    /// is deleted and the index is rewritten once, so readers always find the
+    /// post under one of the two slugs. Every step can be repeated, so running
+    /// it again after a failure finishes the move.
+    pub async fn rename_blog_post(&self, old_slug: &str, post: &BlogPostKv) -> Result<()> {
+        let key = format!("blog:post:{}", post.slug);
+        let value = serde_json::to_string(post)?;
+        self.put(&key, &value).await?;
+
+        self.update_blog_index_entry(Some(old_slug), post).await?;
+
+        self.delete(&format!("blog:post:{}", old_slug)).await?;
+        Ok(())
+    }
+
     pub async fn get_blog_index(&self) -> Result<Vec<BlogIndexEntry>> {
         if let Some(content) = self.get("blog:index").await? {
             let index: Vec<BlogIndexEntry> = serde_json::from_str(&content)?;
@@ -230,21 +311,52 @@ impl KvStore {
         }
     }
 
+    /// Fill in `author_id` on index entries written before it was indexed,
+    /// from their posts, so author filters find them. Returns how many
+    /// entries changed; the index is only rewritten if any did.
+    pub async fn backfill_blog_index_authors(&self) -> Result<usize> {
+        let mut index = self.get_blog_index().await?;
+        let mut filled = 0;
+        for entry in index.iter_mut().filter(|entry| entry.author_id.is_empty()) {
+            if let Some(post) = self.get_blog_post(&entry.slug).await?
+                && !post.author_id.is_empty()
+            {
+                entry.author_id = post.author_id;
+                filled += 1;
+            }
+        }
+
+        if filled > 0 {
+            let value = serde_json::to_string(&index)?;
+            self.put("blog:index", &value).await?;
+        }
+        Ok(filled)
+    }
+
     async fn update_blog_index(&self, post: &BlogPostKv) -> Result<()> {
+        self.update_blog_index_entry(None, post).await
+    }
+
+    // Replace the post's entry, and the entry under its previous slug if renamed
+    async fn update_blog_index_entry(&self, old_slug: Option<&str>, post: &BlogPostKv) -> Result<()> {
         let mut index = self.get_blog_index().await?;
         
         // Remove existing entry if it exists
-        index.retain(|entry| entry.slug != post.slug);
+        index.retain(|entry| entry.slug != post.slug && Some(entry.slug.as_str()) != old_slug);
         
         // Add new entry
         let entry = BlogIndexEntry {
             slug: post.slug.clone(),
             title: post.title.clone(),
             summary: post.summary.clone(),
+            author_id: post.author_id.clone(),
             cover_image: post.cover_image.clone(),
             date_published: post.date_published.clone(),
             tags: post.tags.clone(),
             visibility: post.visibility.clone(),
+            status: post.status.clone(),
+            publish_at: post.publish_at.clone(),
+            unpublish_at: post.unpublish_at.clone(),
         };
         
         index.push(entry);
@@ -1,26 +1,41 @@
+pub mod api_keys;
 pub mod audit;
 pub mod auth;
 pub mod backup;
 pub mod blog;
 pub mod config;
+pub mod cookies;
 pub mod error;
+pub mod guardians;
 pub mod handlers;
+pub mod keyring;
 pub mod kv;
+pub mod mail;
 pub mod middleware;
 pub mod models;
+pub mod oidc;
+pub mod rate_limit;
+pub mod revocation;
+pub mod signup;
+pub mod slug;
 pub mod storage;
+pub mod totp;
+pub mod users;
 
 use sqlx::SqlitePool;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: SqlitePool,
     pub config: config::AppConfig,
     pub kv: kv::KvStore,
+    pub mailer: Arc<dyn mail::MailSender>, // Built once so the SMTP connection pool is shared
 }
 
 impl AppState {
-    pub fn new(db: SqlitePool, config: config::AppConfig, kv: kv::KvStore) -> Self {
-        Self { db, config, kv }
+    pub fn new(db: SqlitePool, config: config::AppConfig, kv: kv::KvStore) -> error::AppResult<Self> {
+        let mailer = mail::mail_sender_from_config(&config)?;
+        Ok(Self { db, config, kv, mailer })
     }
 }
@@ -0,0 +1,127 @@
+use crate::config::AppConfig;
+use crate::error::{AppError, AppResult};
+use async_trait::async_trait;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::fs;
+use uuid::Uuid;
+
+/// A plain-text transactional email
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Pluggable delivery backend for outgoing mail
+#[async_trait]
+pub trait MailSender: Send + Sync {
+    async fn send(&self, message: &MailMessage) -> AppResult<()>;
+}
+
+/// Build the mail sender selected by `mail_transport` ("smtp" or "file")
+pub fn mail_sender_from_config(config: &AppConfig) -> AppResult<Arc<dyn MailSender>> {
+    match config.mail_transport.as_str() {
+        "smtp" => Ok(Arc::new(SmtpMailSender::new(config)?)),
+        "file" => Ok(Arc::new(FileMailSender::new(&config.mail_outbox_dir))),
+        other => Err(AppError::Internal(format!(
+            "Unknown mail transport: {}",
+            other
+        ))),
+    }
+}
+
+/// Delivers mail through an SMTP relay using STARTTLS
+pub struct SmtpMailSender {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailSender {
+    pub fn new(config: &AppConfig) -> AppResult<Self> {
+        let host = config
+            .smtp_host
+            .as_ref()
+            .ok_or_else(|| AppError::Internal("SMTP host not configured".to_string()))?;
+
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+            .map_err(|e| AppError::Internal(format!("Invalid SMTP relay: {}", e)))?
+            .port(config.smtp_port);
+
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        let from = config
+            .mail_from
+            .parse()
+            .map_err(|_| AppError::Internal(format!("Invalid mail_from: {}", config.mail_from)))?;
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl MailSender for SmtpMailSender {
+    async fn send(&self, message: &MailMessage) -> AppResult<()> {
+        let to: Mailbox = message
+            .to
+            .parse()
+            .map_err(|_| AppError::Validation(format!("Invalid email address: {}", message.to)))?;
+
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&message.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(message.body.clone())
+            .map_err(|e| AppError::Internal(format!("Failed to build email: {}", e)))?;
+
+        self.transport
+            .send(email)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to send email: {}", e)))?;
+
+        Ok(())
+    }
+}
+
+/// Writes each message as a JSON file into an outbox directory instead of
+/// sending it (development and tests)
+pub struct FileMailSender {
+    outbox_dir: PathBuf,
+}
+
+impl FileMailSender {
+    pub fn new(outbox_dir: &str) -> Self {
+        Self {
+            outbox_dir: PathBuf::from(outbox_dir),
+        }
+    }
+}
+
+#[async_trait]
+impl MailSender for FileMailSender {
+    async fn send(&self, message: &MailMessage) -> AppResult<()> {
+        fs::create_dir_all(&self.outbox_dir).await?;
+
+        let filename = format!(
+            "{}-{}.json",
+            chrono::Utc::now().format("%Y%m%d%H%M%S"),
+            Uuid::new_v4()
+        );
+        let path = self.outbox_dir.join(filename);
+        fs::write(&path, serde_json::to_string_pretty(message)?).await?;
+
+        tracing::info!("Mail to {} written to outbox: {}", message.to, path.display());
+        Ok(())
+    }
+}
@@ -1,20 +1,36 @@
+mod api_keys;
 mod audit;
 mod auth;
 mod backup;
 mod blog;
 mod config;
+mod cookies;
 mod error;
+mod guardians;
 mod handlers;
+mod keyring;
 mod kv;
+mod mail;
 mod middleware;
 mod models;
+mod oidc;
+mod rate_limit;
+mod revocation;
+mod signup;
+mod slug;
 mod storage;
+mod totp;
+mod users;
 
 use crate::backup::BackupService;
+use crate::blog::BlogService;
 use crate::config::AppConfig;
 use crate::error::AppResult;
 use crate::kv::KvStore;
+use crate::mail::MailSender;
+use crate::revocation::RevocationService;
 use sqlx::SqlitePool;
+use std::sync::Arc;
 use tokio_cron_scheduler::{Job, JobScheduler};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -23,11 +39,13 @@ pub struct AppState {
     pub db: SqlitePool,
     pub config: AppConfig,
     pub kv: KvStore,
+    pub mailer: Arc<dyn MailSender>, // Built once so the SMTP connection pool is shared
 }
 
 impl AppState {
-    pub fn new(db: SqlitePool, config: AppConfig, kv: KvStore) -> Self {
-        Self { db, config, kv }
+    pub fn new(db: SqlitePool, config: AppConfig, kv: KvStore) -> AppResult<Self> {
+        let mailer = mail::mail_sender_from_config(&config)?;
+        Ok(Self { db, config, kv, mailer })
     }
 }
 
@@ -48,6 +66,14 @@ async fn main() -> AppResult<()> {
         config.environment
     );
 
+    // Fail fast on a malformed sign-up policy rather than on first login
+    signup::SignupPolicy::from_config(&config)?;
+    cookies::CookiePolicy::from_config(&config)?;
+    oidc::OidcRegistry::from_config(&config)?;
+    middleware::TrustedProxies::from_config(&config)?;
+    let keyring = keyring::Keyring::for_config(&config)?;
+    tracing::info!("Signing JWTs with key {}", keyring.active_kid());
+
     // Initialize database with WAL mode and performance optimizations
     let db = initialize_database(&config.database_url).await?;
     tracing::info!("Connected to database with WAL mode enabled");
@@ -56,6 +82,9 @@ async fn main() -> AppResult<()> {
     sqlx::migrate!("./migrations").run(&db).await?;
     tracing::info!("Database migrations completed");
 
+    // Fail fast on an unknown rate limit backend
+    rate_limit::rate_limit_store_from_config(&config, &db)?;
+
     // Initialize KV store with proper storage directory
     let kv_storage_dir = if config.environment == "development" {
         "kv_storage"
@@ -68,8 +97,20 @@ async fn main() -> AppResult<()> {
         config.environment
     );
 
+    // Index entries from before author_id was indexed can't be filtered by author
+    let backfilled = kv.backfill_blog_index_authors().await?;
+    if backfilled > 0 {
+        tracing::info!("Backfilled author_id on {} blog index entries", backfilled);
+    }
+
+    // Slug changes interrupted part-way through their KV writes
+    let moved = BlogService::new(kv.clone(), db.clone()).finish_slug_moves().await?;
+    if moved > 0 {
+        tracing::info!("Finished {} interrupted blog post slug changes", moved);
+    }
+
     // Initialize application state
-    let state = AppState::new(db.clone(), config.clone(), kv);
+    let state = AppState::new(db.clone(), config.clone(), kv.clone())?;
 
     // Initialize default admin user if in development
     if config.environment == "development" {
@@ -81,6 +122,12 @@ async fn main() -> AppResult<()> {
         start_backup_scheduler(db.clone(), config.clone()).await?;
     }
 
+    // Prune revocations of expired tokens
+    start_revocation_prune_scheduler(db.clone(), config.clone()).await?;
+
+    // Take scheduled posts live and expired ones down
+    start_publish_scheduler(kv, db.clone(), config.clone()).await?;
+
     // Create router
     let app = handlers::create_router(state);
 
@@ -89,7 +136,12 @@ async fn main() -> AppResult<()> {
     let listener = tokio::net::TcpListener::bind(&addr).await?;
     tracing::info!("Server running on http://{}", addr);
 
-    axum::serve(listener, app).await?;
+    // Peer addresses back the per-IP rate limits when no proxy header is set
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -191,3 +243,52 @@ async fn start_backup_scheduler(db: SqlitePool, config: AppConfig) -> AppResult<
     tracing::info!("Backup scheduler started with schedule: {}", schedule);
     Ok(())
 }
+
+/// Start the scheduler that deletes revocations past their `expires_at`
+async fn start_revocation_prune_scheduler(db: SqlitePool, config: AppConfig) -> AppResult<()> {
+    let scheduler = JobScheduler::new().await?;
+
+    let schedule = config.revocation_prune_schedule.clone();
+
+    let job = Job::new_async(schedule.as_str(), move |_uuid, _l| {
+        let db = db.clone();
+        Box::pin(async move {
+            if let Err(e) = RevocationService::new(db).prune_expired().await {
+                tracing::error!("Revocation pruning failed: {}", e);
+            }
+        })
+    })?;
+
+    scheduler.add(job).await?;
+    scheduler.start().await?;
+
+    tracing::info!(
+        "Revocation prune scheduler started with schedule: {}",
+        schedule
+    );
+    Ok(())
+}
+
+/// Start the scheduler that publishes scheduled posts and archives expired ones
+async fn start_publish_scheduler(kv: KvStore, db: SqlitePool, config: AppConfig) -> AppResult<()> {
+    let scheduler = JobScheduler::new().await?;
+
+    let schedule = config.publish_schedule.clone();
+
+    let job = Job::new_async(schedule.as_str(), move |_uuid, _l| {
+        let blog_service = BlogService::new(kv.clone(), db.clone());
+        Box::pin(async move {
+            match blog_service.publish_due_posts(chrono::Utc::now()).await {
+                Ok(0) => {}
+                Ok(changed) => tracing::info!("Publish scheduler updated {} posts", changed),
+                Err(e) => tracing::error!("Scheduled publishing failed: {}", e),
+            }
+        })
+    })?;
+
+    scheduler.add(job).await?;
+    scheduler.start().await?;
+
+    tracing::info!("Publish scheduler started with schedule: {}", schedule);
+    Ok(())
+}
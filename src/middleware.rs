@@ -1,36 +1,297 @@
-use crate::auth::AuthService;
-use crate::models::UserResponse;
+use crate::api_keys::ApiKeyService;
+use crate::audit::AuditService;
+use crate::auth::{AuthService, CSRF_HEADER};
+use crate::config::AppConfig;
+use crate::error::{AppError, AppResult};
+use crate::models::{Claims, ClientInfo, Permission, UserResponse};
+use crate::rate_limit::AuthRateLimiter;
 use crate::AppState;
 use axum::{
-    extract::{Extension, Request, State},
-    http::{header, StatusCode},
+    extract::{ConnectInfo, Extension, FromRequestParts, Request, State},
+    http::{header, request::Parts, Extensions, HeaderMap, Method, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
 
 // Extension type for authenticated user
 pub type AuthUser = Extension<UserResponse>;
 
-// Middleware to verify authentication for protected routes
+// Extension type for the verified access token of the request
+pub type AuthClaims = Extension<Claims>;
+
+/// The admin behind an impersonation token; the request's `AuthUser` is the
+/// user being viewed as
+#[derive(Clone)]
+pub struct Impersonator(pub UserResponse);
+
+/// Read a cookie value from the request's Cookie header
+pub fn extract_cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    let cookie_str = headers.get(header::COOKIE)?.to_str().ok()?;
+    cookie_str.split(';').find_map(|cookie| {
+        let (key, value) = cookie.trim().split_once('=')?;
+        (key == name).then(|| value.to_string())
+    })
+}
+
+/// Proxies whose forwarding headers are believed, from `trusted_proxies`
+/// (IP addresses or CIDR ranges). Headers from any other peer are ignored,
+/// since a client can put any address in them.
+pub struct TrustedProxies {
+    ranges: Vec<(IpAddr, u8)>,
+}
+
+impl TrustedProxies {
+    pub fn from_config(config: &AppConfig) -> AppResult<Self> {
+        let ranges = config
+            .trusted_proxies
+            .iter()
+            .map(|entry| {
+                parse_ip_range(entry).ok_or_else(|| {
+                    AppError::Config(config::ConfigError::Message(format!(
+                        "Invalid trusted proxy (expected an IP or CIDR range): {}",
+                        entry
+                    )))
+                })
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+        Ok(Self { ranges })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.ranges.iter().any(|&(network, prefix)| in_range(ip, network, prefix))
+    }
+
+    /// The client behind `peer`: the peer itself, or when it is a trusted
+    /// proxy the address it forwarded. `X-Forwarded-For` is read from the
+    /// right, skipping our own proxies, so a client cannot prepend entries.
+    pub fn client_ip(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<String> {
+        let peer = peer?;
+        if !self.contains(peer) {
+            return Some(peer.to_canonical().to_string());
+        }
+
+        let forwarded = header_value(headers, "cf-connecting-ip")
+            .and_then(|value| value.parse::<IpAddr>().ok())
+            .or_else(|| {
+                let chain = header_value(headers, "x-forwarded-for")?;
+                let hops: Vec<IpAddr> = chain
+                    .split(',')
+                    .filter_map(|hop| hop.trim().parse().ok())
+                    .collect();
+                hops.iter()
+                    .rev()
+                    .find(|hop| !self.contains(**hop))
+                    .or(hops.first())
+                    .copied()
+            })
+            .or_else(|| header_value(headers, "x-real-ip").and_then(|value| value.parse().ok()));
+
+        Some(forwarded.unwrap_or(peer).to_canonical().to_string())
+    }
+}
+
+/// Parse `ip` or `ip/prefix`
+fn parse_ip_range(entry: &str) -> Option<(IpAddr, u8)> {
+    let (ip, prefix) = match entry.trim().split_once('/') {
+        Some((ip, prefix)) => (ip.parse::<IpAddr>().ok()?, Some(prefix.parse::<u8>().ok()?)),
+        None => (entry.trim().parse::<IpAddr>().ok()?, None),
+    };
+    let ip = ip.to_canonical();
+    let max_prefix = if ip.is_ipv4() { 32 } else { 128 };
+    let prefix = prefix.unwrap_or(max_prefix);
+    (prefix <= max_prefix).then_some((ip, prefix))
+}
+
+fn in_range(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// The TCP peer, when the server was started with connect info
+fn peer_ip(extensions: &Extensions) -> Option<IpAddr> {
+    extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+/// User agent and client IP of a request, trusting forwarding headers only
+/// from the configured proxies
+pub fn client_info(config: &AppConfig, peer: Option<IpAddr>, headers: &HeaderMap) -> ClientInfo {
+    let ip_address = TrustedProxies::from_config(config)
+        .ok()
+        .and_then(|proxies| proxies.client_ip(peer, headers));
+
+    ClientInfo {
+        user_agent: header_value(headers, header::USER_AGENT.as_str()),
+        ip_address,
+    }
+}
+
+// Lets handlers take the `ClientInfo` of the request as an argument
+impl FromRequestParts<AppState> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        Ok(client_info(&state.config, peer_ip(&parts.extensions), &parts.headers))
+    }
+}
+
+/// Throttle a route per client IP; over the limit the client gets 429 with
+/// `Retry-After`. Requests without a known client address are not counted.
+pub async fn rate_limit_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let peer = peer_ip(request.extensions());
+    let ip_address = client_info(&state.config, peer, request.headers()).ip_address;
+
+    if let Some(ip_address) = ip_address {
+        let result = match AuthRateLimiter::new(state.db.clone(), state.config.clone()) {
+            Ok(limiter) => limiter.check_ip(&ip_address).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            return e.into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+/// Methods that must not change state and so need no CSRF token
+fn is_safe_method(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Which credentials a group of routes accepts
+#[derive(Clone, Copy)]
+struct AuthPolicy {
+    api_keys: bool,                  // Server-to-server keys, limited to their scopes
+    allow_impersonated_writes: bool, // Impersonation tokens may change state
+}
+
+// Middleware to verify authentication for protected routes. Impersonation
+// tokens may only read through it.
 pub async fn auth_middleware(
     State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let policy = AuthPolicy {
+        api_keys: true,
+        allow_impersonated_writes: false,
+    };
+    authenticate_request(state, request, next, policy).await
+}
+
+/// Like `auth_middleware`, but only for user sessions. API keys act as their
+/// owner without being them, so the owner's own routes (`/api/users/me/*`:
+/// sessions, second factor, children) refuse them.
+pub async fn session_auth_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let policy = AuthPolicy {
+        api_keys: false,
+        allow_impersonated_writes: false,
+    };
+    authenticate_request(state, request, next, policy).await
+}
+
+/// Like `session_auth_middleware`, but also lets impersonation tokens make
+/// changes. Only for routes that must work while impersonating, such as
+/// ending it.
+pub async fn auth_middleware_allowing_impersonation(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let policy = AuthPolicy {
+        api_keys: false,
+        allow_impersonated_writes: true,
+    };
+    authenticate_request(state, request, next, policy).await
+}
+
+async fn authenticate_request(
+    state: AppState,
     mut request: Request,
     next: Next,
+    policy: AuthPolicy,
 ) -> Result<Response, StatusCode> {
     let auth_service = AuthService::new(state.db.clone(), state.config.clone());
 
     let headers = request.headers();
 
+    // API keys for server-to-server clients, via X-Api-Key or Authorization: ApiKey
+    let api_key = headers
+        .get("x-api-key")
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| {
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("ApiKey "))
+        })
+        .map(|key| key.trim().to_string());
+
+    if let Some(api_key) = api_key {
+        if !policy.api_keys {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        let api_key_service = ApiKeyService::new(state.db.clone());
+        match api_key_service.authenticate(&api_key).await {
+            Ok(user) => {
+                // Synthetic user limited to the key's scopes
+                request.extensions_mut().insert(user);
+                return Ok(next.run(request).await);
+            }
+            Err(_) => return Err(StatusCode::UNAUTHORIZED),
+        }
+    }
+
     // First try Bearer token (for API compatibility)
     if let Some(auth_header) = headers.get(header::AUTHORIZATION) {
         if let Ok(auth_str) = auth_header.to_str() {
             if auth_str.starts_with("Bearer ") {
                 let token = &auth_str[7..];
-                match auth_service.verify_session_token(token).await {
-                    Ok(user) => {
-                        // Add user to request extensions
-                        request.extensions_mut().insert(user);
-                        return Ok(next.run(request).await);
+                match auth_service.authenticate(token).await {
+                    Ok((claims, user)) => {
+                        return run_authenticated(
+                            &auth_service,
+                            claims,
+                            user,
+                            request,
+                            next,
+                            policy.allow_impersonated_writes,
+                        )
+                        .await;
                     }
                     Err(_) => return Err(StatusCode::UNAUTHORIZED),
                 }
@@ -38,37 +299,165 @@ pub async fn auth_middleware(
         }
     }
 
-    // Then try session cookie
-    if let Some(cookie_header) = headers.get(header::COOKIE) {
-        if let Ok(cookie_str) = cookie_header.to_str() {
-            for cookie in cookie_str.split(';') {
-                let cookie = cookie.trim();
-                if cookie.starts_with("session=") {
-                    let session_value = &cookie[8..];
-                    match auth_service.verify_session_cookie(session_value).await {
-                        Ok(user) => {
-                            // Add user to request extensions
-                            request.extensions_mut().insert(user);
-                            return Ok(next.run(request).await);
-                        }
-                        Err(_) => return Err(StatusCode::UNAUTHORIZED),
+    // Then try the access token cookie. The refresh token cookie never
+    // authenticates a request; it is only accepted by /api/auth/refresh.
+    let session_cookie = auth_service
+        .cookie_policy()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Some(session_value) = extract_cookie(headers, session_cookie.session_name()) {
+        match auth_service.authenticate(&session_value).await {
+            Ok((claims, user)) => {
+                // Browsers attach the cookie to cross-site requests too, so
+                // writes must prove they came from our frontend
+                if !is_safe_method(request.method()) {
+                    let csrf_token = headers
+                        .get(CSRF_HEADER)
+                        .and_then(|value| value.to_str().ok())
+                        .unwrap_or_default();
+                    if auth_service.verify_csrf_token(&claims.jti, csrf_token).is_err() {
+                        return Err(StatusCode::FORBIDDEN);
                     }
                 }
+
+                return run_authenticated(
+                    &auth_service,
+                    claims,
+                    user,
+                    request,
+                    next,
+                    policy.allow_impersonated_writes,
+                )
+                .await;
             }
+            Err(_) => return Err(StatusCode::UNAUTHORIZED),
         }
     }
 
     Err(StatusCode::UNAUTHORIZED)
 }
 
-// Middleware to verify admin role for admin routes
-pub async fn admin_middleware(request: Request, next: Next) -> Result<Response, StatusCode> {
-    // Get user from request extensions (set by auth_middleware)
-    if let Some(user) = request.extensions().get::<UserResponse>() {
-        if user.role == "admin" {
-            return Ok(next.run(request).await);
+/// Run a request authenticated by an access token. Under impersonation the
+/// admin is exposed as `Impersonator`, writes are refused unless allowed,
+/// and every request is audited under the admin's id.
+async fn run_authenticated(
+    auth_service: &AuthService,
+    claims: Claims,
+    user: UserResponse,
+    mut request: Request,
+    next: Next,
+    allow_impersonated_writes: bool,
+) -> Result<Response, StatusCode> {
+    let actor = auth_service
+        .resolve_actor(&claims)
+        .await
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    let Some(actor) = actor else {
+        // Add user to request extensions
+        request.extensions_mut().insert(claims);
+        request.extensions_mut().insert(user);
+        return Ok(next.run(request).await);
+    };
+
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let jti = claims.jti.clone();
+    let user_id = user.id.clone();
+
+    let response = if is_safe_method(&method) || allow_impersonated_writes {
+        request.extensions_mut().insert(claims);
+        request.extensions_mut().insert(user);
+        request.extensions_mut().insert(Impersonator(actor.clone()));
+        next.run(request).await
+    } else {
+        AppError::Impersonation(format!("{} {}", method, path)).into_response()
+    };
+
+    let audit = AuditService::new(auth_service.db.clone())
+        .log_action(
+            &actor.id,
+            "impersonated_request".to_string(),
+            Some(user_id),
+            Some(serde_json::json!({
+                "method": method.as_str(),
+                "path": path,
+                "status": response.status().as_u16(),
+                "jti": jti,
+            })),
+        )
+        .await;
+    if let Err(e) = audit {
+        tracing::error!("Failed to audit impersonated request {} {}: {}", method, path, e);
+    }
+
+    Ok(response)
+}
+
+/// Route layer that only lets the request through if the authenticated
+/// user's role grants `permission`. Must run inside `auth_middleware`.
+pub fn require_permission(state: &AppState, permission: Permission) -> RequirePermissionLayer {
+    RequirePermissionLayer {
+        state: state.clone(),
+        permission,
+    }
+}
+
+#[derive(Clone)]
+pub struct RequirePermissionLayer {
+    state: AppState,
+    permission: Permission,
+}
+
+impl<S> Layer<S> for RequirePermissionLayer {
+    type Service = RequirePermission<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequirePermission {
+            inner,
+            state: self.state.clone(),
+            permission: self.permission,
         }
     }
+}
+
+#[derive(Clone)]
+pub struct RequirePermission<S> {
+    inner: S,
+    state: AppState,
+    permission: Permission,
+}
+
+impl<S> Service<Request> for RequirePermission<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
 
-    Err(StatusCode::FORBIDDEN)
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // Take the service that was polled ready, leave a clone in its place
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let state = self.state.clone();
+        let permission = self.permission;
+
+        Box::pin(async move {
+            // Get user from request extensions (set by auth_middleware)
+            let Some(user) = request.extensions().get::<UserResponse>().cloned() else {
+                return Ok(AppError::Auth("Authentication required".to_string()).into_response());
+            };
+
+            let auth_service = AuthService::new(state.db.clone(), state.config.clone());
+            match auth_service.ensure_user_permission(&user, permission).await {
+                Ok(()) => inner.call(request).await,
+                Err(e) => Ok(e.into_response()),
+            }
+        })
+    }
 }
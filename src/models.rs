@@ -1,3 +1,5 @@
+use crate::kv::BlogIndexEntry;
+use crate::slug::slugify;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -32,6 +34,168 @@ impl UserRole {
     }
 }
 
+/// Whether an account may be used. Suspended accounts are locked out without
+/// losing their data or sessions; pending ones have not been approved yet.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountStatus {
+    Active,
+    Suspended,
+    Pending,
+}
+
+impl AccountStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountStatus::Active => "active",
+            AccountStatus::Suspended => "suspended",
+            AccountStatus::Pending => "pending",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "active" => Some(AccountStatus::Active),
+            "suspended" => Some(AccountStatus::Suspended),
+            "pending" => Some(AccountStatus::Pending),
+            _ => None,
+        }
+    }
+}
+
+/// Editorial state of a blog post. Only published posts inside their
+/// publish window are served publicly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostStatus {
+    Draft,
+    InReview,
+    Scheduled,
+    Published,
+    Archived,
+}
+
+impl PostStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PostStatus::Draft => "draft",
+            PostStatus::InReview => "in_review",
+            PostStatus::Scheduled => "scheduled",
+            PostStatus::Published => "published",
+            PostStatus::Archived => "archived",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "draft" => Some(PostStatus::Draft),
+            "in_review" => Some(PostStatus::InReview),
+            "scheduled" => Some(PostStatus::Scheduled),
+            "published" => Some(PostStatus::Published),
+            "archived" => Some(PostStatus::Archived),
+            _ => None,
+        }
+    }
+
+    /// Statuses that put a public post in front of readers, now or later
+    pub fn goes_live(&self) -> bool {
+        matches!(self, PostStatus::Scheduled | PostStatus::Published)
+    }
+}
+
+/// How a guardian is related to a student
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuardianRelationship {
+    Mother,
+    Father,
+    Guardian,
+    Grandparent,
+    Sibling,
+    Other,
+}
+
+impl GuardianRelationship {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GuardianRelationship::Mother => "mother",
+            GuardianRelationship::Father => "father",
+            GuardianRelationship::Guardian => "guardian",
+            GuardianRelationship::Grandparent => "grandparent",
+            GuardianRelationship::Sibling => "sibling",
+            GuardianRelationship::Other => "other",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "mother" => Some(GuardianRelationship::Mother),
+            "father" => Some(GuardianRelationship::Father),
+            "guardian" => Some(GuardianRelationship::Guardian),
+            "grandparent" => Some(GuardianRelationship::Grandparent),
+            "sibling" => Some(GuardianRelationship::Sibling),
+            "other" => Some(GuardianRelationship::Other),
+            _ => None,
+        }
+    }
+}
+
+// Permissions granted to roles through the `role_permissions` table
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Permission {
+    #[serde(rename = "posts:write")]
+    PostsWrite,
+    #[serde(rename = "posts:publish")]
+    PostsPublish,
+    #[serde(rename = "media:upload")]
+    MediaUpload,
+    #[serde(rename = "audit:read")]
+    AuditRead,
+    #[serde(rename = "audit:manage")]
+    AuditManage,
+    #[serde(rename = "backup:restore")]
+    BackupRestore,
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:manage")]
+    UsersManage,
+    #[serde(rename = "api_keys:manage")]
+    ApiKeysManage,
+    #[serde(rename = "users:impersonate")]
+    UsersImpersonate,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 10] = [
+        Permission::PostsWrite,
+        Permission::PostsPublish,
+        Permission::MediaUpload,
+        Permission::AuditRead,
+        Permission::AuditManage,
+        Permission::BackupRestore,
+        Permission::UsersRead,
+        Permission::UsersManage,
+        Permission::ApiKeysManage,
+        Permission::UsersImpersonate,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::PostsWrite => "posts:write",
+            Permission::PostsPublish => "posts:publish",
+            Permission::MediaUpload => "media:upload",
+            Permission::AuditRead => "audit:read",
+            Permission::AuditManage => "audit:manage",
+            Permission::BackupRestore => "backup:restore",
+            Permission::UsersRead => "users:read",
+            Permission::UsersManage => "users:manage",
+            Permission::ApiKeysManage => "api_keys:manage",
+            Permission::UsersImpersonate => "users:impersonate",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Permission::ALL.into_iter().find(|p| p.as_str() == s)
+    }
+}
+
 // User model for new passwordless authentication system
 #[derive(Serialize, Deserialize, Clone, Debug, sqlx::FromRow)]
 pub struct User {
@@ -41,6 +205,10 @@ pub struct User {
     pub google_id: Option<String>, // For Google OAuth integration
     pub full_name: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub deactivated_at: Option<DateTime<Utc>>, // Set while the account is blocked from signing in
+    pub status: String, // Store as string for database compatibility
+    pub status_reason: Option<String>, // Why the account was suspended
+    pub status_changed_at: Option<DateTime<Utc>>,
 }
 
 impl User {
@@ -52,6 +220,10 @@ impl User {
             google_id: None,
             full_name,
             created_at: Utc::now(),
+            deactivated_at: None,
+            status: AccountStatus::Active.as_str().to_string(),
+            status_reason: None,
+            status_changed_at: None,
         }
     }
 
@@ -68,12 +240,37 @@ impl User {
             google_id: Some(google_id),
             full_name,
             created_at: Utc::now(),
+            deactivated_at: None,
+            status: AccountStatus::Active.as_str().to_string(),
+            status_reason: None,
+            status_changed_at: None,
         }
     }
 
     pub fn get_role(&self) -> Option<UserRole> {
         UserRole::from_str(&self.role)
     }
+
+    pub fn get_status(&self) -> Option<AccountStatus> {
+        AccountStatus::parse(&self.status)
+    }
+
+    /// Why the account may not sign in or use its sessions, if it may not
+    pub fn inactive_reason(&self) -> Option<&'static str> {
+        if self.deactivated_at.is_some() {
+            return Some("Account is deactivated");
+        }
+        match self.get_status() {
+            Some(AccountStatus::Active) => None,
+            Some(AccountStatus::Suspended) => Some("Account is suspended"),
+            Some(AccountStatus::Pending) => Some("Account is pending approval"),
+            None => Some("Invalid account status"),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.inactive_reason().is_none()
+    }
 }
 
 // JWT Revocation model for blacklisting tokens
@@ -96,6 +293,87 @@ impl Revocation {
     }
 }
 
+// Opaque refresh token; rotated on every use within a token family
+#[derive(Serialize, Deserialize, Clone, Debug, sqlx::FromRow)]
+pub struct RefreshToken {
+    pub id: String,
+    pub user_id: String,
+    pub family_id: String, // Shared by every token rotated from the same login
+    pub token_hash: String, // SHA-256 hex of the token handed to the client
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub rotated_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub mfa_verified: bool, // The family's login passed a second factor
+}
+
+impl RefreshToken {
+    pub fn new(
+        user_id: String,
+        family_id: String,
+        token_hash: String,
+        ttl_days: i64,
+        mfa_verified: bool,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            family_id,
+            token_hash,
+            created_at: now,
+            expires_at: now + chrono::Duration::days(ttl_days),
+            rotated_at: None,
+            revoked_at: None,
+            mfa_verified,
+        }
+    }
+}
+
+// Issued access token (jti) with the client it was issued to
+#[derive(Serialize, Deserialize, Clone, Debug, sqlx::FromRow)]
+pub struct Session {
+    pub jti: String,
+    pub user_id: String,
+    pub family_id: String, // Login the token belongs to; shared with its refresh tokens
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>, // Refresh token expiry for the login
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl Session {
+    pub fn new(
+        jti: String,
+        user_id: String,
+        family_id: String,
+        client: &ClientInfo,
+        expires_at: DateTime<Utc>,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            jti,
+            user_id,
+            family_id,
+            user_agent: client.user_agent.clone(),
+            ip_address: client.ip_address.clone(),
+            created_at: now,
+            last_seen_at: now,
+            expires_at,
+            revoked_at: None,
+        }
+    }
+}
+
+// Device details recorded against a session when it is issued
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
 // New JSON-based audit log model with incremental appends
 #[derive(Serialize, Deserialize, Clone, Debug, sqlx::FromRow)]
 pub struct AuditLog {
@@ -120,6 +398,149 @@ impl AuditLog {
     }
 }
 
+// Server-side record of an in-flight OAuth login (state, nonce, PKCE verifier)
+#[derive(Serialize, Deserialize, Clone, Debug, sqlx::FromRow)]
+pub struct OAuthState {
+    pub state: String,
+    pub provider: String, // Name of the OIDC provider the login was started with
+    pub nonce: String,
+    pub code_verifier: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>, // Set once the callback has used it
+    pub link_user_id: Option<String>, // Signed-in user linking the provider account
+}
+
+impl OAuthState {
+    pub fn new(
+        state: String,
+        provider: String,
+        nonce: String,
+        code_verifier: String,
+        ttl_seconds: i64,
+        link_user_id: Option<String>,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            state,
+            provider,
+            nonce,
+            code_verifier,
+            created_at: now,
+            expires_at: now + chrono::Duration::seconds(ttl_seconds),
+            consumed_at: None,
+            link_user_id,
+        }
+    }
+}
+
+// Single-use email sign-in token; only the hash is persisted
+#[derive(Serialize, Deserialize, Clone, Debug, sqlx::FromRow)]
+pub struct MagicLinkToken {
+    pub id: String,
+    pub user_id: String,
+    pub token_hash: String, // SHA-256 hex of the token sent by email
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+}
+
+impl MagicLinkToken {
+    pub fn new(user_id: String, token_hash: String, ttl_minutes: i64) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            token_hash,
+            created_at: now,
+            expires_at: now + chrono::Duration::minutes(ttl_minutes),
+            consumed_at: None,
+        }
+    }
+}
+
+// TOTP second factor of a user; pending until `enabled_at` is set
+#[derive(Serialize, Deserialize, Clone, Debug, sqlx::FromRow)]
+pub struct TotpCredential {
+    pub user_id: String,
+    #[serde(skip_serializing)]
+    pub secret: String, // Base32
+    pub created_at: DateTime<Utc>,
+    pub enabled_at: Option<DateTime<Utc>>,
+    pub last_used_step: Option<i64>, // Rejects replays of an accepted code
+}
+
+// Login waiting for its second factor; only the token hash is persisted
+#[derive(Serialize, Deserialize, Clone, Debug, sqlx::FromRow)]
+pub struct MfaChallenge {
+    pub id: String,
+    pub user_id: String,
+    pub token_hash: String, // SHA-256 hex of the token returned to the client
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub consumed_at: Option<DateTime<Utc>>,
+    pub failed_attempts: i64,
+}
+
+impl MfaChallenge {
+    pub fn new(user_id: String, token_hash: String, ttl_minutes: i64) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            token_hash,
+            created_at: now,
+            expires_at: now + chrono::Duration::minutes(ttl_minutes),
+            consumed_at: None,
+            failed_attempts: 0,
+        }
+    }
+}
+
+// Admin-issued API key; the secret is only stored as a bcrypt hash
+#[derive(Serialize, Deserialize, Clone, Debug, sqlx::FromRow)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub key_prefix: String, // Public part of the key used to find this row
+    #[serde(skip_serializing)]
+    pub key_hash: String,
+    pub scopes: String, // JSON array of permissions
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn new(
+        name: String,
+        key_prefix: String,
+        key_hash: String,
+        scopes: &[Permission],
+        created_by: String,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            key_prefix,
+            key_hash,
+            scopes: serde_json::to_string(scopes).unwrap_or_else(|_| "[]".to_string()),
+            created_by,
+            created_at: Utc::now(),
+            expires_at,
+            last_used_at: None,
+            revoked_at: None,
+        }
+    }
+
+    pub fn get_scopes(&self) -> Vec<Permission> {
+        serde_json::from_str(&self.scopes).unwrap_or_default()
+    }
+}
+
 // Individual audit action for JSON storage
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AuditAction {
@@ -161,69 +582,68 @@ pub struct BlogPost {
     pub updated_at: DateTime<Utc>,
 }
 
+/// The editable fields of a `BlogPost`, as `new` and `update` set them
+pub struct BlogPostContent {
+    pub title: String,
+    pub content: String,
+    pub excerpt: String,
+    pub tags: Vec<String>,
+    pub visibility: String,
+    pub cover_image: Option<String>,
+    pub inline_images: Vec<String>,
+    pub attachments: Vec<String>,
+}
+
+impl From<CreateBlogPostRequest> for BlogPostContent {
+    fn from(payload: CreateBlogPostRequest) -> Self {
+        // Generate excerpt from body_html (first 150 chars) without a summary
+        let excerpt = payload.summary.unwrap_or_else(|| {
+            let plain_text = payload.body_html.chars().take(150).collect::<String>();
+            format!("{}...", plain_text)
+        });
+        Self {
+            title: payload.title,
+            content: payload.body_html,
+            excerpt,
+            tags: payload.tags,
+            visibility: payload.visibility,
+            cover_image: payload.cover_image,
+            inline_images: vec![], // inline_images not in CreateBlogPostRequest
+            attachments: payload.attachments,
+        }
+    }
+}
+
 impl BlogPost {
-    pub fn new(
-        title: String,
-        content: String,
-        excerpt: String,
-        author_id: String,
-        tags: Vec<String>,
-        visibility: String,
-        cover_image: Option<String>,
-        inline_images: Vec<String>,
-        attachments: Vec<String>,
-    ) -> Self {
-        let slug = title
-            .to_lowercase()
-            .replace(" ", "-")
-            .chars()
-            .map(|c| {
-                if c.is_alphanumeric() || c == '-' {
-                    c
-                } else {
-                    '-'
-                }
-            })
-            .collect::<String>()
-            .trim_matches('-')
-            .to_string();
+    pub fn new(fields: BlogPostContent, author_id: String) -> Self {
+        let slug = slugify(&fields.title);
 
         Self {
             id: Uuid::new_v4().to_string(),
             slug,
-            title,
-            content,
-            excerpt,
+            title: fields.title,
+            content: fields.content,
+            excerpt: fields.excerpt,
             author_id,
-            tags,
-            visibility,
-            cover_image,
-            inline_images,
-            attachments,
+            tags: fields.tags,
+            visibility: fields.visibility,
+            cover_image: fields.cover_image,
+            inline_images: fields.inline_images,
+            attachments: fields.attachments,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         }
     }
 
-    pub fn update(
-        &mut self,
-        title: String,
-        content: String,
-        excerpt: String,
-        tags: Vec<String>,
-        visibility: String,
-        cover_image: Option<String>,
-        inline_images: Vec<String>,
-        attachments: Vec<String>,
-    ) {
-        self.title = title;
-        self.content = content;
-        self.excerpt = excerpt;
-        self.tags = tags;
-        self.visibility = visibility;
-        self.cover_image = cover_image;
-        self.inline_images = inline_images;
-        self.attachments = attachments;
+    pub fn update(&mut self, fields: BlogPostContent) {
+        self.title = fields.title;
+        self.content = fields.content;
+        self.excerpt = fields.excerpt;
+        self.tags = fields.tags;
+        self.visibility = fields.visibility;
+        self.cover_image = fields.cover_image;
+        self.inline_images = fields.inline_images;
+        self.attachments = fields.attachments;
         self.updated_at = Utc::now();
     }
 }
@@ -235,32 +655,275 @@ pub struct LoginRequest {
 }
 
 #[derive(Deserialize)]
-pub struct GoogleAuthRequest {
+pub struct MagicLinkVerifyRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct OidcAuthRequest {
     pub code: String,
     pub state: Option<String>,
 }
 
+#[derive(Serialize)]
+pub struct OidcAuthStartResponse {
+    pub provider: String,
+    pub authorization_url: String,
+    pub state: String,
+    pub expires_at: usize, // Unix timestamp after which the state is rejected
+}
+
+#[derive(Serialize)]
+pub struct OidcProvidersResponse {
+    pub providers: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize)]
-pub struct GoogleUserInfo {
+pub struct OidcUserInfo {
+    #[serde(alias = "sub")] // OIDC userinfo returns `sub`, Google's v2 endpoint returns `id`
     pub id: String,
     pub email: String,
+    #[serde(default)]
+    pub email_verified: Option<bool>,
+    pub name: Option<String>,
+    pub picture: Option<String>,
+}
+
+// Response from a provider's token endpoint after exchanging an authorization code
+#[derive(Deserialize)]
+pub struct OidcTokenResponse {
+    pub access_token: String,
+    pub id_token: String,
+}
+
+// Verified claims from a provider-issued ID token
+#[derive(Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String, // Stable account ID at the provider
+    pub aud: serde_json::Value, // A string, or an array when there are several audiences
+    pub exp: usize,
+    pub iat: usize,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
     pub name: Option<String>,
     pub picture: Option<String>,
+    pub nonce: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
+}
+
+/// First login step done; either a session or a second-factor challenge
+pub enum LoginOutcome {
+    Session(LoginResponse, String), // Response and access cookie
+    MfaRequired(MfaChallengeResponse),
+}
+
+#[derive(Serialize)]
+pub struct MfaChallengeResponse {
+    pub mfa_required: bool, // Always true; tells clients this is not a session
+    pub challenge_token: String,
+    pub enrollment_required: bool, // TOTP must be set up before the code is sent
+    pub expires_at: usize,
+}
+
+#[derive(Deserialize)]
+pub struct MfaChallengeRequest {
+    pub challenge_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct MfaVerifyRequest {
+    pub challenge_token: String,
+    pub code: String, // Current TOTP code or an unused recovery code
+}
+
+#[derive(Serialize)]
+pub struct MfaLoginResponse {
+    #[serde(flatten)]
+    pub session: LoginResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>, // Set when the login completed enrollment
+}
+
+#[derive(Serialize)]
+pub struct TotpEnrollmentResponse {
+    pub secret: String, // Base32, for manual entry
+    pub provisioning_uri: String, // otpauth:// URI to render as a QR code
+}
+
+#[derive(Deserialize)]
+pub struct TotpCodeRequest {
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>, // Shown once; only hashes are stored
 }
 
 #[derive(Serialize)]
 pub struct LoginResponse {
-    pub token: String,
+    pub token: String, // Short-lived access JWT
+    pub refresh_token: String,
+    pub csrf_token: String, // Echo in X-CSRF-Token on cookie-authenticated writes
     pub user: UserResponse,
     pub expires_at: usize, // Unix timestamp for client-side expiry checks
 }
 
+#[derive(Serialize, sqlx::FromRow)]
+pub struct SessionResponse {
+    pub jti: String, // Latest access token of the login; pass to DELETE to revoke it
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub current: bool, // Session making this request
+}
+
 #[derive(Serialize, Clone)]
 pub struct UserResponse {
     pub id: String,
     pub email: String,
     pub role: String,
     pub full_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<Vec<Permission>>, // Set for API keys instead of role-based permissions
+}
+
+#[derive(Deserialize, Default)]
+pub struct ImpersonateRequest {
+    pub reason: Option<String>, // Recorded in the audit log
+}
+
+#[derive(Serialize)]
+pub struct ImpersonationResponse {
+    pub token: String, // Bearer-only access JWT for the impersonated user
+    pub expires_at: usize,
+    pub user: UserResponse,  // Who the token acts as
+    pub actor: UserResponse, // The admin who requested it
+}
+
+#[derive(Deserialize)]
+pub struct UserListQuery {
+    pub page: Option<u32>, // 1-based
+    pub per_page: Option<u32>,
+    pub search: Option<String>, // Matched against email and full name
+    pub role: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct UserListResponse {
+    pub users: Vec<User>,
+    pub page: u32,
+    pub per_page: u32,
+    pub total: u64,
+}
+
+#[derive(Deserialize)]
+pub struct CreateUserRequest {
+    pub email: String,
+    pub role: String,
+    pub full_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateUserRequest {
+    pub role: Option<String>,
+    pub full_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SuspendUserRequest {
+    pub reason: String,
+}
+
+// Parent-student link
+#[derive(Serialize, Deserialize, Clone, Debug, sqlx::FromRow)]
+pub struct Guardianship {
+    pub guardian_id: String, // Parent
+    pub student_id: String,
+    pub relationship: String, // Store as string for database compatibility
+    pub is_primary_contact: bool,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct LinkGuardianRequest {
+    pub guardian_id: String,
+    pub student_id: String,
+    pub relationship: String,
+    #[serde(default)]
+    pub is_primary_contact: bool,
+}
+
+/// The user on the other side of a guardianship, with the link's details
+#[derive(Serialize, sqlx::FromRow)]
+pub struct LinkedUserResponse {
+    pub id: String,
+    pub email: String,
+    pub full_name: Option<String>,
+    pub relationship: String,
+    pub is_primary_contact: bool,
+    pub linked_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<String>, // e.g. "posts:write"
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct ApiKeyResponse {
+    pub id: String,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<Permission>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiKey> for ApiKeyResponse {
+    fn from(key: ApiKey) -> Self {
+        Self {
+            scopes: key.get_scopes(),
+            id: key.id,
+            name: key.name,
+            key_prefix: key.key_prefix,
+            created_by: key.created_by,
+            created_at: key.created_at,
+            expires_at: key.expires_at,
+            last_used_at: key.last_used_at,
+            revoked_at: key.revoked_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct CreatedApiKeyResponse {
+    pub key: String, // Shown once; only its hash is stored
+    pub api_key: ApiKeyResponse,
+}
+
+#[derive(Deserialize)]
+pub struct RolePermissionsRequest {
+    pub permissions: Vec<String>, // e.g. "posts:write"
+}
+
+#[derive(Serialize)]
+pub struct RolePermissionsResponse {
+    pub role: String,
+    pub permissions: Vec<Permission>,
 }
 
 #[derive(Deserialize)]
@@ -270,10 +933,150 @@ pub struct CreateBlogPostRequest {
     pub body_html: String,
     pub tags: Vec<String>,
     pub visibility: String,
+    #[serde(default)]
+    pub status: Option<String>, // Defaults to "published"
+    #[serde(default)]
+    pub publish_at: Option<DateTime<Utc>>, // Required when scheduled
+    #[serde(default)]
+    pub unpublish_at: Option<DateTime<Utc>>,
     pub cover_image: Option<String>,
     pub attachments: Vec<String>,
 }
 
+impl CreateBlogPostRequest {
+    /// The requested status, if the request names a valid one. Creates
+    /// without one publish immediately; updates keep the stored status.
+    pub fn post_status(&self) -> Option<PostStatus> {
+        self.status.as_deref().and_then(PostStatus::parse)
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct BlogIndexQuery {
+    pub page: Option<u32>, // 1-based
+    pub per_page: Option<u32>,
+    pub tag: Option<String>,    // Matched by slug, so "Sports Day" finds "sports-day"
+    pub author: Option<String>, // Author's user ID
+    pub from: Option<DateTime<Utc>>, // Published at or after
+    pub to: Option<DateTime<Utc>>,   // Published before
+    pub sort: Option<String>,   // "newest" (default), "oldest" or "title"
+    pub status: Option<String>, // Admin listing only
+}
+
+#[derive(Serialize)]
+pub struct BlogIndexResponse {
+    pub posts: Vec<BlogIndexEntry>,
+    pub page: u32,
+    pub per_page: u32,
+    pub total: u64,
+    pub total_pages: u32,
+    pub next: Option<String>, // Link to the next page with the same filters
+    pub prev: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeSlugRequest {
+    pub slug: String,
+}
+
+// A registered tag; posts refer to it by name
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Tag {
+    pub slug: String,
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TagResponse {
+    pub slug: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub post_count: u64, // Live public posts, or all posts in admin listings
+}
+
+// Tag landing page: the tag and one page of its posts
+#[derive(Serialize)]
+pub struct TagPageResponse {
+    pub tag: TagResponse,
+    #[serde(flatten)]
+    pub page: BlogIndexResponse,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateTagRequest {
+    pub name: String, // Renaming changes the slug too
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MergeTagRequest {
+    pub into: String, // Slug of the tag that absorbs this one
+}
+
+// A saved version of a blog post; `payload` is the KV record as JSON
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PostRevision {
+    pub id: String,
+    pub post_id: String,
+    pub revision: i64,
+    pub slug: String,
+    pub author_id: String,
+    pub created_at: DateTime<Utc>,
+    pub restored_from: Option<i64>,
+    pub payload: String,
+}
+
+#[derive(Serialize)]
+pub struct PostRevisionResponse {
+    pub revision: i64,
+    pub slug: String,
+    pub author_id: String,
+    pub created_at: DateTime<Utc>,
+    pub restored_from: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post: Option<serde_json::Value>, // Only when a single revision is requested
+}
+
+impl PostRevision {
+    pub fn to_response(&self, with_post: bool) -> serde_json::Result<PostRevisionResponse> {
+        let post = if with_post {
+            Some(serde_json::from_str(&self.payload)?)
+        } else {
+            None
+        };
+        Ok(PostRevisionResponse {
+            revision: self.revision,
+            slug: self.slug.clone(),
+            author_id: self.author_id.clone(),
+            created_at: self.created_at,
+            restored_from: self.restored_from,
+            post,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RevisionDiffQuery {
+    pub from: i64,
+    pub to: i64,
+}
+
+/// A post field that differs between two revisions
+#[derive(Serialize, Debug)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: serde_json::Value,
+    pub after: serde_json::Value,
+}
+
+#[derive(Serialize)]
+pub struct RevisionDiffResponse {
+    pub from: i64,
+    pub to: i64,
+    pub changes: Vec<FieldChange>,
+}
+
 #[derive(Serialize)]
 pub struct BlogPostResponse {
     pub id: String,
@@ -291,10 +1094,26 @@ pub struct BlogPostResponse {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Serialize, Deserialize)]
+pub const ACCESS_TOKEN_TYPE: &str = "access";
+
+fn default_token_type() -> String {
+    ACCESS_TOKEN_TYPE.to_string()
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Claims {
     pub sub: String, // User ID
     pub exp: usize,  // Expiration timestamp
     pub jti: String, // JWT ID for revocation support
     pub iat: usize,  // Issued at timestamp
+    #[serde(default = "default_token_type")] // Tokens issued before `typ` existed are access tokens
+    pub typ: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<ActorClaim>, // Set on impersonation tokens
+}
+
+/// RFC 8693 actor claim: the admin acting on behalf of the token's `sub`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ActorClaim {
+    pub sub: String,
 }
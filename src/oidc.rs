@@ -0,0 +1,254 @@
+use crate::config::AppConfig;
+use crate::error::{AppError, AppResult};
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, Instant};
+
+/// Name of the built-in provider configured by the `google_*` settings
+pub const GOOGLE_PROVIDER: &str = "google";
+
+/// Issuers Google uses in its ID tokens
+const GOOGLE_ISSUERS: &[&str] = &["https://accounts.google.com", "accounts.google.com"];
+
+/// How long fetched discovery documents and signing keys are trusted before
+/// refetching (providers rotate keys regularly)
+const METADATA_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Discovery documents keyed by issuer and JWKS keyed by URI. `AuthService`
+/// is built per request, so the caches live at module level.
+static DISCOVERY_CACHE: LazyLock<RwLock<HashMap<String, (ProviderMetadata, Instant)>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+static JWKS_CACHE: LazyLock<RwLock<HashMap<String, (JwkSet, Instant)>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+fn cached_metadata(issuer: &str) -> Option<ProviderMetadata> {
+    let cache = DISCOVERY_CACHE.read().unwrap_or_else(|e| e.into_inner());
+    let (metadata, fetched_at) = cache.get(issuer)?;
+    if fetched_at.elapsed() > METADATA_TTL {
+        return None;
+    }
+    Some(metadata.clone())
+}
+
+fn cached_jwk(jwks_uri: &str, kid: &str) -> Option<Jwk> {
+    let cache = JWKS_CACHE.read().unwrap_or_else(|e| e.into_inner());
+    let (jwks, fetched_at) = cache.get(jwks_uri)?;
+    if fetched_at.elapsed() > METADATA_TTL {
+        return None;
+    }
+    jwks.find(kid).cloned()
+}
+
+/// The parts of an OpenID Provider's discovery document the login flow uses
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    pub userinfo_endpoint: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum MetadataSource {
+    Static(ProviderMetadata), // Endpoints set in config
+    Discovery(String),        // Issuer whose discovery document lists them
+}
+
+/// A configured OpenID Connect provider
+#[derive(Debug, Clone)]
+pub struct OidcProvider {
+    pub name: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+    pub scopes: String,
+    pub trust_email: bool, // Emails count as verified without email_verified
+    issuers: Vec<String>,  // Accepted `iss` values
+    source: MetadataSource,
+}
+
+impl OidcProvider {
+    /// Endpoints of the provider, from config or its (cached) discovery document
+    pub async fn metadata(&self) -> AppResult<ProviderMetadata> {
+        let issuer = match &self.source {
+            MetadataSource::Static(metadata) => return Ok(metadata.clone()),
+            MetadataSource::Discovery(issuer) => issuer,
+        };
+
+        if let Some(metadata) = cached_metadata(issuer) {
+            return Ok(metadata);
+        }
+
+        let url = format!("{}/.well-known/openid-configuration", issuer.trim_end_matches('/'));
+        let response = reqwest::Client::new().get(&url).send().await?;
+        if !response.status().is_success() {
+            return Err(AppError::Internal(format!(
+                "Failed to fetch {} discovery document: {}",
+                self.name,
+                response.status()
+            )));
+        }
+
+        let metadata: ProviderMetadata = response.json().await?;
+        // Guards against a document served for a different issuer
+        if metadata.issuer != *issuer {
+            return Err(AppError::Internal(format!(
+                "{} discovery document is for issuer {}",
+                self.name, metadata.issuer
+            )));
+        }
+
+        DISCOVERY_CACHE
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(issuer.clone(), (metadata.clone(), Instant::now()));
+
+        Ok(metadata)
+    }
+
+    pub fn issuers(&self) -> Vec<&str> {
+        self.issuers.iter().map(String::as_str).collect()
+    }
+
+    /// Look up a signing key by `kid`, refreshing the cached JWKS when it is
+    /// stale or doesn't contain the key
+    pub async fn signing_key(&self, jwks_uri: &str, kid: &str) -> AppResult<Jwk> {
+        if let Some(jwk) = cached_jwk(jwks_uri, kid) {
+            return Ok(jwk);
+        }
+
+        let response = reqwest::Client::new().get(jwks_uri).send().await?;
+        if !response.status().is_success() {
+            return Err(AppError::Internal(format!(
+                "Failed to fetch {} JWKS: {}",
+                self.name,
+                response.status()
+            )));
+        }
+
+        let jwks: JwkSet = response.json().await?;
+        let jwk = jwks.find(kid).cloned();
+
+        JWKS_CACHE
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(jwks_uri.to_string(), (jwks, Instant::now()));
+
+        jwk.ok_or_else(|| AppError::Auth("Unknown ID token signing key".to_string()))
+    }
+}
+
+/// The login providers available from config: Google when `google_client_id`
+/// is set, plus every entry of `oidc_providers`
+#[derive(Debug, Clone)]
+pub struct OidcRegistry {
+    providers: Vec<OidcProvider>,
+}
+
+impl OidcRegistry {
+    pub fn from_config(config: &AppConfig) -> AppResult<Self> {
+        let mut providers = Vec::new();
+
+        if let Some(client_id) = &config.google_client_id {
+            providers.push(OidcProvider {
+                name: GOOGLE_PROVIDER.to_string(),
+                client_id: client_id.clone(),
+                client_secret: config.google_client_secret.clone(),
+                redirect_uri: config.google_redirect_uri.clone(),
+                scopes: "openid email profile".to_string(),
+                trust_email: true, // Only an explicit email_verified=false is rejected
+                issuers: GOOGLE_ISSUERS.iter().map(|issuer| issuer.to_string()).collect(),
+                source: MetadataSource::Static(ProviderMetadata {
+                    issuer: GOOGLE_ISSUERS[0].to_string(),
+                    authorization_endpoint: config.google_authorize_endpoint.clone(),
+                    token_endpoint: config.google_token_endpoint.clone(),
+                    jwks_uri: config.google_jwks_uri.clone(),
+                    userinfo_endpoint: Some(config.google_userinfo_endpoint.clone()),
+                }),
+            });
+        }
+
+        for provider in &config.oidc_providers {
+            let name = provider.name.as_str();
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            {
+                return Err(config_error(format!("Invalid OIDC provider name: {:?}", name)));
+            }
+            if providers.iter().any(|p: &OidcProvider| p.name == name) {
+                return Err(config_error(format!("Duplicate OIDC provider: {}", name)));
+            }
+            let issuer = url::Url::parse(&provider.issuer).map_err(|e| {
+                config_error(format!("Invalid issuer for OIDC provider {}: {}", name, e))
+            })?;
+            let local = matches!(issuer.host_str(), Some("localhost" | "127.0.0.1"));
+            if issuer.scheme() != "https" && !local {
+                return Err(config_error(format!(
+                    "Issuer for OIDC provider {} must use https",
+                    name
+                )));
+            }
+            // Multi-tenant endpoints issue tokens under each tenant's own
+            // issuer, which the exact issuer check would reject; register
+            // one provider per tenant instead
+            let multi_tenant = provider.issuer.contains("{tenantid}")
+                || issuer.path_segments().is_some_and(|mut segments| {
+                    segments.any(|segment| {
+                        matches!(segment, "common" | "organizations" | "consumers")
+                    })
+                });
+            if multi_tenant {
+                return Err(config_error(format!(
+                    "Issuer for OIDC provider {} must be a single tenant",
+                    name
+                )));
+            }
+            if provider.client_id.is_empty() || provider.redirect_uri.is_empty() {
+                return Err(config_error(format!(
+                    "OIDC provider {} needs a client ID and redirect URI",
+                    name
+                )));
+            }
+            if !provider.scopes.split_whitespace().any(|scope| scope == "openid") {
+                return Err(config_error(format!(
+                    "Scopes for OIDC provider {} must include openid",
+                    name
+                )));
+            }
+
+            providers.push(OidcProvider {
+                name: name.to_string(),
+                client_id: provider.client_id.clone(),
+                client_secret: provider.client_secret.clone(),
+                redirect_uri: provider.redirect_uri.clone(),
+                scopes: provider.scopes.clone(),
+                trust_email: provider.trust_email,
+                issuers: vec![provider.issuer.clone()],
+                source: MetadataSource::Discovery(provider.issuer.clone()),
+            });
+        }
+
+        Ok(Self { providers })
+    }
+
+    pub fn get(&self, name: &str) -> AppResult<&OidcProvider> {
+        self.providers
+            .iter()
+            .find(|provider| provider.name == name)
+            .ok_or_else(|| AppError::NotFound(format!("Unknown login provider: {}", name)))
+    }
+
+    /// Provider names, for rendering login buttons
+    pub fn names(&self) -> Vec<String> {
+        self.providers.iter().map(|provider| provider.name.clone()).collect()
+    }
+}
+
+fn config_error(message: String) -> AppError {
+    AppError::Config(config::ConfigError::Message(message))
+}
@@ -0,0 +1,203 @@
+use crate::audit::{AuditService, SYSTEM_USER_ID};
+use crate::config::AppConfig;
+use crate::error::{AppError, AppResult};
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+/// Upper bound on in-memory buckets before stale ones are swept
+const MAX_MEMORY_BUCKETS: usize = 100_000;
+
+/// Counters for the in-memory backend. `AuthRateLimiter` is built per
+/// request, so like the revocation cache they live at module level.
+static MEMORY_BUCKETS: LazyLock<Mutex<HashMap<String, Bucket>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Attempts seen for one key in its current fixed window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bucket {
+    pub window_start: i64,
+    pub hits: i64,
+    pub locked_until: i64, // 0 when not locked out
+}
+
+/// Pluggable storage for rate-limit counters
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Count one attempt against `key`, starting a new window if the current
+    /// one is older than `window_seconds`, and return the updated bucket
+    async fn hit(&self, key: &str, now: i64, window_seconds: i64) -> AppResult<Bucket>;
+
+    /// Reject attempts for `key` until `locked_until`
+    async fn lock(&self, key: &str, locked_until: i64) -> AppResult<()>;
+}
+
+/// Build the store selected by `rate_limit_backend` ("memory" or "sqlite")
+pub fn rate_limit_store_from_config(
+    config: &AppConfig,
+    db: &SqlitePool,
+) -> AppResult<Box<dyn RateLimitStore>> {
+    match config.rate_limit_backend.as_str() {
+        "memory" => Ok(Box::new(MemoryRateLimitStore)),
+        "sqlite" => Ok(Box::new(SqliteRateLimitStore::new(db.clone()))),
+        other => Err(AppError::Config(config::ConfigError::Message(format!(
+            "Unknown rate limit backend: {}",
+            other
+        )))),
+    }
+}
+
+/// Per-process counters; enough for a single instance
+pub struct MemoryRateLimitStore;
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn hit(&self, key: &str, now: i64, window_seconds: i64) -> AppResult<Bucket> {
+        let mut buckets = MEMORY_BUCKETS.lock().unwrap_or_else(|e| e.into_inner());
+        if buckets.len() >= MAX_MEMORY_BUCKETS {
+            buckets.retain(|_, bucket| {
+                bucket.window_start > now - window_seconds || bucket.locked_until > now
+            });
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            window_start: now,
+            hits: 0,
+            locked_until: 0,
+        });
+        if bucket.window_start <= now - window_seconds {
+            bucket.window_start = now;
+            bucket.hits = 0;
+        }
+        bucket.hits += 1;
+        Ok(*bucket)
+    }
+
+    async fn lock(&self, key: &str, locked_until: i64) -> AppResult<()> {
+        let mut buckets = MEMORY_BUCKETS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(bucket) = buckets.get_mut(key) {
+            bucket.locked_until = locked_until;
+        }
+        Ok(())
+    }
+}
+
+/// Counters in the `rate_limits` table, shared by every instance using the
+/// same database
+pub struct SqliteRateLimitStore {
+    db: SqlitePool,
+}
+
+impl SqliteRateLimitStore {
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for SqliteRateLimitStore {
+    async fn hit(&self, key: &str, now: i64, window_seconds: i64) -> AppResult<Bucket> {
+        // Single statement so concurrent instances cannot lose increments
+        let (window_start, hits, locked_until): (i64, i64, i64) = sqlx::query_as(
+            "INSERT INTO rate_limits (key, window_start, hits, locked_until) VALUES ($1, $2, 1, 0)
+             ON CONFLICT(key) DO UPDATE SET
+                 hits = CASE WHEN window_start <= $2 - $3 THEN 1 ELSE hits + 1 END,
+                 window_start = CASE WHEN window_start <= $2 - $3 THEN $2 ELSE window_start END
+             RETURNING window_start, hits, locked_until",
+        )
+        .bind(key)
+        .bind(now)
+        .bind(window_seconds)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(Bucket {
+            window_start,
+            hits,
+            locked_until,
+        })
+    }
+
+    async fn lock(&self, key: &str, locked_until: i64) -> AppResult<()> {
+        sqlx::query("UPDATE rate_limits SET locked_until = $1 WHERE key = $2")
+            .bind(locked_until)
+            .bind(key)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Throttles the authentication endpoints per client IP and per identity
+/// (the email a login is attempted for). A key that goes over its burst is
+/// locked out for `auth_lockout_seconds` and the lockout is audited.
+pub struct AuthRateLimiter {
+    db: SqlitePool,
+    config: AppConfig,
+    store: Box<dyn RateLimitStore>,
+}
+
+impl AuthRateLimiter {
+    pub fn new(db: SqlitePool, config: AppConfig) -> AppResult<Self> {
+        let store = rate_limit_store_from_config(&config, &db)?;
+        Ok(Self { db, config, store })
+    }
+
+    /// Count an attempt from this client IP
+    pub async fn check_ip(&self, ip_address: &str) -> AppResult<()> {
+        self.check(
+            &format!("ip:{}", ip_address),
+            self.config.auth_rate_limit_ip_window_seconds,
+            self.config.auth_rate_limit_ip_burst,
+        )
+        .await
+    }
+
+    /// Count an attempt for this email or other account identifier
+    pub async fn check_identity(&self, identity: &str) -> AppResult<()> {
+        self.check(
+            &format!("identity:{}", identity.trim().to_lowercase()),
+            self.config.auth_rate_limit_identity_window_seconds,
+            self.config.auth_rate_limit_identity_burst,
+        )
+        .await
+    }
+
+    async fn check(&self, key: &str, window_seconds: u32, burst: u32) -> AppResult<()> {
+        let now = Utc::now().timestamp();
+        let window_seconds = window_seconds.max(1) as i64;
+        let bucket = self.store.hit(key, now, window_seconds).await?;
+
+        if bucket.locked_until > now {
+            return Err(AppError::RateLimited((bucket.locked_until - now) as u64));
+        }
+        if bucket.hits <= burst as i64 {
+            return Ok(());
+        }
+
+        // Over the burst: lock out at least until the window ends
+        let locked_until =
+            (now + self.config.auth_lockout_seconds as i64).max(bucket.window_start + window_seconds);
+        self.store.lock(key, locked_until).await?;
+
+        tracing::warn!("Rate limit exceeded for {}; locked out until {}", key, locked_until);
+        AuditService::new(self.db.clone())
+            .log_action(
+                SYSTEM_USER_ID,
+                "auth_lockout".to_string(),
+                Some(key.to_string()),
+                Some(serde_json::json!({
+                    "key": key,
+                    "hits": bucket.hits,
+                    "window_seconds": window_seconds,
+                    "burst": burst,
+                    "locked_until": locked_until,
+                })),
+            )
+            .await?;
+
+        Err(AppError::RateLimited((locked_until - now) as u64))
+    }
+}
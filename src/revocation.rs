@@ -0,0 +1,209 @@
+use crate::error::AppResult;
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, RwLock};
+
+/// Upper bound on cached jtis before expired entries are swept
+const MAX_CACHE_ENTRIES: usize = 100_000;
+
+/// How long past `exp` a revocation is kept; covers the JWT validation leeway
+const EXPIRY_GRACE_SECONDS: i64 = 5 * 60;
+
+/// How long a "not revoked" answer is trusted before the table is asked again
+pub const ACTIVE_TTL_SECONDS: i64 = 5;
+
+/// Process-wide revocation cache. `AuthService` is built per request, so like
+/// the Google JWKS cache it lives at module level.
+pub static REVOCATION_CACHE: LazyLock<RevocationCache> = LazyLock::new(RevocationCache::new);
+
+struct CacheEntry {
+    revoked: bool,
+    expires_at: i64, // Unix timestamp after which the token is rejected anyway
+    recheck_at: i64, // Unix timestamp after which a "not revoked" answer is stale
+}
+
+/// In-memory mirror of lookups against the `revocations` table.
+///
+/// Every revocation written by this process is also recorded here as revoked,
+/// and a "not revoked" answer never overwrites a revoked entry, so a lookup
+/// racing a revocation cannot cache a stale result. Revocations written by
+/// other instances only reach the table, so "not revoked" answers are kept
+/// for `active_ttl` seconds: a token revoked elsewhere is still accepted here
+/// for at most that long.
+pub struct RevocationCache {
+    entries: RwLock<HashMap<String, CacheEntry>>,
+    active_ttl: i64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    rows_pruned: AtomicU64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RevocationCacheMetrics {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64, // hits / lookups, 0.0 before the first lookup
+    pub rows_pruned: u64, // Total revocation rows deleted by pruning
+}
+
+impl RevocationCache {
+    pub fn new() -> Self {
+        Self::with_active_ttl(ACTIVE_TTL_SECONDS)
+    }
+
+    /// A cache that trusts "not revoked" answers for `seconds`; 0 never
+    /// serves them from the cache
+    pub fn with_active_ttl(seconds: i64) -> Self {
+        Self {
+            entries: RwLock::new(HashMap::new()),
+            active_ttl: seconds,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            rows_pruned: AtomicU64::new(0),
+        }
+    }
+
+    /// Cached revocation status of a jti, or `None` if the table must be asked
+    pub fn get(&self, jti: &str) -> Option<bool> {
+        let entries = self.entries.read().unwrap();
+        let now = Utc::now().timestamp();
+        match entries.get(jti).filter(|entry| entry.revoked || now < entry.recheck_at) {
+            Some(entry) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(entry.revoked)
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Record a revocation; always wins over a cached "not revoked"
+    pub fn mark_revoked(&self, jti: &str, expires_at: i64) {
+        let mut entries = self.entries.write().unwrap();
+        Self::make_room(&mut entries);
+        entries.insert(
+            jti.to_string(),
+            CacheEntry {
+                revoked: true,
+                expires_at,
+                recheck_at: expires_at,
+            },
+        );
+    }
+
+    /// Record a "not revoked" lookup result unless the jti is already cached
+    /// as revoked; it is trusted for `active_ttl` seconds
+    pub fn mark_active(&self, jti: &str, expires_at: i64) {
+        let mut entries = self.entries.write().unwrap();
+        Self::make_room(&mut entries);
+        if entries.get(jti).is_some_and(|entry| entry.revoked) {
+            return;
+        }
+        entries.insert(
+            jti.to_string(),
+            CacheEntry {
+                revoked: false,
+                expires_at,
+                recheck_at: Utc::now().timestamp() + self.active_ttl,
+            },
+        );
+    }
+
+    /// Drop entries for tokens that have expired (past the grace period)
+    pub fn evict_expired(&self) {
+        let cutoff = Utc::now().timestamp() - EXPIRY_GRACE_SECONDS;
+        self.entries
+            .write()
+            .unwrap()
+            .retain(|_, entry| entry.expires_at > cutoff);
+    }
+
+    pub fn record_pruned(&self, rows: u64) {
+        self.rows_pruned.fetch_add(rows, Ordering::Relaxed);
+    }
+
+    pub fn metrics(&self) -> RevocationCacheMetrics {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let lookups = hits + misses;
+
+        RevocationCacheMetrics {
+            entries: self.entries.read().unwrap().len(),
+            hits,
+            misses,
+            hit_rate: if lookups == 0 {
+                0.0
+            } else {
+                hits as f64 / lookups as f64
+            },
+            rows_pruned: self.rows_pruned.load(Ordering::Relaxed),
+        }
+    }
+
+    fn make_room(entries: &mut HashMap<String, CacheEntry>) {
+        if entries.len() < MAX_CACHE_ENTRIES {
+            return;
+        }
+
+        let cutoff = Utc::now().timestamp() - EXPIRY_GRACE_SECONDS;
+        entries.retain(|_, entry| entry.expires_at > cutoff);
+
+        // Still full of live tokens: drop "not revoked" answers first, they
+        // are the cheap ones to re-learn from the table
+        if entries.len() >= MAX_CACHE_ENTRIES {
+            entries.retain(|_, entry| entry.revoked);
+        }
+        if entries.len() >= MAX_CACHE_ENTRIES {
+            entries.clear();
+        }
+    }
+}
+
+impl Default for RevocationCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Service for maintaining the `revocations` table
+pub struct RevocationService {
+    pub db: SqlitePool,
+}
+
+impl RevocationService {
+    pub fn new(db: SqlitePool) -> Self {
+        Self { db }
+    }
+
+    /// Delete revocations whose token has expired; returns the number of rows removed.
+    /// An expired token fails signature validation before the list is consulted,
+    /// so these rows can never matter again.
+    pub async fn prune_expired(&self) -> AppResult<u64> {
+        let result =
+            sqlx::query("DELETE FROM revocations WHERE expires_at IS NOT NULL AND expires_at < $1")
+                .bind(Utc::now() - chrono::Duration::seconds(EXPIRY_GRACE_SECONDS))
+                .execute(&self.db)
+                .await?;
+
+        let rows = result.rows_affected();
+        REVOCATION_CACHE.record_pruned(rows);
+        REVOCATION_CACHE.evict_expired();
+
+        let metrics = REVOCATION_CACHE.metrics();
+        tracing::info!(
+            "Pruned {} expired revocations ({} total); cache has {} entries, hit rate {:.1}%",
+            rows,
+            metrics.rows_pruned,
+            metrics.entries,
+            metrics.hit_rate * 100.0
+        );
+
+        Ok(rows)
+    }
+}
@@ -0,0 +1,123 @@
+use crate::config::AppConfig;
+use crate::error::{AppError, AppResult};
+use crate::models::UserRole;
+
+/// Who may create an account on first Google login, and with which role
+#[derive(Debug, Clone)]
+pub struct SignupPolicy {
+    closed: bool,
+    allowed_domains: Vec<String>,
+    rules: Vec<RoleRule>,
+    default_role: UserRole,
+}
+
+#[derive(Debug, Clone)]
+struct RoleRule {
+    pattern: RulePattern,
+    role: UserRole,
+}
+
+#[derive(Debug, Clone)]
+enum RulePattern {
+    Domain(String),
+    Email(String),
+}
+
+impl SignupPolicy {
+    /// Build the policy from `signup_*` settings, rejecting malformed values
+    pub fn from_config(config: &AppConfig) -> AppResult<Self> {
+        let closed = match config.signup_mode.as_str() {
+            "open" => false,
+            "closed" => true,
+            other => return Err(config_error(format!("Invalid signup_mode: {}", other))),
+        };
+
+        let allowed_domains = config
+            .signup_allowed_domains
+            .iter()
+            .map(|domain| domain.trim().trim_start_matches('@').to_lowercase())
+            .collect();
+
+        let rules = config
+            .signup_role_rules
+            .iter()
+            .map(|rule| RoleRule::parse(rule))
+            .collect::<AppResult<Vec<_>>>()?;
+
+        let default_role = UserRole::from_str(&config.signup_default_role).ok_or_else(|| {
+            config_error(format!(
+                "Invalid signup_default_role: {}",
+                config.signup_default_role
+            ))
+        })?;
+
+        Ok(Self {
+            closed,
+            allowed_domains,
+            rules,
+            default_role,
+        })
+    }
+
+    /// Role for a new account with this email, or `SignupRejected` with a
+    /// message that can be shown to the user.
+    ///
+    /// Explicit email rules win over domain rules. A matching rule also admits
+    /// the email when its domain is not on the allow-list.
+    pub fn role_for(&self, email: &str) -> AppResult<UserRole> {
+        if self.closed {
+            return Err(AppError::SignupRejected(
+                "Sign-up is closed. Ask an administrator to create your account.".to_string(),
+            ));
+        }
+
+        let email = email.trim().to_lowercase();
+        let domain = email.rsplit_once('@').map(|(_, domain)| domain).unwrap_or("");
+
+        let email_rule = self
+            .rules
+            .iter()
+            .find(|rule| matches!(&rule.pattern, RulePattern::Email(e) if *e == email));
+        let domain_rule = self
+            .rules
+            .iter()
+            .find(|rule| matches!(&rule.pattern, RulePattern::Domain(d) if d == domain));
+        if let Some(rule) = email_rule.or(domain_rule) {
+            return Ok(rule.role.clone());
+        }
+
+        if !self.allowed_domains.is_empty() && !self.allowed_domains.iter().any(|d| d == domain) {
+            return Err(AppError::SignupRejected(format!(
+                "Sign-up is not open to {} addresses. Use your school account.",
+                if domain.is_empty() { "these" } else { domain }
+            )));
+        }
+
+        Ok(self.default_role.clone())
+    }
+}
+
+impl RoleRule {
+    /// Parse `@domain=role`, `domain=role` or `email=role`
+    fn parse(rule: &str) -> AppResult<Self> {
+        let (pattern, role) = rule
+            .split_once('=')
+            .ok_or_else(|| config_error(format!("Invalid signup role rule: {}", rule)))?;
+
+        let role = UserRole::from_str(role.trim())
+            .ok_or_else(|| config_error(format!("Invalid role in signup rule: {}", rule)))?;
+
+        let pattern = pattern.trim().to_lowercase();
+        let pattern = match pattern.strip_prefix('@') {
+            Some(domain) => RulePattern::Domain(domain.to_string()),
+            None if pattern.contains('@') => RulePattern::Email(pattern),
+            None => RulePattern::Domain(pattern),
+        };
+
+        Ok(Self { pattern, role })
+    }
+}
+
+fn config_error(message: String) -> AppError {
+    AppError::Config(config::ConfigError::Message(message))
+}
@@ -0,0 +1,68 @@
+use deunicode::deunicode;
+
+/// Longest slug generated from a title
+pub const MAX_SLUG_LENGTH: usize = 80;
+
+/// Longest slug an editor may set by hand
+pub const MAX_CUSTOM_SLUG_LENGTH: usize = 200;
+
+/// Slug used when a title has no letters or digits at all
+const FALLBACK_SLUG: &str = "post";
+
+/// Turn a title into a URL slug: transliterated to ASCII, lowercase, words
+/// joined by single dashes and cut at a word boundary to fit
+/// `MAX_SLUG_LENGTH`. "Prize-Giving Day — 2024!" becomes
+/// "prize-giving-day-2024".
+pub fn slugify(title: &str) -> String {
+    try_slugify(title).unwrap_or_else(|| FALLBACK_SLUG.to_string())
+}
+
+/// Like `slugify`, but `None` when the text has no letters or digits to
+/// build a slug from rather than falling back to "post"
+pub fn try_slugify(title: &str) -> Option<String> {
+    let ascii = deunicode(title).to_lowercase();
+    let words = ascii
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty());
+
+    let mut slug = String::new();
+    for word in words {
+        let separator = usize::from(!slug.is_empty());
+        if slug.len() + separator + word.len() > MAX_SLUG_LENGTH {
+            // A single overlong word is cut rather than dropped
+            if slug.is_empty() {
+                slug.push_str(&word[..MAX_SLUG_LENGTH]);
+            }
+            break;
+        }
+        if separator == 1 {
+            slug.push('-');
+        }
+        slug.push_str(word);
+    }
+
+    (!slug.is_empty()).then_some(slug)
+}
+
+/// The `n`th candidate for a slug: `base` itself, then `base-2`, `base-3`...
+/// with `base` shortened so the suffix still fits.
+pub fn numbered(base: &str, n: u32) -> String {
+    if n <= 1 {
+        return base.to_string();
+    }
+    let suffix = format!("-{}", n);
+    let keep = base.len().min(MAX_SLUG_LENGTH - suffix.len());
+    format!("{}{}", base[..keep].trim_end_matches('-'), suffix)
+}
+
+/// Lowercase ASCII letters and digits separated by single dashes
+pub fn is_valid(slug: &str) -> bool {
+    !slug.is_empty()
+        && slug.len() <= MAX_CUSTOM_SLUG_LENGTH
+        && slug.split('-').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        })
+}
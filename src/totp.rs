@@ -0,0 +1,395 @@
+use crate::audit::AuditService;
+use crate::auth::hash_secret_token;
+use crate::config::AppConfig;
+use crate::error::{AppError, AppResult};
+use crate::models::{TotpCredential, TotpEnrollmentResponse, UserRole};
+use chrono::{DateTime, Utc};
+use rand::{Rng, RngCore};
+use ring::hmac;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// RFC 6238 defaults, which is what authenticator apps assume
+const TOTP_PERIOD_SECONDS: i64 = 30;
+const TOTP_DIGITS: u32 = 6;
+
+/// Steps either side of the current one still accepted, for clock drift
+const TOTP_SKEW_STEPS: i64 = 1;
+
+const SECRET_BYTES: usize = 20;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Unambiguous lowercase characters for recovery codes (no 0/o, 1/l/i)
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Source of the current time, so code verification can be tested
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Always reports the same instant
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// TOTP code (HMAC-SHA1, 6 digits, 30 s steps) for `secret` at `time`
+pub fn totp_code(secret: &[u8], time: DateTime<Utc>) -> String {
+    hotp(secret, time.timestamp().div_euclid(TOTP_PERIOD_SECONDS) as u64)
+}
+
+fn hotp(secret: &[u8], counter: u64) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let digest = hmac::sign(&key, &counter.to_be_bytes());
+    let digest = digest.as_ref();
+
+    // Dynamic truncation (RFC 4226 section 5.3)
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(TOTP_DIGITS),
+        width = TOTP_DIGITS as usize
+    )
+}
+
+/// RFC 4648 base32 without padding
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    encoded
+}
+
+/// Decode base32, ignoring case, spaces and padding
+pub fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in encoded.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a as char == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+        }
+    }
+    Some(decoded)
+}
+
+/// `otpauth://` URI authenticator apps import from a QR code
+pub fn provisioning_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(account),
+        secret,
+        percent_encode(issuer),
+        TOTP_DIGITS,
+        TOTP_PERIOD_SECONDS
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'@' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// Recovery codes are compared without dashes, spaces or case
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn generate_recovery_code() -> String {
+    let mut rng = rand::thread_rng();
+    let chars: String = (0..10)
+        .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+        .collect();
+    format!("{}-{}", &chars[..5], &chars[5..])
+}
+
+/// TOTP enrollment and verification. Codes are checked against `clock`,
+/// which is the system clock unless replaced with `with_clock`.
+pub struct TotpService {
+    pub db: SqlitePool,
+    pub config: AppConfig,
+    pub audit: AuditService,
+    clock: Arc<dyn Clock>,
+}
+
+impl TotpService {
+    pub fn new(db: SqlitePool, config: AppConfig) -> Self {
+        let audit = AuditService::new(db.clone());
+        Self {
+            db,
+            config,
+            audit,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// The user's TOTP credential, enabled or still pending
+    pub async fn credential(&self, user_id: &str) -> AppResult<Option<TotpCredential>> {
+        let credential = sqlx::query_as(
+            "SELECT user_id, secret, created_at, enabled_at, last_used_step FROM totp_credentials WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(credential)
+    }
+
+    pub async fn is_enabled(&self, user_id: &str) -> AppResult<bool> {
+        Ok(self
+            .credential(user_id)
+            .await?
+            .is_some_and(|credential| credential.enabled_at.is_some()))
+    }
+
+    /// Whether a login for this role must pass a second factor even without
+    /// an enrolled credential
+    pub fn is_required_for(&self, role: Option<UserRole>) -> bool {
+        self.config.admin_totp_required && role == Some(UserRole::Admin)
+    }
+
+    /// Generate a new pending secret, replacing any earlier pending one
+    pub async fn start_enrollment(&self, user_id: &str, email: &str) -> AppResult<TotpEnrollmentResponse> {
+        if self.is_enabled(user_id).await? {
+            return Err(AppError::Conflict(
+                "Two-factor authentication is already enabled".to_string(),
+            ));
+        }
+
+        let mut bytes = [0u8; SECRET_BYTES];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let secret = base32_encode(&bytes);
+
+        sqlx::query(
+            "INSERT INTO totp_credentials (user_id, secret, created_at, enabled_at, last_used_step)
+             VALUES ($1, $2, $3, NULL, NULL)
+             ON CONFLICT(user_id) DO UPDATE SET secret = excluded.secret, created_at = excluded.created_at",
+        )
+        .bind(user_id)
+        .bind(&secret)
+        .bind(Utc::now())
+        .execute(&self.db)
+        .await?;
+
+        Ok(TotpEnrollmentResponse {
+            provisioning_uri: provisioning_uri(&self.config.totp_issuer, email, &secret),
+            secret,
+        })
+    }
+
+    /// Enable the pending secret once the app produces a valid code, and
+    /// return the recovery codes (shown once)
+    pub async fn confirm_enrollment(&self, user_id: &str, code: &str) -> AppResult<Vec<String>> {
+        let credential = self
+            .credential(user_id)
+            .await?
+            .filter(|credential| credential.enabled_at.is_none())
+            .ok_or_else(|| {
+                AppError::Validation("No two-factor enrollment is in progress".to_string())
+            })?;
+        self.check_totp(&credential, code).await?;
+
+        sqlx::query("UPDATE totp_credentials SET enabled_at = $1 WHERE user_id = $2")
+            .bind(Utc::now())
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+        let recovery_codes = self.replace_recovery_codes(user_id).await?;
+
+        self.audit
+            .log_action(user_id, "totp_enabled".to_string(), Some(user_id.to_string()), None)
+            .await?;
+
+        Ok(recovery_codes)
+    }
+
+    /// Accept a current TOTP code or an unused recovery code (which is then
+    /// used up)
+    pub async fn verify(&self, user_id: &str, code: &str) -> AppResult<()> {
+        let credential = self
+            .credential(user_id)
+            .await?
+            .filter(|credential| credential.enabled_at.is_some())
+            .ok_or_else(|| {
+                AppError::Auth("Two-factor authentication is not enabled".to_string())
+            })?;
+
+        let trimmed = code.trim();
+        if trimmed.len() == TOTP_DIGITS as usize && trimmed.chars().all(|c| c.is_ascii_digit()) {
+            return self.check_totp(&credential, trimmed).await;
+        }
+
+        let used: Option<String> = sqlx::query_scalar(
+            "UPDATE totp_recovery_codes SET used_at = $1 WHERE user_id = $2 AND code_hash = $3 AND used_at IS NULL RETURNING id",
+        )
+        .bind(Utc::now())
+        .bind(user_id)
+        .bind(hash_secret_token(&normalize_recovery_code(code)))
+        .fetch_optional(&self.db)
+        .await?;
+        if used.is_none() {
+            return Err(AppError::Auth("Invalid recovery code".to_string()));
+        }
+
+        self.audit
+            .log_action(user_id, "totp_recovery_code_used".to_string(), Some(user_id.to_string()), None)
+            .await?;
+        Ok(())
+    }
+
+    /// Replace the recovery codes; needs a current code
+    pub async fn regenerate_recovery_codes(&self, user_id: &str, code: &str) -> AppResult<Vec<String>> {
+        self.verify(user_id, code).await?;
+        let recovery_codes = self.replace_recovery_codes(user_id).await?;
+
+        self.audit
+            .log_action(
+                user_id,
+                "totp_recovery_codes_regenerated".to_string(),
+                Some(user_id.to_string()),
+                None,
+            )
+            .await?;
+
+        Ok(recovery_codes)
+    }
+
+    /// Turn two-factor authentication off; needs a current code. Not allowed
+    /// for roles it is mandatory for.
+    pub async fn disable(&self, user_id: &str, role: Option<UserRole>, code: &str) -> AppResult<()> {
+        if self.is_required_for(role) {
+            return Err(AppError::Validation(
+                "Two-factor authentication is required for administrators".to_string(),
+            ));
+        }
+        self.verify(user_id, code).await?;
+
+        let mut tx = self.db.begin().await?;
+        sqlx::query("DELETE FROM totp_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM totp_credentials WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        self.audit
+            .log_action(user_id, "totp_disabled".to_string(), Some(user_id.to_string()), None)
+            .await?;
+        Ok(())
+    }
+
+    /// Check a TOTP code within the allowed skew and record its step so the
+    /// same code cannot be used twice
+    async fn check_totp(&self, credential: &TotpCredential, code: &str) -> AppResult<()> {
+        let secret = base32_decode(&credential.secret)
+            .ok_or_else(|| AppError::Internal("Stored TOTP secret is not base32".to_string()))?;
+        let code = code.trim();
+        let current_step = self.clock.now().timestamp().div_euclid(TOTP_PERIOD_SECONDS);
+
+        let step = (current_step - TOTP_SKEW_STEPS..=current_step + TOTP_SKEW_STEPS)
+            .find(|&step| constant_time_eq(&hotp(&secret, step as u64), code))
+            .ok_or_else(|| AppError::Auth("Invalid two-factor code".to_string()))?;
+
+        // Conditional update so concurrent requests cannot both use the code
+        let result = sqlx::query(
+            "UPDATE totp_credentials SET last_used_step = $1 WHERE user_id = $2 AND (last_used_step IS NULL OR last_used_step < $1)",
+        )
+        .bind(step)
+        .bind(&credential.user_id)
+        .execute(&self.db)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::Auth("Two-factor code has already been used".to_string()));
+        }
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(&self, user_id: &str) -> AppResult<Vec<String>> {
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| generate_recovery_code())
+            .collect();
+
+        let mut tx = self.db.begin().await?;
+        sqlx::query("DELETE FROM totp_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        for code in &codes {
+            sqlx::query(
+                "INSERT INTO totp_recovery_codes (id, user_id, code_hash, created_at) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(user_id)
+            .bind(hash_secret_token(&normalize_recovery_code(code)))
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(codes)
+    }
+}
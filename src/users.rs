@@ -0,0 +1,346 @@
+use crate::audit::{AuditService, SYSTEM_USER_ID};
+use crate::error::{AppError, AppResult};
+use crate::models::{
+    AccountStatus, CreateUserRequest, UpdateUserRequest, User, UserListQuery, UserListResponse,
+    UserRole,
+};
+use chrono::Utc;
+use serde_json::json;
+use sqlx::SqlitePool;
+
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// Service for administering user accounts. Every change is audited under
+/// the acting admin with before/after values.
+pub struct UserService {
+    pub db: SqlitePool,
+    pub audit: AuditService,
+}
+
+impl UserService {
+    pub fn new(db: SqlitePool) -> Self {
+        let audit = AuditService::new(db.clone());
+        Self { db, audit }
+    }
+
+    /// List users newest first, optionally filtered by role and by a search
+    /// term matched against email and full name
+    pub async fn list_users(&self, query: UserListQuery) -> AppResult<UserListResponse> {
+        let page = query.page.unwrap_or(1).max(1);
+        let per_page = query
+            .per_page
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+
+        let role = match query.role.as_deref().filter(|r| !r.is_empty()) {
+            Some(role_str) => Some(parse_role(role_str)?.as_str()),
+            None => None,
+        };
+        let search = query
+            .search
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| format!("%{}%", s.to_lowercase()));
+
+        let filter = format!(
+            "id != '{}' AND ($1 IS NULL OR role = $1) AND ($2 IS NULL OR LOWER(email) LIKE $2 OR LOWER(COALESCE(full_name, '')) LIKE $2)",
+            SYSTEM_USER_ID
+        );
+
+        let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM users WHERE {}", filter))
+            .bind(role)
+            .bind(&search)
+            .fetch_one(&self.db)
+            .await?;
+
+        let users: Vec<User> = sqlx::query_as(&format!(
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE {} ORDER BY created_at DESC LIMIT $3 OFFSET $4",
+            filter
+        ))
+        .bind(role)
+        .bind(&search)
+        .bind(per_page as i64)
+        .bind((page as i64 - 1) * per_page as i64)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(UserListResponse {
+            users,
+            page,
+            per_page,
+            total: total as u64,
+        })
+    }
+
+    pub async fn get_user(&self, user_id: &str) -> AppResult<User> {
+        // The audit system actor is not a manageable account
+        if user_id == SYSTEM_USER_ID {
+            return Err(AppError::NotFound("User not found".to_string()));
+        }
+
+        let user: Option<User> = sqlx::query_as(
+            "SELECT id, email, role, google_id, full_name, created_at, deactivated_at, status, status_reason, status_changed_at FROM users WHERE id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.db)
+        .await?;
+
+        user.ok_or_else(|| AppError::NotFound("User not found".to_string()))
+    }
+
+    /// Create an account for someone who has not signed in yet
+    pub async fn create_user(&self, payload: CreateUserRequest, actor_id: &str) -> AppResult<User> {
+        let email = payload.email.trim().to_lowercase();
+        if email.is_empty() || !email.contains('@') {
+            return Err(AppError::Validation("Invalid email address".to_string()));
+        }
+        let role = parse_role(&payload.role)?;
+
+        let existing: Option<String> = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+            .bind(&email)
+            .fetch_optional(&self.db)
+            .await?;
+        if existing.is_some() {
+            return Err(AppError::Conflict("A user with this email already exists".to_string()));
+        }
+
+        let full_name = payload
+            .full_name
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+        let user = User::new(email, role, full_name);
+
+        sqlx::query("INSERT INTO users (id, email, role, google_id, full_name, created_at) VALUES ($1, $2, $3, $4, $5, $6)")
+            .bind(&user.id)
+            .bind(&user.email)
+            .bind(&user.role)
+            .bind(&user.google_id)
+            .bind(&user.full_name)
+            .bind(user.created_at)
+            .execute(&self.db)
+            .await?;
+
+        self.audit
+            .log_action(
+                actor_id,
+                "create_user".to_string(),
+                Some(user.id.clone()),
+                Some(json!({ "before": null, "after": user })),
+            )
+            .await?;
+
+        Ok(user)
+    }
+
+    /// Change a user's role and/or full name
+    pub async fn update_user(
+        &self,
+        user_id: &str,
+        payload: UpdateUserRequest,
+        actor_id: &str,
+    ) -> AppResult<User> {
+        let before = self.get_user(user_id).await?;
+        let mut after = before.clone();
+
+        if let Some(role_str) = payload.role.as_deref() {
+            let role = parse_role(role_str)?;
+            // Demoting yourself could leave nobody able to undo it
+            if user_id == actor_id && role != UserRole::Admin {
+                return Err(AppError::Validation("You cannot change your own role".to_string()));
+            }
+            after.role = role.as_str().to_string();
+        }
+
+        if let Some(full_name) = payload.full_name {
+            let full_name = full_name.trim().to_string();
+            after.full_name = (!full_name.is_empty()).then_some(full_name);
+        }
+
+        sqlx::query("UPDATE users SET role = $1, full_name = $2 WHERE id = $3")
+            .bind(&after.role)
+            .bind(&after.full_name)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+
+        if before.role != after.role {
+            self.audit
+                .log_action(
+                    actor_id,
+                    "change_user_role".to_string(),
+                    Some(user_id.to_string()),
+                    Some(json!({ "before": before.role, "after": after.role })),
+                )
+                .await?;
+        }
+        if before.full_name != after.full_name {
+            self.audit
+                .log_action(
+                    actor_id,
+                    "rename_user".to_string(),
+                    Some(user_id.to_string()),
+                    Some(json!({ "before": before.full_name, "after": after.full_name })),
+                )
+                .await?;
+        }
+
+        Ok(after)
+    }
+
+    /// Block a user from signing in while keeping their data
+    pub async fn deactivate_user(&self, user_id: &str, actor_id: &str) -> AppResult<User> {
+        if user_id == actor_id {
+            return Err(AppError::Validation("You cannot deactivate your own account".to_string()));
+        }
+        self.set_deactivated_at(user_id, Some(Utc::now()), "deactivate_user", actor_id)
+            .await
+    }
+
+    pub async fn reactivate_user(&self, user_id: &str, actor_id: &str) -> AppResult<User> {
+        self.set_deactivated_at(user_id, None, "reactivate_user", actor_id)
+            .await
+    }
+
+    async fn set_deactivated_at(
+        &self,
+        user_id: &str,
+        deactivated_at: Option<chrono::DateTime<Utc>>,
+        action: &str,
+        actor_id: &str,
+    ) -> AppResult<User> {
+        let before = self.get_user(user_id).await?;
+
+        sqlx::query("UPDATE users SET deactivated_at = $1 WHERE id = $2")
+            .bind(deactivated_at)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+
+        let after = User {
+            deactivated_at,
+            ..before.clone()
+        };
+
+        self.audit
+            .log_action(
+                actor_id,
+                action.to_string(),
+                Some(user_id.to_string()),
+                Some(json!({
+                    "before": { "deactivated_at": before.deactivated_at },
+                    "after": { "deactivated_at": after.deactivated_at }
+                })),
+            )
+            .await?;
+
+        Ok(after)
+    }
+
+    /// Lock a user out until reinstated. Their JWTs stay valid but are
+    /// rejected at session verification, so reinstating restores them.
+    pub async fn suspend_user(&self, user_id: &str, reason: &str, actor_id: &str) -> AppResult<User> {
+        if user_id == actor_id {
+            return Err(AppError::Validation("You cannot suspend your own account".to_string()));
+        }
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return Err(AppError::Validation("A suspension reason is required".to_string()));
+        }
+        self.set_status(
+            user_id,
+            AccountStatus::Suspended,
+            Some(reason.to_string()),
+            "suspend_user",
+            actor_id,
+        )
+        .await
+    }
+
+    /// Make a suspended or pending account active again
+    pub async fn reinstate_user(&self, user_id: &str, actor_id: &str) -> AppResult<User> {
+        self.set_status(user_id, AccountStatus::Active, None, "reinstate_user", actor_id)
+            .await
+    }
+
+    async fn set_status(
+        &self,
+        user_id: &str,
+        status: AccountStatus,
+        reason: Option<String>,
+        action: &str,
+        actor_id: &str,
+    ) -> AppResult<User> {
+        let before = self.get_user(user_id).await?;
+        let changed_at = Utc::now();
+
+        sqlx::query("UPDATE users SET status = $1, status_reason = $2, status_changed_at = $3 WHERE id = $4")
+            .bind(status.as_str())
+            .bind(&reason)
+            .bind(changed_at)
+            .bind(user_id)
+            .execute(&self.db)
+            .await?;
+
+        let after = User {
+            status: status.as_str().to_string(),
+            status_reason: reason,
+            status_changed_at: Some(changed_at),
+            ..before.clone()
+        };
+
+        self.audit
+            .log_action(
+                actor_id,
+                action.to_string(),
+                Some(user_id.to_string()),
+                Some(json!({
+                    "before": { "status": before.status, "status_reason": before.status_reason },
+                    "after": { "status": after.status, "status_reason": after.status_reason }
+                })),
+            )
+            .await?;
+
+        Ok(after)
+    }
+
+    /// Permanently delete a user; their sessions and tokens go with them
+    pub async fn delete_user(&self, user_id: &str, actor_id: &str) -> AppResult<User> {
+        if user_id == actor_id {
+            return Err(AppError::Validation("You cannot delete your own account".to_string()));
+        }
+        let before = self.get_user(user_id).await?;
+
+        let mut tx = self.db.begin().await?;
+
+        // Revocations outlive the user (they reference it without cascading)
+        sqlx::query("UPDATE revocations SET user_id = NULL WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        self.audit
+            .log_action(
+                actor_id,
+                "delete_user".to_string(),
+                Some(user_id.to_string()),
+                Some(json!({ "before": before, "after": null })),
+            )
+            .await?;
+
+        Ok(before)
+    }
+}
+
+fn parse_role(role_str: &str) -> AppResult<UserRole> {
+    UserRole::from_str(role_str)
+        .ok_or_else(|| AppError::Validation(format!("Invalid user role: {}", role_str)))
+}
@@ -2,9 +2,19 @@ use chrono::Datelike;
 use edufy::audit::AuditService;
 use edufy::auth::AuthService;
 use edufy::backup::BackupService;
-use edufy::config::AppConfig;
-use edufy::models::{User, UserRole};
+use edufy::blog::BlogService;
+use edufy::config::{AppConfig, OidcProviderConfig};
+use edufy::error::AppError;
+use edufy::mail::{FileMailSender, MailMessage, MailSender};
+use edufy::oidc::OidcRegistry;
+use edufy::models::{
+    ClientInfo, CreateBlogPostRequest, LoginRequest, OidcAuthRequest, OidcAuthStartResponse, User,
+    UserRole,
+};
+use edufy::revocation::{RevocationCache, RevocationService};
+use edufy::users::UserService;
 use sqlx::SqlitePool;
+use std::sync::{Arc, Mutex};
 use tempfile::tempdir;
 use tokio;
 
@@ -30,6 +40,27 @@ fn test_config() -> AppConfig {
     AppConfig {
         database_url: "sqlite::memory:".to_string(),
         jwt_secret: "test-secret-key".to_string(),
+        jwt_keys: Vec::new(),
+        jwt_active_kid: "default".to_string(),
+        access_token_ttl_minutes: 15,
+        refresh_token_ttl_days: 30,
+        impersonation_ttl_minutes: 30,
+        admin_totp_required: false,
+        totp_issuer: "LLA Portal".to_string(),
+        mfa_challenge_ttl_minutes: 5,
+        session_cookie_name: "session".to_string(),
+        cookie_domain: None,
+        cookie_path: "/".to_string(),
+        cookie_same_site: None,
+        cookie_secure: None,
+        cookie_host_prefix: false,
+        rate_limit_backend: "memory".to_string(),
+        auth_rate_limit_ip_window_seconds: 60,
+        auth_rate_limit_ip_burst: 20,
+        auth_rate_limit_identity_window_seconds: 900,
+        auth_rate_limit_identity_burst: 5,
+        auth_lockout_seconds: 900,
+        trusted_proxies: Vec::new(),
         server_port: 3001,
         upload_dir: "test_uploads".to_string(),
         environment: "test".to_string(),
@@ -41,6 +72,25 @@ fn test_config() -> AppConfig {
         google_client_id: None,
         google_client_secret: None,
         google_redirect_uri: "http://localhost:3001/auth/google/callback".to_string(),
+        google_authorize_endpoint: "https://accounts.google.com/o/oauth2/v2/auth".to_string(),
+        google_token_endpoint: "https://oauth2.googleapis.com/token".to_string(),
+        google_jwks_uri: "https://www.googleapis.com/oauth2/v3/certs".to_string(),
+        google_userinfo_endpoint: "https://openidconnect.googleapis.com/v1/userinfo".to_string(),
+        oauth_state_ttl_seconds: 600,
+        oidc_providers: Vec::new(),
+        signup_mode: "open".to_string(),
+        signup_allowed_domains: Vec::new(),
+        signup_role_rules: Vec::new(),
+        signup_default_role: "student".to_string(),
+        magic_link_url: "http://localhost:5173/login/verify".to_string(),
+        magic_link_ttl_minutes: 15,
+        mail_transport: "file".to_string(),
+        mail_from: "LLA Portal <no-reply@test.com>".to_string(),
+        mail_outbox_dir: "test_mail_outbox".to_string(),
+        smtp_host: None,
+        smtp_port: 587,
+        smtp_username: None,
+        smtp_password: None,
         sharepoint_tenant_id: None,
         sharepoint_client_id: None,
         sharepoint_client_secret: None,
@@ -49,6 +99,8 @@ fn test_config() -> AppConfig {
         backup_enabled: false,
         backup_schedule: "0 0 2 * * *".to_string(),
         backup_retention_days: 30,
+        revocation_prune_schedule: "0 15 * * * *".to_string(),
+        publish_schedule: "0 * * * * *".to_string(),
     }
 }
 
@@ -373,3 +425,4572 @@ async fn test_backup_service_with_temp_directory() {
     assert!(backup_path.starts_with(temp_path));
     assert!(backup_path.to_string_lossy().contains(&backup_filename));
 }
+
+// RSA key used to sign ID tokens served by the mock Google endpoints
+const TEST_RSA_KEY: &str = include_str!("fixtures/oauth_test_key.pem");
+const TEST_RSA_N: &str = "tgqUwznLPINPK6KMoDbcGnCqTk7WJITSgokI1mmbqW7pHtVICrjS8FdruK9NLjKDXUqFSwETzjHWmyxEaeLGvFXZZ4OkY4jXJCdEn2mH_ZYrIdPGPByTDHvKDV5ggizZDG7EySzEubuIlezdBfqiZkqYvXVE_Uq4CiEV-k2l9EOWnm1VU8JAngXiwvOf9bSSbvTSJkyVe2vT9grAD2dE424pzLWB7Df05UPg0Vz1NCeLml_qkoe6A9uYbAkrcOolU9NxFcsBdCgvn1u-N0BkFCZ8SSZtVXr3WZV4dxJmK234B9FhRblERtzuqagOQRjzcwjv0bwUBOlOchy3z0ZT6w";
+
+fn sign_test_id_token(claims: serde_json::Value) -> String {
+    let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
+    header.kid = Some("test-key".to_string());
+    jsonwebtoken::encode(
+        &header,
+        &claims,
+        &jsonwebtoken::EncodingKey::from_rsa_pem(TEST_RSA_KEY.as_bytes()).unwrap(),
+    )
+    .unwrap()
+}
+
+/// Serve token, JWKS and userinfo endpoints on a random local port
+async fn spawn_mock_google(id_token: String) -> String {
+    spawn_mock_oidc(Arc::new(Mutex::new(id_token))).await
+}
+
+/// Like `spawn_mock_google`, plus a discovery document naming the server as
+/// issuer. The token endpoint returns whatever ID token is in the slot.
+async fn spawn_mock_oidc(id_token: Arc<Mutex<String>>) -> String {
+    use axum::{routing::get, routing::post, Json, Router};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let discovery = serde_json::json!({
+        "issuer": base_url,
+        "authorization_endpoint": format!("{}/authorize", base_url),
+        "token_endpoint": format!("{}/token", base_url),
+        "jwks_uri": format!("{}/jwks", base_url),
+        "userinfo_endpoint": format!("{}/userinfo", base_url)
+    });
+
+    let app = Router::new()
+        .route(
+            "/.well-known/openid-configuration",
+            get(move || async move { Json(discovery) }),
+        )
+        .route(
+            "/token",
+            post(move || async move {
+                let id_token = id_token.lock().unwrap().clone();
+                Json(serde_json::json!({
+                    "access_token": "mock-access-token",
+                    "id_token": id_token,
+                    "expires_in": 3599,
+                    "token_type": "Bearer"
+                }))
+            }),
+        )
+        .route(
+            "/jwks",
+            get(|| async {
+                Json(serde_json::json!({
+                    "keys": [{
+                        "kty": "RSA",
+                        "kid": "test-key",
+                        "use": "sig",
+                        "alg": "RS256",
+                        "n": TEST_RSA_N,
+                        "e": "AQAB"
+                    }]
+                }))
+            }),
+        )
+        .route(
+            "/userinfo",
+            get(|| async {
+                Json(serde_json::json!({
+                    "sub": "google-sub-123",
+                    "email": "parent@example.com",
+                    "name": "Parent User"
+                }))
+            }),
+        );
+
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    base_url
+}
+
+fn google_test_config(base_url: &str) -> AppConfig {
+    let mut config = test_config();
+    config.google_client_id = Some("test-client-id".to_string());
+    config.google_client_secret = Some("test-client-secret".to_string());
+    config.google_token_endpoint = format!("{}/token", base_url);
+    config.google_jwks_uri = format!("{}/jwks", base_url);
+    config.google_userinfo_endpoint = format!("{}/userinfo", base_url);
+    config
+}
+
+fn google_id_token_claims(aud: &str, nonce: &str) -> serde_json::Value {
+    let now = chrono::Utc::now().timestamp();
+    serde_json::json!({
+        "iss": "https://accounts.google.com",
+        "sub": "google-sub-123",
+        "aud": aud,
+        "iat": now,
+        "exp": now + 3600,
+        "email": "parent@example.com",
+        "email_verified": true,
+        "name": "Parent User",
+        "nonce": nonce
+    })
+}
+
+/// Start a login and pull the issued state and nonce back out of the authorize URL
+async fn start_test_google_login(db: &SqlitePool) -> (String, String) {
+    let auth_service = AuthService::new(db.clone(), google_test_config("http://127.0.0.1"));
+    let start = auth_service.start_google_oauth().await.unwrap();
+
+    let url = url::Url::parse(&start.authorization_url).unwrap();
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .unwrap()
+    };
+
+    assert_eq!(param("state"), start.state);
+    assert_eq!(param("code_challenge_method"), "S256");
+    (start.state, param("nonce"))
+}
+
+/// Value of the cookie the start endpoint sets to bind `state` to the browser
+fn oauth_state_cookie(auth_service: &AuthService, state: &str) -> String {
+    let cookie = auth_service.create_oauth_state_cookie_string(state).unwrap();
+    cookie.split(';').next().unwrap().split_once('=').unwrap().1.to_string()
+}
+
+#[tokio::test]
+async fn test_google_oauth_login_exchanges_code_and_verifies_id_token() {
+    let db = setup_test_db().await;
+    let (state, nonce) = start_test_google_login(&db).await;
+    let id_token = sign_test_id_token(google_id_token_claims("test-client-id", &nonce));
+    let base_url = spawn_mock_google(id_token).await;
+    let auth_service = AuthService::new(db.clone(), google_test_config(&base_url));
+    let request = OidcAuthRequest {
+        code: "mock-authorization-code".to_string(),
+        state: Some(state.clone()),
+    };
+
+    let state_cookie = oauth_state_cookie(&auth_service, &state);
+    let (response, cookie) = expect_session(
+        auth_service
+            .google_oauth_login(request, Some(&state_cookie))
+            .await
+            .unwrap(),
+    );
+
+    assert_eq!(response.user.email, "parent@example.com");
+    assert_eq!(response.user.full_name, Some("Parent User".to_string()));
+    assert!(cookie.starts_with("session="));
+
+    let user = auth_service
+        .get_user_by_google_id("google-sub-123")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(user.id, response.user.id);
+}
+
+#[tokio::test]
+async fn test_google_oauth_login_needs_the_starting_browsers_state_cookie() {
+    let db = setup_test_db().await;
+    let (state, nonce) = start_test_google_login(&db).await;
+    let id_token = sign_test_id_token(google_id_token_claims("test-client-id", &nonce));
+    let base_url = spawn_mock_google(id_token).await;
+    let auth_service = AuthService::new(db.clone(), google_test_config(&base_url));
+    let request = || OidcAuthRequest {
+        code: "mock-authorization-code".to_string(),
+        state: Some(state.clone()),
+    };
+
+    // A browser without the state cookie is refused
+    let result = auth_service.google_oauth_login(request(), None).await;
+    assert!(matches!(result, Err(AppError::OAuthState(_))));
+
+    // So is one holding the cookie of another login
+    let other_browser = oauth_state_cookie(&auth_service, "another-state");
+    let result = auth_service
+        .google_oauth_login(request(), Some(&other_browser))
+        .await;
+    assert!(matches!(result, Err(AppError::OAuthState(_))));
+
+    // Refused attempts don't use the state up for the browser that started it
+    let state_cookie = oauth_state_cookie(&auth_service, &state);
+    assert!(auth_service
+        .google_oauth_login(request(), Some(&state_cookie))
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn test_oauth_start_route_sets_a_hashed_state_cookie() {
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use tower::ServiceExt;
+
+    let db = setup_test_db().await;
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let config = google_test_config("http://127.0.0.1");
+    let auth_service = AuthService::new(db.clone(), config.clone());
+    let app = edufy::handlers::create_router(edufy::AppState::new(db, config, kv).unwrap());
+
+    let response = app
+        .oneshot(Request::get("/api/auth/google/start").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let cookie = response.headers()["set-cookie"].to_str().unwrap().to_string();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let start: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let state = start["state"].as_str().unwrap();
+
+    // The cookie carries a hash of the state, never the state itself
+    assert!(cookie.contains("HttpOnly") && cookie.contains("SameSite=Lax"));
+    assert!(!cookie.contains(state));
+    assert_eq!(
+        cookie.split(';').next().unwrap().split_once('=').unwrap().1,
+        oauth_state_cookie(&auth_service, state)
+    );
+}
+
+#[tokio::test]
+async fn test_google_oauth_login_rejects_wrong_audience() {
+    let db = setup_test_db().await;
+    let (state, nonce) = start_test_google_login(&db).await;
+    let id_token = sign_test_id_token(google_id_token_claims("someone-elses-client", &nonce));
+    let base_url = spawn_mock_google(id_token).await;
+    let auth_service = AuthService::new(db.clone(), google_test_config(&base_url));
+
+    let state_cookie = oauth_state_cookie(&auth_service, &state);
+    let result = auth_service
+        .google_oauth_login(
+            OidcAuthRequest {
+                code: "mock-authorization-code".to_string(),
+                state: Some(state),
+            },
+            Some(&state_cookie),
+        )
+        .await;
+
+    assert!(result.is_err());
+    assert!(auth_service
+        .get_user_by_email("parent@example.com")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+fn entra_provider(issuer: &str) -> OidcProviderConfig {
+    OidcProviderConfig {
+        name: "entra".to_string(),
+        issuer: issuer.to_string(),
+        client_id: "entra-client-id".to_string(),
+        client_secret: None,
+        redirect_uri: "http://localhost:5173/auth/entra/callback".to_string(),
+        scopes: "openid email profile".to_string(),
+        trust_email: true,
+    }
+}
+
+/// An auth service with Google and an Entra provider served by a mock
+/// discovery document, whose ID token the test sets per login
+struct EntraApp {
+    db: SqlitePool,
+    config: AppConfig,
+    auth_service: AuthService,
+    base_url: String,
+    id_token: Arc<Mutex<String>>,
+}
+
+async fn entra_app() -> EntraApp {
+    let db = setup_test_db().await;
+    let id_token = Arc::new(Mutex::new(String::new()));
+    let base_url = spawn_mock_oidc(id_token.clone()).await;
+    let mut config = google_test_config(&base_url);
+    config.oidc_providers = vec![entra_provider(&base_url)];
+    let auth_service = AuthService::new(db.clone(), config.clone());
+    EntraApp { db, config, auth_service, base_url, id_token }
+}
+
+/// Sign the provider's ID token for a started flow and build its callback
+fn entra_callback(
+    entra: &EntraApp,
+    start: OidcAuthStartResponse,
+    subject: &str,
+    email: &str,
+    verified: Option<bool>,
+) -> (OidcAuthRequest, String) {
+    assert_eq!(start.provider, "entra");
+    assert!(start
+        .authorization_url
+        .starts_with(&format!("{}/authorize?", entra.base_url)));
+    let url = url::Url::parse(&start.authorization_url).unwrap();
+    let nonce = url
+        .query_pairs()
+        .find(|(key, _)| key == "nonce")
+        .unwrap()
+        .1
+        .into_owned();
+
+    let now = chrono::Utc::now().timestamp();
+    let mut claims = serde_json::json!({
+        "iss": entra.base_url,
+        "sub": subject,
+        "aud": "entra-client-id",
+        "iat": now,
+        "exp": now + 3600,
+        "email": email,
+        "name": "Staff Member",
+        "nonce": nonce
+    });
+    if let Some(verified) = verified {
+        claims["email_verified"] = serde_json::json!(verified);
+    }
+    *entra.id_token.lock().unwrap() = sign_test_id_token(claims);
+
+    let state_cookie = oauth_state_cookie(&entra.auth_service, &start.state);
+    let request = OidcAuthRequest {
+        code: "mock-authorization-code".to_string(),
+        state: Some(start.state),
+    };
+    (request, state_cookie)
+}
+
+/// Log in through Entra as the given subject
+async fn entra_login(
+    entra: &EntraApp,
+    subject: &str,
+    email: &str,
+    verified: Option<bool>,
+) -> Result<edufy::models::LoginOutcome, AppError> {
+    let start = entra.auth_service.start_oidc_login("entra").await.unwrap();
+    let (request, state_cookie) = entra_callback(entra, start, subject, email, verified);
+    entra
+        .auth_service
+        .oidc_login("entra", request, Some(&state_cookie))
+        .await
+}
+
+/// Start a link for `owner_id` and finish it as `finisher_id`
+async fn entra_link(
+    entra: &EntraApp,
+    owner_id: &str,
+    finisher_id: &str,
+    subject: &str,
+    email: &str,
+) -> Result<(), AppError> {
+    let start = entra
+        .auth_service
+        .start_identity_link(owner_id, "entra")
+        .await
+        .unwrap();
+    let (request, state_cookie) = entra_callback(entra, start, subject, email, None);
+    entra
+        .auth_service
+        .link_oidc_identity(finisher_id, "entra", request, Some(&state_cookie))
+        .await
+}
+
+async fn google_user(auth_service: &AuthService, email: &str) -> User {
+    auth_service
+        .create_user_with_google(email.to_string(), format!("google-{email}"), None)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_misconfigured_oidc_providers_fail_at_startup() {
+    let EntraApp { config, base_url, .. } = entra_app().await;
+
+    let registry = OidcRegistry::from_config(&config).unwrap();
+    assert_eq!(registry.names(), vec!["google", "entra"]);
+
+    let mut bad = config.clone();
+    bad.oidc_providers = vec![entra_provider("http://idp.example.com")];
+    assert!(OidcRegistry::from_config(&bad).is_err());
+    bad.oidc_providers = vec![OidcProviderConfig {
+        name: "google".to_string(),
+        ..entra_provider(&base_url)
+    }];
+    assert!(OidcRegistry::from_config(&bad).is_err());
+    bad.oidc_providers = vec![OidcProviderConfig {
+        scopes: "email".to_string(),
+        ..entra_provider(&base_url)
+    }];
+    assert!(OidcRegistry::from_config(&bad).is_err());
+}
+
+#[tokio::test]
+async fn test_multi_tenant_oidc_issuers_are_rejected() {
+    let EntraApp { config, .. } = entra_app().await;
+
+    // They can't pass the exact issuer check
+    let mut bad = config.clone();
+    for issuer in [
+        "https://login.microsoftonline.com/common/v2.0",
+        "https://login.microsoftonline.com/{tenantid}/v2.0",
+    ] {
+        bad.oidc_providers = vec![entra_provider(issuer)];
+        assert!(OidcRegistry::from_config(&bad).is_err());
+    }
+}
+
+#[tokio::test]
+async fn test_oidc_login_uses_the_discovered_endpoints() {
+    let entra = entra_app().await;
+
+    let outcome = entra_login(&entra, "entra-oid-42", "staff@example.com", None)
+        .await
+        .unwrap();
+    let (login, _) = expect_session(outcome);
+    assert_eq!(login.user.email, "staff@example.com");
+    let user = entra
+        .auth_service
+        .get_user_by_identity("entra", "entra-oid-42")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(user.id, login.user.id);
+    assert!(user.google_id.is_none());
+}
+
+#[tokio::test]
+async fn test_oidc_subject_not_email_identifies_the_account() {
+    let entra = entra_app().await;
+
+    let first = entra_login(&entra, "entra-oid-42", "staff@example.com", None)
+        .await
+        .unwrap();
+    let second = entra_login(&entra, "entra-oid-42", "renamed@example.com", None)
+        .await
+        .unwrap();
+    assert_eq!(expect_session(second).0.user.id, expect_session(first).0.user.id);
+    let (email,): (String,) = sqlx::query_as(
+        "SELECT email FROM user_identities WHERE provider = 'entra' AND subject = 'entra-oid-42'",
+    )
+    .fetch_one(&entra.db)
+    .await
+    .unwrap();
+    assert_eq!(email, "renamed@example.com");
+}
+
+#[tokio::test]
+async fn test_oidc_login_needs_a_verified_email_to_take_over_an_account() {
+    let entra = entra_app().await;
+    google_user(&entra.auth_service, "teacher@example.com").await;
+
+    // A trusted email is not enough
+    let result = entra_login(&entra, "entra-oid-7", "teacher@example.com", None).await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+    assert!(entra
+        .auth_service
+        .get_user_by_identity("entra", "entra-oid-7")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_oidc_verified_email_links_on_first_login() {
+    let entra = entra_app().await;
+    let parent = google_user(&entra.auth_service, "parent2@example.com").await;
+
+    let outcome = entra_login(&entra, "entra-oid-9", "parent2@example.com", Some(true))
+        .await
+        .unwrap();
+    assert_eq!(expect_session(outcome).0.user.id, parent.id);
+}
+
+#[tokio::test]
+async fn test_signed_in_users_can_link_an_oidc_identity() {
+    let entra = entra_app().await;
+    let existing = google_user(&entra.auth_service, "teacher@example.com").await;
+
+    entra_link(&entra, &existing.id, &existing.id, "entra-oid-7", "teacher@example.com")
+        .await
+        .unwrap();
+    let outcome = entra_login(&entra, "entra-oid-7", "teacher@example.com", None)
+        .await
+        .unwrap();
+    assert_eq!(expect_session(outcome).0.user.id, existing.id);
+}
+
+#[tokio::test]
+async fn test_oidc_link_state_cannot_log_in() {
+    let entra = entra_app().await;
+    let existing = google_user(&entra.auth_service, "teacher@example.com").await;
+
+    let start = entra
+        .auth_service
+        .start_identity_link(&existing.id, "entra")
+        .await
+        .unwrap();
+    let (request, state_cookie) =
+        entra_callback(&entra, start, "entra-oid-7", "teacher@example.com", None);
+    let result = entra
+        .auth_service
+        .oidc_login("entra", request, Some(&state_cookie))
+        .await;
+    assert!(matches!(result, Err(AppError::OAuthState(_))));
+}
+
+#[tokio::test]
+async fn test_oidc_link_can_only_be_finished_by_its_user() {
+    let entra = entra_app().await;
+    let existing = google_user(&entra.auth_service, "teacher@example.com").await;
+    let other = google_user(&entra.auth_service, "other@example.com").await;
+
+    let result = entra_link(&entra, &existing.id, &other.id, "entra-oid-7", "teacher@example.com").await;
+    assert!(matches!(result, Err(AppError::OAuthState(_))));
+    assert!(entra
+        .auth_service
+        .get_user_by_identity("entra", "entra-oid-7")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_oidc_subject_linked_elsewhere_cannot_be_linked_again() {
+    let entra = entra_app().await;
+    let existing = google_user(&entra.auth_service, "teacher@example.com").await;
+    entra_login(&entra, "entra-oid-42", "staff@example.com", None)
+        .await
+        .unwrap();
+
+    let result = entra_link(&entra, &existing.id, &existing.id, "entra-oid-42", "staff@example.com").await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+}
+
+#[tokio::test]
+async fn test_google_state_cannot_finish_an_entra_login() {
+    let entra = entra_app().await;
+
+    let (state, _) = start_test_google_login(&entra.db).await;
+    let state_cookie = oauth_state_cookie(&entra.auth_service, &state);
+    let result = entra
+        .auth_service
+        .oidc_login(
+            "entra",
+            OidcAuthRequest {
+                code: "mock-authorization-code".to_string(),
+                state: Some(state),
+            },
+            Some(&state_cookie),
+        )
+        .await;
+    assert!(matches!(result, Err(AppError::OAuthState(_))));
+}
+
+#[tokio::test]
+async fn test_unknown_oidc_provider_is_not_found() {
+    let entra = entra_app().await;
+
+    let result = entra.auth_service.start_oidc_login("okta").await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+}
+
+
+#[tokio::test]
+async fn test_oauth_state_is_single_use_and_expires() {
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), google_test_config("http://127.0.0.1"));
+
+    // Unknown state
+    let result = auth_service.consume_oauth_state("never-issued").await;
+    assert!(matches!(result, Err(AppError::OAuthState(_))));
+
+    // First use succeeds, replay is rejected
+    let (state, _) = start_test_google_login(&db).await;
+    assert!(auth_service.consume_oauth_state(&state).await.is_ok());
+    let result = auth_service.consume_oauth_state(&state).await;
+    assert!(matches!(result, Err(AppError::OAuthState(_))));
+
+    // Expired state is rejected
+    let (state, _) = start_test_google_login(&db).await;
+    sqlx::query("UPDATE oauth_states SET expires_at = ? WHERE state = ?")
+        .bind(chrono::Utc::now() - chrono::Duration::minutes(1))
+        .bind(&state)
+        .execute(&db)
+        .await
+        .unwrap();
+    let result = auth_service.consume_oauth_state(&state).await;
+    assert!(matches!(result, Err(AppError::OAuthState(_))));
+}
+
+#[tokio::test]
+async fn test_login_route_sends_through_the_shared_mailer() {
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+
+    struct RecordingMailer(Mutex<Vec<MailMessage>>);
+    #[async_trait::async_trait]
+    impl MailSender for RecordingMailer {
+        async fn send(&self, message: &MailMessage) -> Result<(), AppError> {
+            self.0.lock().unwrap().push(message.clone());
+            Ok(())
+        }
+    }
+
+    let db = setup_test_db().await;
+    AuthService::new(db.clone(), test_config())
+        .create_user_with_google("guardian@example.com".to_string(), "google_guardian".to_string(), None)
+        .await
+        .unwrap();
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let mut state = edufy::AppState::new(db.clone(), test_config(), kv).unwrap();
+    let mailer = Arc::new(RecordingMailer(Mutex::new(Vec::new())));
+    state.mailer = mailer.clone();
+    let app = edufy::handlers::create_router(state);
+
+    let response = app
+        .oneshot(
+            Request::post("/api/auth/login")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"email":"guardian@example.com"}"#))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+    // Delivery happens in the background after the response
+    for _ in 0..50 {
+        if !mailer.0.lock().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    let sent = mailer.0.lock().unwrap();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].to, "guardian@example.com");
+}
+
+#[tokio::test]
+async fn test_login_route_answers_the_same_when_mail_fails() {
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use tower::ServiceExt;
+
+    struct FailingMailer;
+    #[async_trait::async_trait]
+    impl MailSender for FailingMailer {
+        async fn send(&self, _message: &MailMessage) -> Result<(), AppError> {
+            Err(AppError::Internal("SMTP relay unavailable".to_string()))
+        }
+    }
+
+    let db = setup_test_db().await;
+    AuthService::new(db.clone(), test_config())
+        .create_user_with_google("guardian@example.com".to_string(), "google_guardian".to_string(), None)
+        .await
+        .unwrap();
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let mut state = edufy::AppState::new(db, test_config(), kv).unwrap();
+    state.mailer = Arc::new(FailingMailer);
+    let app = edufy::handlers::create_router(state);
+
+    for email in ["guardian@example.com", "nobody@example.com"] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/api/auth/login")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::json!({ "email": email }).to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+}
+
+#[tokio::test]
+async fn test_app_state_needs_a_usable_mail_transport() {
+    let db = setup_test_db().await;
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+
+    // SMTP without a relay host fails at startup, not on the first login
+    let mut config = test_config();
+    config.mail_transport = "smtp".to_string();
+    assert!(edufy::AppState::new(db.clone(), config, kv.clone()).is_err());
+
+    let mut config = test_config();
+    config.mail_transport = "pigeon".to_string();
+    assert!(edufy::AppState::new(db, config, kv).is_err());
+}
+
+#[tokio::test]
+async fn test_magic_link_for_an_unknown_address_sends_nothing() {
+    let db = setup_test_db().await;
+    let outbox = tempdir().unwrap();
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let mailer = FileMailSender::new(outbox.path().to_str().unwrap());
+
+    // Succeeds silently, so the answer can't be used to find accounts
+    auth_service
+        .send_magic_link(
+            LoginRequest {
+                email: "nobody@example.com".to_string(),
+            },
+            &mailer,
+        )
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read_dir(outbox.path()).map(|d| d.count()).unwrap_or(0), 0);
+}
+
+#[tokio::test]
+async fn test_magic_link_mail_failure_is_not_reported() {
+    struct FailingMailer;
+    #[async_trait::async_trait]
+    impl MailSender for FailingMailer {
+        async fn send(&self, _message: &MailMessage) -> Result<(), AppError> {
+            Err(AppError::Internal("SMTP relay unavailable".to_string()))
+        }
+    }
+
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), test_config());
+    auth_service
+        .create_user_with_google("guardian@example.com".to_string(), "google_guardian".to_string(), None)
+        .await
+        .unwrap();
+
+    // Answers like an unknown address, so it can't reveal the account
+    auth_service
+        .send_magic_link(
+            LoginRequest {
+                email: "guardian@example.com".to_string(),
+            },
+            &FailingMailer,
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_magic_link_login_is_single_use() {
+    let db = setup_test_db().await;
+    let outbox = tempdir().unwrap();
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let mailer = FileMailSender::new(outbox.path().to_str().unwrap());
+
+    let user = auth_service
+        .create_user_with_google(
+            "guardian@example.com".to_string(),
+            "google_guardian".to_string(),
+            None,
+        )
+        .await
+        .unwrap();
+
+    auth_service
+        .send_magic_link(
+            LoginRequest {
+                email: "guardian@example.com".to_string(),
+            },
+            &mailer,
+        )
+        .await
+        .unwrap();
+
+    let sent: Vec<_> = std::fs::read_dir(outbox.path()).unwrap().collect();
+    assert_eq!(sent.len(), 1);
+    let message: MailMessage =
+        serde_json::from_str(&std::fs::read_to_string(sent[0].as_ref().unwrap().path()).unwrap())
+            .unwrap();
+    assert_eq!(message.to, "guardian@example.com");
+
+    let link = message
+        .body
+        .split_whitespace()
+        .find(|word| word.starts_with("http"))
+        .unwrap();
+    let token = url::Url::parse(link)
+        .unwrap()
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .map(|(_, value)| value.into_owned())
+        .unwrap();
+
+    // Only the hash is stored
+    let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM magic_link_tokens WHERE token_hash = ?")
+        .bind(&token)
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(stored, 0);
+
+    let (response, cookie) = expect_session(auth_service.verify_magic_link(&token).await.unwrap());
+    assert_eq!(response.user.id, user.id);
+    assert!(cookie.starts_with("session="));
+
+    assert!(auth_service.verify_magic_link(&token).await.is_err());
+}
+
+#[tokio::test]
+async fn test_refresh_token_rotation_and_reuse_detection() {
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), test_config());
+
+    let user = auth_service
+        .create_user_with_google(
+            "rotation@example.com".to_string(),
+            "google_rotation".to_string(),
+            None,
+        )
+        .await
+        .unwrap();
+
+    let (login, _) = auth_service.create_login_session(user).await.unwrap();
+
+    // Access tokens are short-lived
+    let claims = auth_service.verify_jwt_token(&login.token).await.unwrap();
+    assert!(claims.exp - claims.iat <= 15 * 60);
+
+    // Refreshing rotates the refresh token
+    let (rotated, cookie) = auth_service
+        .refresh_session(&login.refresh_token)
+        .await
+        .unwrap();
+    assert_ne!(rotated.refresh_token, login.refresh_token);
+    assert!(cookie.starts_with("session="));
+    assert!(auth_service.verify_jwt_token(&rotated.token).await.is_ok());
+
+    // Replaying the old token is rejected and revokes the whole family
+    assert!(auth_service.refresh_session(&login.refresh_token).await.is_err());
+    assert!(auth_service
+        .refresh_session(&rotated.refresh_token)
+        .await
+        .is_err());
+}
+
+/// A user signed in on a laptop and on a second device whose login has
+/// been refreshed once; returns the laptop login and the refreshed one
+async fn two_device_logins(
+    db: &SqlitePool,
+) -> (AuthService, User, edufy::models::LoginResponse, edufy::models::LoginResponse) {
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let user = auth_service
+        .create_user_with_google(
+            "sessions@example.com".to_string(),
+            "google_sessions".to_string(),
+            None,
+        )
+        .await
+        .unwrap();
+
+    let laptop = AuthService::new(db.clone(), test_config()).with_client_info(ClientInfo {
+        user_agent: Some("Laptop Browser".to_string()),
+        ip_address: Some("203.0.113.10".to_string()),
+    });
+    let (first, _) = laptop.create_login_session(user.clone()).await.unwrap();
+    let (second, _) = auth_service.create_login_session(user.clone()).await.unwrap();
+    let (rotated, _) = auth_service
+        .refresh_session(&second.refresh_token)
+        .await
+        .unwrap();
+    (auth_service, user, first, rotated)
+}
+
+#[tokio::test]
+async fn test_sessions_are_listed_per_device() {
+    let db = setup_test_db().await;
+    let (auth_service, user, _, rotated) = two_device_logins(&db).await;
+
+    // Refreshing keeps the login as one session under its newest jti
+    let current = auth_service.verify_jwt_token(&rotated.token).await.unwrap();
+    let sessions = auth_service
+        .list_sessions(&user.id, Some(&current.jti))
+        .await
+        .unwrap();
+    assert_eq!(sessions.len(), 2);
+    let current_session = sessions.iter().find(|s| s.current).unwrap();
+    assert_eq!(current_session.jti, current.jti);
+    let laptop_session = sessions.iter().find(|s| !s.current).unwrap();
+    assert_eq!(laptop_session.user_agent.as_deref(), Some("Laptop Browser"));
+    assert_eq!(laptop_session.ip_address.as_deref(), Some("203.0.113.10"));
+}
+
+#[tokio::test]
+async fn test_revoking_a_session_ends_only_that_login() {
+    let db = setup_test_db().await;
+    let (auth_service, user, laptop, rotated) = two_device_logins(&db).await;
+    let laptop_jti = auth_service.verify_jwt_token(&laptop.token).await.unwrap().jti;
+
+    auth_service.revoke_session(&user.id, &laptop_jti).await.unwrap();
+    assert!(auth_service.verify_jwt_token(&laptop.token).await.is_err());
+    assert!(auth_service.refresh_session(&laptop.refresh_token).await.is_err());
+    assert!(auth_service.verify_jwt_token(&rotated.token).await.is_ok());
+    assert_eq!(
+        auth_service.list_sessions(&user.id, None).await.unwrap().len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_another_users_session_cannot_be_revoked_by_jti() {
+    let db = setup_test_db().await;
+    let (auth_service, _, _, rotated) = two_device_logins(&db).await;
+    let current = auth_service.verify_jwt_token(&rotated.token).await.unwrap();
+
+    assert!(matches!(
+        auth_service.revoke_session("someone-else", &current.jti).await,
+        Err(AppError::NotFound(_))
+    ));
+    assert!(auth_service.verify_jwt_token(&rotated.token).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_sign_out_everywhere_ends_every_login() {
+    let db = setup_test_db().await;
+    let (auth_service, user, laptop, rotated) = two_device_logins(&db).await;
+
+    let revoked = auth_service.revoke_all_sessions(&user.id).await.unwrap();
+    assert_eq!(revoked, 2);
+    for login in [&laptop, &rotated] {
+        assert!(auth_service.verify_jwt_token(&login.token).await.is_err());
+        assert!(auth_service.refresh_session(&login.refresh_token).await.is_err());
+    }
+    assert!(auth_service.list_sessions(&user.id, None).await.unwrap().is_empty());
+}
+
+
+#[tokio::test]
+async fn test_revocation_cache_rechecks_not_revoked_answers() {
+    let exp = chrono::Utc::now().timestamp() + 900;
+
+    // Within the TTL a "not revoked" answer is served from the cache
+    let cache = RevocationCache::new();
+    cache.mark_active("jti-1", exp);
+    assert_eq!(cache.get("jti-1"), Some(false));
+
+    // Once stale it goes back to the table, where another instance may
+    // have revoked the token
+    let cache = RevocationCache::with_active_ttl(0);
+    cache.mark_active("jti-1", exp);
+    assert_eq!(cache.get("jti-1"), None);
+    cache.mark_revoked("jti-1", exp);
+    assert_eq!(cache.get("jti-1"), Some(true));
+}
+
+#[tokio::test]
+async fn test_revocation_cache_never_shadows_a_later_revocation() {
+    let cache = RevocationCache::new();
+    let exp = chrono::Utc::now().timestamp() + 900;
+    assert_eq!(cache.get("jti-1"), None);
+    cache.mark_active("jti-1", exp);
+    assert_eq!(cache.get("jti-1"), Some(false));
+    cache.mark_revoked("jti-1", exp);
+    cache.mark_active("jti-1", exp);
+    assert_eq!(cache.get("jti-1"), Some(true));
+
+    let metrics = cache.metrics();
+    assert_eq!((metrics.hits, metrics.misses), (2, 1));
+    assert!((metrics.hit_rate - 2.0 / 3.0).abs() < f64::EPSILON);
+}
+
+#[tokio::test]
+async fn test_token_verified_before_revocation_is_rejected_after_it() {
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let user = auth_service
+        .create_user_with_google("prune@example.com".to_string(), "google_prune".to_string(), None)
+        .await
+        .unwrap();
+    let token = auth_service.create_jwt_token(&user.id).await.unwrap();
+    assert!(auth_service.verify_jwt_token(&token).await.is_ok());
+    auth_service.revoke_token(&token).await.unwrap();
+    assert!(auth_service.verify_jwt_token(&token).await.is_err());
+}
+
+#[tokio::test]
+async fn test_pruning_only_drops_revocations_of_expired_tokens() {
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let user = auth_service
+        .create_user_with_google("prune@example.com".to_string(), "google_prune".to_string(), None)
+        .await
+        .unwrap();
+    let token = auth_service.create_jwt_token(&user.id).await.unwrap();
+    auth_service.revoke_token(&token).await.unwrap();
+
+    sqlx::query("INSERT INTO revocations (jti, user_id, revoked_at, expires_at) VALUES (?, ?, ?, ?)")
+        .bind("expired-jti")
+        .bind(&user.id)
+        .bind(chrono::Utc::now() - chrono::Duration::days(2))
+        .bind(chrono::Utc::now() - chrono::Duration::days(1))
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let pruned = RevocationService::new(db.clone()).prune_expired().await.unwrap();
+    assert_eq!(pruned, 1);
+
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM revocations")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(remaining, 1);
+    assert!(auth_service.verify_jwt_token(&token).await.is_err());
+}
+
+#[tokio::test]
+async fn test_seeded_role_permissions() {
+    use edufy::models::Permission;
+
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), test_config());
+
+    // Teachers author posts but cannot publish or restore
+    assert!(auth_service.role_has_permission("teacher", Permission::PostsWrite).await.unwrap());
+    assert!(!auth_service.role_has_permission("teacher", Permission::PostsPublish).await.unwrap());
+    assert!(auth_service.role_has_permission("admin", Permission::BackupRestore).await.unwrap());
+    assert!(matches!(
+        auth_service.ensure_permission("student", Permission::MediaUpload).await,
+        Err(AppError::MissingPermission(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_role_permission_changes_take_effect_immediately() {
+    use edufy::models::Permission;
+
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), test_config());
+
+    // The admin role cannot lock itself out of user management
+    assert!(auth_service
+        .set_role_permissions(&UserRole::Admin, &[Permission::PostsWrite])
+        .await
+        .is_err());
+
+    // Granting a permission takes effect immediately
+    auth_service
+        .set_role_permissions(&UserRole::Teacher, &[Permission::PostsWrite, Permission::PostsPublish])
+        .await
+        .unwrap();
+    assert_eq!(
+        auth_service.role_permissions(&UserRole::Teacher).await.unwrap(),
+        vec![Permission::PostsPublish, Permission::PostsWrite]
+    );
+}
+
+#[tokio::test]
+async fn test_require_permission_layer_forbids_roles_without_it() {
+    use axum::{body::Body, http::Request, http::StatusCode, routing::get, Extension, Router};
+    use edufy::middleware::require_permission;
+    use edufy::models::{Permission, UserResponse};
+    use tower::ServiceExt;
+
+    let db = setup_test_db().await;
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let state = edufy::AppState::new(db.clone(), test_config(), kv).unwrap();
+
+    let request_as = |role: &str| {
+        let app = Router::new()
+            .route(
+                "/restore",
+                get(|| async { "restored" })
+                    .route_layer(require_permission(&state, Permission::BackupRestore)),
+            )
+            .layer(Extension(UserResponse {
+                id: "user-1".to_string(),
+                email: "user@example.com".to_string(),
+                role: role.to_string(),
+                full_name: None,
+                permissions: None,
+            }));
+        app.oneshot(Request::get("/restore").body(Body::empty()).unwrap())
+    };
+
+    assert_eq!(request_as("admin").await.unwrap().status(), StatusCode::OK);
+    assert_eq!(request_as("teacher").await.unwrap().status(), StatusCode::FORBIDDEN);
+}
+
+/// An admin, and a teacher account that admin created
+async fn admin_and_teacher(db: &SqlitePool) -> (User, User) {
+    use edufy::models::CreateUserRequest;
+
+    let admin = AuthService::new(db.clone(), test_config())
+        .create_user_with_google("head@example.com".to_string(), "google_head".to_string(), None)
+        .await
+        .unwrap();
+    let teacher = UserService::new(db.clone())
+        .create_user(
+            CreateUserRequest {
+                email: "Ada@Example.com".to_string(),
+                role: "teacher".to_string(),
+                full_name: Some("Ada".to_string()),
+            },
+            &admin.id,
+        )
+        .await
+        .unwrap();
+    (admin, teacher)
+}
+
+#[tokio::test]
+async fn test_created_users_have_unique_lowercased_emails() {
+    use edufy::models::CreateUserRequest;
+
+    let db = setup_test_db().await;
+    let user_service = UserService::new(db.clone());
+    let (admin, teacher) = admin_and_teacher(&db).await;
+    assert_eq!(teacher.email, "ada@example.com");
+
+    let duplicate = CreateUserRequest {
+        email: "ada@example.com".to_string(),
+        role: "teacher".to_string(),
+        full_name: None,
+    };
+    assert!(matches!(
+        user_service.create_user(duplicate, &admin.id).await,
+        Err(AppError::Conflict(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_user_updates_reject_unknown_roles() {
+    use edufy::models::UpdateUserRequest;
+
+    let db = setup_test_db().await;
+    let user_service = UserService::new(db.clone());
+    let (admin, teacher) = admin_and_teacher(&db).await;
+
+    let bad_role = UpdateUserRequest {
+        role: Some("headmaster".to_string()),
+        full_name: None,
+    };
+    assert!(matches!(
+        user_service.update_user(&teacher.id, bad_role, &admin.id).await,
+        Err(AppError::Validation(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_list_users_searches_and_filters_by_role() {
+    use edufy::models::UserListQuery;
+
+    let db = setup_test_db().await;
+    let user_service = UserService::new(db.clone());
+    let (_, teacher) = admin_and_teacher(&db).await;
+
+    let listed = user_service
+        .list_users(UserListQuery {
+            page: None,
+            per_page: None,
+            search: Some("ADA".to_string()),
+            role: Some("teacher".to_string()),
+        })
+        .await
+        .unwrap();
+    assert_eq!(listed.total, 1);
+    assert_eq!(listed.users[0].id, teacher.id);
+}
+
+#[tokio::test]
+async fn test_deactivated_users_cannot_sign_in() {
+    let db = setup_test_db().await;
+    let user_service = UserService::new(db.clone());
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let (admin, teacher) = admin_and_teacher(&db).await;
+
+    let deactivated = user_service
+        .deactivate_user(&teacher.id, &admin.id)
+        .await
+        .unwrap();
+    assert!(!deactivated.is_active());
+    assert!(auth_service.create_login_session(deactivated).await.is_err());
+
+    // Admins can't deactivate themselves
+    assert!(user_service.deactivate_user(&admin.id, &admin.id).await.is_err());
+}
+
+#[tokio::test]
+async fn test_admin_user_management_is_audited() {
+    use edufy::models::UpdateUserRequest;
+
+    let db = setup_test_db().await;
+    let user_service = UserService::new(db.clone());
+    let (admin, teacher) = admin_and_teacher(&db).await;
+
+    // Role change and rename in one update
+    let updated = user_service
+        .update_user(
+            &teacher.id,
+            UpdateUserRequest {
+                role: Some("admin".to_string()),
+                full_name: Some("Ada Lovelace".to_string()),
+            },
+            &admin.id,
+        )
+        .await
+        .unwrap();
+    assert_eq!(updated.role, "admin");
+    assert_eq!(updated.full_name.as_deref(), Some("Ada Lovelace"));
+
+    user_service.deactivate_user(&teacher.id, &admin.id).await.unwrap();
+    user_service.delete_user(&teacher.id, &admin.id).await.unwrap();
+    assert!(matches!(
+        user_service.get_user(&teacher.id).await,
+        Err(AppError::NotFound(_))
+    ));
+
+    let logs = AuditService::new(db.clone())
+        .get_user_audit_logs(
+            &admin.id,
+            chrono::Utc::now() - chrono::Duration::hours(1),
+            chrono::Utc::now() + chrono::Duration::hours(1),
+        )
+        .await
+        .unwrap();
+    let actions: Vec<&str> = logs.iter().map(|log| log.action.as_str()).collect();
+    assert_eq!(
+        actions,
+        vec!["create_user", "change_user_role", "rename_user", "deactivate_user", "delete_user"]
+    );
+    let role_change = logs.iter().find(|log| log.action == "change_user_role").unwrap();
+    assert_eq!(
+        role_change.details,
+        Some(serde_json::json!({ "before": "teacher", "after": "admin" }))
+    );
+}
+
+#[tokio::test]
+async fn test_list_users_past_the_last_page_is_empty() {
+    use edufy::models::UserListQuery;
+
+    let db = setup_test_db().await;
+    let user_service = UserService::new(db.clone());
+    let auth_service = AuthService::new(db.clone(), test_config());
+
+    auth_service
+        .create_user_with_google("head@example.com".to_string(), "google_head".to_string(), None)
+        .await
+        .unwrap();
+
+    // The offset for the largest page number must not wrap around to page one
+    let listed = user_service
+        .list_users(UserListQuery {
+            page: Some(u32::MAX),
+            per_page: Some(100),
+            search: None,
+            role: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(listed.total, 1);
+    assert!(listed.users.is_empty());
+    assert_eq!(listed.page, u32::MAX);
+}
+
+#[tokio::test]
+async fn test_suspensions_need_a_reason_and_another_user() {
+    let db = setup_test_db().await;
+    let user_service = UserService::new(db.clone());
+    let (admin, teacher) = admin_and_teacher(&db).await;
+
+    assert!(matches!(
+        user_service.suspend_user(&teacher.id, "  ", &admin.id).await,
+        Err(AppError::Validation(_))
+    ));
+    assert!(user_service.suspend_user(&admin.id, "test", &admin.id).await.is_err());
+    let teacher = user_service.get_user(&teacher.id).await.unwrap();
+    assert_eq!(teacher.status, "active");
+}
+
+#[tokio::test]
+async fn test_suspended_users_are_rejected_at_session_verification() {
+    let db = setup_test_db().await;
+    let user_service = UserService::new(db.clone());
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let (admin, teacher) = admin_and_teacher(&db).await;
+    assert_eq!(teacher.status, "active");
+    let (login, _) = auth_service.create_login_session(teacher.clone()).await.unwrap();
+
+    let suspended = user_service
+        .suspend_user(&teacher.id, "Unpaid fees", &admin.id)
+        .await
+        .unwrap();
+    assert_eq!(suspended.status, "suspended");
+    assert_eq!(suspended.status_reason.as_deref(), Some("Unpaid fees"));
+    assert!(suspended.status_changed_at.is_some());
+
+    // The JWT is still valid but the session is refused, as are new logins
+    assert!(auth_service.verify_jwt_token(&login.token).await.is_ok());
+    assert!(matches!(
+        auth_service.verify_session_cookie(&login.token).await,
+        Err(AppError::Auth(message)) if message == "Account is suspended"
+    ));
+    assert!(auth_service.create_login_session(suspended).await.is_err());
+}
+
+#[tokio::test]
+async fn test_reinstating_a_user_restores_their_sessions() {
+    let db = setup_test_db().await;
+    let user_service = UserService::new(db.clone());
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let (admin, teacher) = admin_and_teacher(&db).await;
+    let (login, _) = auth_service.create_login_session(teacher.clone()).await.unwrap();
+    user_service
+        .suspend_user(&teacher.id, "Unpaid fees", &admin.id)
+        .await
+        .unwrap();
+
+    let reinstated = user_service.reinstate_user(&teacher.id, &admin.id).await.unwrap();
+    assert_eq!(reinstated.status, "active");
+    assert!(reinstated.status_reason.is_none());
+    assert!(auth_service.verify_session_cookie(&login.token).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_suspensions_and_reinstatements_are_audited() {
+    let db = setup_test_db().await;
+    let user_service = UserService::new(db.clone());
+    let (admin, teacher) = admin_and_teacher(&db).await;
+    user_service
+        .suspend_user(&teacher.id, "Unpaid fees", &admin.id)
+        .await
+        .unwrap();
+    user_service.reinstate_user(&teacher.id, &admin.id).await.unwrap();
+
+    let logs = AuditService::new(db.clone())
+        .get_user_audit_logs(
+            &admin.id,
+            chrono::Utc::now() - chrono::Duration::hours(1),
+            chrono::Utc::now() + chrono::Duration::hours(1),
+        )
+        .await
+        .unwrap();
+    let suspension = logs.iter().find(|log| log.action == "suspend_user").unwrap();
+    assert_eq!(
+        suspension.details,
+        Some(serde_json::json!({
+            "before": { "status": "active", "status_reason": null },
+            "after": { "status": "suspended", "status_reason": "Unpaid fees" }
+        }))
+    );
+    assert!(logs.iter().any(|log| log.action == "reinstate_user"));
+}
+
+
+/// Sign-ups from llacademy.ng, with staff addresses as teachers and one
+/// Gmail address as an admin
+fn signup_policy_config() -> AppConfig {
+    let mut config = test_config();
+    config.signup_allowed_domains = vec!["llacademy.ng".to_string()];
+    config.signup_role_rules = vec![
+        "@staff.llacademy.ng=teacher".to_string(),
+        "head@gmail.com=admin".to_string(),
+    ];
+    config
+}
+
+async fn rejected_signup_emails(db: &SqlitePool) -> Vec<String> {
+    let logs = AuditService::new(db.clone())
+        .get_user_audit_logs(
+            edufy::audit::SYSTEM_USER_ID,
+            chrono::Utc::now() - chrono::Duration::hours(1),
+            chrono::Utc::now() + chrono::Duration::hours(1),
+        )
+        .await
+        .unwrap();
+    logs.iter()
+        .filter(|log| log.action == "signup_rejected")
+        .filter_map(|log| log.resource_id.clone())
+        .collect()
+}
+
+#[test]
+fn test_signup_policy_roles_by_domain_and_rule() {
+    use edufy::signup::SignupPolicy;
+
+    let policy = SignupPolicy::from_config(&signup_policy_config()).unwrap();
+    assert_eq!(policy.role_for("pupil@llacademy.ng").unwrap(), UserRole::Student);
+    assert_eq!(policy.role_for("Tutor@Staff.llacademy.ng").unwrap(), UserRole::Teacher);
+    assert_eq!(policy.role_for("head@gmail.com").unwrap(), UserRole::Admin);
+    assert!(matches!(
+        policy.role_for("someone@gmail.com"),
+        Err(AppError::SignupRejected(_))
+    ));
+}
+
+#[test]
+fn test_malformed_signup_rules_are_configuration_errors() {
+    use edufy::signup::SignupPolicy;
+
+    let mut config = signup_policy_config();
+    config.signup_role_rules = vec!["@staff.llacademy.ng=principal".to_string()];
+    assert!(SignupPolicy::from_config(&config).is_err());
+}
+
+#[tokio::test]
+async fn test_signups_get_the_role_their_rule_gives() {
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), signup_policy_config());
+
+    let teacher = auth_service
+        .create_user_with_google(
+            "tutor@staff.llacademy.ng".to_string(),
+            "google_tutor".to_string(),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(teacher.role, "teacher");
+}
+
+#[tokio::test]
+async fn test_rejected_signups_create_no_user_and_are_audited() {
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), signup_policy_config());
+
+    let rejected = auth_service
+        .create_user_with_google("someone@gmail.com".to_string(), "google_someone".to_string(), None)
+        .await;
+    assert!(matches!(rejected, Err(AppError::SignupRejected(_))));
+    assert!(auth_service.get_user_by_email("someone@gmail.com").await.unwrap().is_none());
+    assert_eq!(rejected_signup_emails(&db).await, vec!["someone@gmail.com"]);
+}
+
+#[tokio::test]
+async fn test_closed_signups_reject_allowed_domains_too() {
+    let db = setup_test_db().await;
+    let mut config = signup_policy_config();
+    config.signup_mode = "closed".to_string();
+    let auth_service = AuthService::new(db.clone(), config);
+
+    assert!(matches!(
+        auth_service
+            .create_user_with_google("new@llacademy.ng".to_string(), "google_new".to_string(), None)
+            .await,
+        Err(AppError::SignupRejected(_))
+    ));
+    assert_eq!(rejected_signup_emails(&db).await, vec!["new@llacademy.ng"]);
+}
+
+
+/// An admin, and an API key of theirs carrying `scopes`
+async fn admin_api_key(
+    db: &SqlitePool,
+    scopes: &[&str],
+) -> (edufy::models::UserResponse, String, edufy::models::ApiKey) {
+    use edufy::api_keys::ApiKeyService;
+    use edufy::models::{CreateApiKeyRequest, UserResponse};
+
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let admin = auth_service
+        .create_user_with_google("head@example.com".to_string(), "google_head".to_string(), None)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE users SET role = 'admin' WHERE id = ?")
+        .bind(&admin.id)
+        .execute(db)
+        .await
+        .unwrap();
+    let admin = UserResponse {
+        id: admin.id,
+        email: admin.email,
+        role: "admin".to_string(),
+        full_name: None,
+        permissions: None,
+    };
+    let admin_permissions = auth_service.user_permissions(&admin).await.unwrap();
+
+    let (key, api_key) = ApiKeyService::new(db.clone())
+        .create_key(
+            CreateApiKeyRequest {
+                name: "Newsletter sync".to_string(),
+                scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+                expires_at: None,
+            },
+            &admin,
+            &admin_permissions,
+        )
+        .await
+        .unwrap();
+    (admin, key, api_key)
+}
+
+#[tokio::test]
+async fn test_api_keys_are_stored_hashed() {
+    use edufy::api_keys::ApiKeyService;
+
+    let db = setup_test_db().await;
+    let api_key_service = ApiKeyService::new(db.clone());
+    let (_, key, api_key) = admin_api_key(&db, &["posts:write"]).await;
+    assert!(key.starts_with(&format!("lla_{}_", api_key.key_prefix)));
+    assert!(!api_key.key_hash.contains(&key));
+
+    // A wrong secret with a valid prefix is rejected
+    let forged = format!("lla_{}_{}", api_key.key_prefix, "A".repeat(43));
+    assert!(api_key_service.authenticate(&forged).await.is_err());
+}
+
+#[tokio::test]
+async fn test_api_keys_act_as_their_owner_within_their_scopes() {
+    use edufy::api_keys::{ApiKeyService, API_KEY_ROLE};
+    use edufy::models::Permission;
+
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let api_key_service = ApiKeyService::new(db.clone());
+    let (admin, key, _) = admin_api_key(&db, &["posts:write"]).await;
+
+    let key_user = api_key_service.authenticate(&key).await.unwrap();
+    assert_eq!(key_user.id, admin.id);
+    assert_eq!(key_user.role, API_KEY_ROLE);
+    assert_eq!(key_user.permissions, Some(vec![Permission::PostsWrite]));
+    assert!(auth_service
+        .ensure_user_permission(&key_user, Permission::PostsWrite)
+        .await
+        .is_ok());
+    assert!(matches!(
+        auth_service
+            .ensure_user_permission(&key_user, Permission::UsersManage)
+            .await,
+        Err(AppError::MissingPermission(_))
+    ));
+
+    let listed = api_key_service.list_keys().await.unwrap();
+    assert!(listed[0].last_used_at.is_some());
+}
+
+#[tokio::test]
+async fn test_api_keys_reach_admin_routes_but_not_account_routes() {
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use tower::ServiceExt;
+
+    let db = setup_test_db().await;
+    let (_, key, _) = admin_api_key(&db, &["posts:write"]).await;
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let app = edufy::handlers::create_router(edufy::AppState::new(db.clone(), test_config(), kv).unwrap());
+    let send = |method: &str, uri: &str| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("x-api-key", &key)
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    // The owner's own account routes are off limits to their keys
+    assert_eq!(send("GET", "/api/admin/posts").await.unwrap().status(), StatusCode::OK);
+    assert_eq!(send("GET", "/api/users/me").await.unwrap().status(), StatusCode::FORBIDDEN);
+    assert_eq!(
+        send("DELETE", "/api/users/me/sessions").await.unwrap().status(),
+        StatusCode::FORBIDDEN
+    );
+}
+
+#[tokio::test]
+async fn test_api_key_scopes_the_owner_has_lost_no_longer_apply() {
+    use edufy::api_keys::ApiKeyService;
+
+    let db = setup_test_db().await;
+    let (_, key, _) = admin_api_key(&db, &["posts:write"]).await;
+
+    sqlx::query("DELETE FROM role_permissions WHERE role = 'admin' AND permission = 'posts:write'")
+        .execute(&db)
+        .await
+        .unwrap();
+    let key_user = ApiKeyService::new(db.clone()).authenticate(&key).await.unwrap();
+    assert_eq!(key_user.permissions, Some(vec![]));
+}
+
+#[tokio::test]
+async fn test_api_keys_cannot_carry_permissions_their_creator_lacks() {
+    use edufy::api_keys::ApiKeyService;
+    use edufy::models::{CreateApiKeyRequest, UserResponse};
+
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let (admin, _, _) = admin_api_key(&db, &["posts:write"]).await;
+
+    let teacher = UserResponse {
+        role: "teacher".to_string(),
+        ..admin
+    };
+    let teacher_permissions = auth_service.user_permissions(&teacher).await.unwrap();
+    let escalation = ApiKeyService::new(db.clone())
+        .create_key(
+            CreateApiKeyRequest {
+                name: "Too much".to_string(),
+                scopes: vec!["users:manage".to_string()],
+                expires_at: None,
+            },
+            &teacher,
+            &teacher_permissions,
+        )
+        .await;
+    assert!(matches!(escalation, Err(AppError::MissingPermission(_))));
+}
+
+#[tokio::test]
+async fn test_revoked_api_keys_are_rejected() {
+    use edufy::api_keys::ApiKeyService;
+
+    let db = setup_test_db().await;
+    let api_key_service = ApiKeyService::new(db.clone());
+    let (admin, key, api_key) = admin_api_key(&db, &["posts:write"]).await;
+
+    api_key_service.revoke_key(&api_key.id, &admin.id).await.unwrap();
+    assert!(api_key_service.authenticate(&key).await.is_err());
+    assert!(api_key_service.revoke_key(&api_key.id, &admin.id).await.is_err());
+}
+
+#[tokio::test]
+async fn test_expired_api_keys_are_rejected() {
+    use edufy::api_keys::ApiKeyService;
+
+    let db = setup_test_db().await;
+    let (_, key, api_key) = admin_api_key(&db, &["media:upload"]).await;
+
+    sqlx::query("UPDATE api_keys SET expires_at = ? WHERE id = ?")
+        .bind(chrono::Utc::now() - chrono::Duration::minutes(1))
+        .bind(&api_key.id)
+        .execute(&db)
+        .await
+        .unwrap();
+    assert!(ApiKeyService::new(db.clone()).authenticate(&key).await.is_err());
+}
+
+
+const KEY_FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+/// Config with an HS256 key read from `secret_path`, an RS256 key and an
+/// EdDSA key, signing with `active_kid`
+fn rotated_key_config(secret_path: &std::path::Path, active_kid: &str) -> AppConfig {
+    let mut config = test_config();
+    config.jwt_keys = vec![
+        format!("2026-04:HS256:{}", secret_path.display()),
+        format!("2026-10:RS256:{}/oauth_test_key.pem", KEY_FIXTURES),
+        format!("2027-04:EdDSA:{}/jwt_ed25519_key.pem", KEY_FIXTURES),
+    ];
+    config.jwt_active_kid = active_kid.to_string();
+    config
+}
+
+/// A file holding the shared HS256 secret
+fn hs256_secret_file(dir: &tempfile::TempDir) -> std::path::PathBuf {
+    let secret_path = dir.path().join("hs256.key");
+    std::fs::write(&secret_path, "old-shared-secret\n").unwrap();
+    secret_path
+}
+
+async fn keyring_test_user(db: &SqlitePool) -> User {
+    AuthService::new(db.clone(), test_config())
+        .create_user_with_google("keys@example.com".to_string(), "google_keys".to_string(), None)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_tokens_from_before_the_keyring_still_verify() {
+    let db = setup_test_db().await;
+    let user = keyring_test_user(&db).await;
+
+    // No kid, signed with the configured secret
+    let legacy_token = jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &serde_json::json!({
+            "sub": user.id,
+            "exp": chrono::Utc::now().timestamp() + 600,
+            "iat": chrono::Utc::now().timestamp(),
+            "jti": "legacy-jti",
+        }),
+        &jsonwebtoken::EncodingKey::from_secret(b"test-secret-key"),
+    )
+    .unwrap();
+    let auth_service = AuthService::new(db.clone(), test_config());
+    assert_eq!(auth_service.verify_jwt_token(&legacy_token).await.unwrap().sub, user.id);
+}
+
+#[tokio::test]
+async fn test_rotating_keys_signs_with_the_active_kid_and_verifies_older_ones() {
+    let db = setup_test_db().await;
+    let dir = tempdir().unwrap();
+    let secret_path = hs256_secret_file(&dir);
+    let user = keyring_test_user(&db).await;
+
+    let hs_token = AuthService::new(db.clone(), rotated_key_config(&secret_path, "2026-04"))
+        .create_jwt_token(&user.id)
+        .await
+        .unwrap();
+    assert_eq!(
+        jsonwebtoken::decode_header(&hs_token).unwrap().kid.as_deref(),
+        Some("2026-04")
+    );
+
+    let rotated = AuthService::new(db.clone(), rotated_key_config(&secret_path, "2026-10"));
+    let rs_token = rotated.create_jwt_token(&user.id).await.unwrap();
+    let header = jsonwebtoken::decode_header(&rs_token).unwrap();
+    assert_eq!(header.alg, jsonwebtoken::Algorithm::RS256);
+    assert_eq!(rotated.verify_jwt_token(&rs_token).await.unwrap().sub, user.id);
+    assert_eq!(rotated.verify_jwt_token(&hs_token).await.unwrap().sub, user.id);
+
+    let ed_token = AuthService::new(db.clone(), rotated_key_config(&secret_path, "2027-04"))
+        .create_jwt_token(&user.id)
+        .await
+        .unwrap();
+    assert_eq!(
+        jsonwebtoken::decode_header(&ed_token).unwrap().alg,
+        jsonwebtoken::Algorithm::EdDSA
+    );
+}
+
+#[tokio::test]
+async fn test_jwks_publishes_the_asymmetric_keys_only() {
+    use edufy::keyring::Keyring;
+
+    let db = setup_test_db().await;
+    let dir = tempdir().unwrap();
+    let config = rotated_key_config(&hs256_secret_file(&dir), "2027-04");
+    let user = keyring_test_user(&db).await;
+    let ed_token = AuthService::new(db.clone(), config.clone())
+        .create_jwt_token(&user.id)
+        .await
+        .unwrap();
+
+    let jwks = Keyring::for_config(&config).unwrap().jwks();
+    let kids: Vec<_> = jwks.keys.iter().filter_map(|k| k.common.key_id.clone()).collect();
+    assert_eq!(kids, vec!["2026-10", "2027-04"]);
+    let rs_jwk = jwks.find("2026-10").unwrap();
+    match &rs_jwk.algorithm {
+        jsonwebtoken::jwk::AlgorithmParameters::RSA(params) => assert_eq!(params.n, TEST_RSA_N),
+        _ => panic!("expected an RSA key"),
+    }
+
+    // The published key verifies our tokens
+    let mut validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::EdDSA);
+    validation.validate_exp = true;
+    let ed_jwk = jsonwebtoken::DecodingKey::from_jwk(jwks.find("2027-04").unwrap()).unwrap();
+    assert!(jsonwebtoken::decode::<serde_json::Value>(&ed_token, &ed_jwk, &validation).is_ok());
+}
+
+#[tokio::test]
+async fn test_retired_keys_stop_verifying_past_not_after() {
+    let db = setup_test_db().await;
+    let dir = tempdir().unwrap();
+    let secret_path = hs256_secret_file(&dir);
+    let user = keyring_test_user(&db).await;
+    let hs_token = AuthService::new(db.clone(), rotated_key_config(&secret_path, "2026-04"))
+        .create_jwt_token(&user.id)
+        .await
+        .unwrap();
+    let rs_token = AuthService::new(db.clone(), rotated_key_config(&secret_path, "2026-10"))
+        .create_jwt_token(&user.id)
+        .await
+        .unwrap();
+
+    let mut config = rotated_key_config(&secret_path, "2026-10");
+    config.jwt_keys[0] = format!(
+        "2026-04:HS256:{}:{}",
+        secret_path.display(),
+        chrono::Utc::now().timestamp() - 1
+    );
+    let expired = AuthService::new(db.clone(), config);
+    assert!(expired.verify_jwt_token(&hs_token).await.is_err());
+    assert!(expired.verify_jwt_token(&rs_token).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_tokens_naming_an_asymmetric_key_but_signed_with_a_secret_are_rejected() {
+    let db = setup_test_db().await;
+    let dir = tempdir().unwrap();
+    let config = rotated_key_config(&hs256_secret_file(&dir), "2026-10");
+    let auth_service = AuthService::new(db.clone(), config);
+    let user = keyring_test_user(&db).await;
+
+    let mut forged_header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS256);
+    forged_header.kid = Some("2026-10".to_string());
+    let forged = jsonwebtoken::encode(
+        &forged_header,
+        &serde_json::json!({ "sub": user.id, "exp": chrono::Utc::now().timestamp() + 600 }),
+        &jsonwebtoken::EncodingKey::from_secret(b"old-shared-secret"),
+    )
+    .unwrap();
+    assert!(auth_service.verify_jwt_token(&forged).await.is_err());
+}
+
+#[tokio::test]
+async fn test_keyring_misconfiguration_is_caught_when_built() {
+    use edufy::keyring::Keyring;
+
+    let dir = tempdir().unwrap();
+    let secret_path = hs256_secret_file(&dir);
+
+    let mut config = rotated_key_config(&secret_path, "missing");
+    assert!(Keyring::from_config(&config).is_err());
+
+    // An active key past its not_after
+    config.jwt_keys[0] = format!(
+        "2026-04:HS256:{}:{}",
+        secret_path.display(),
+        chrono::Utc::now().timestamp() - 1
+    );
+    config.jwt_active_kid = "2026-04".to_string();
+    assert!(Keyring::from_config(&config).is_err());
+
+    // A key file that doesn't match the algorithm
+    config.jwt_keys = vec![format!("k:ES256:{}/jwt_ed25519_key.pem", KEY_FIXTURES)];
+    config.jwt_active_kid = "k".to_string();
+    assert!(Keyring::from_config(&config).is_err());
+}
+
+#[tokio::test]
+async fn test_cached_keyring_refuses_to_sign_once_its_active_key_expires() {
+    use edufy::keyring::Keyring;
+
+    let dir = tempdir().unwrap();
+    let secret_path = hs256_secret_file(&dir);
+
+    // Rather than issue tokens nobody can verify
+    let mut expiring = test_config();
+    expiring.jwt_keys = vec![format!(
+        "soon:HS256:{}:{}",
+        secret_path.display(),
+        chrono::Utc::now().timestamp() + 1
+    )];
+    expiring.jwt_active_kid = "soon".to_string();
+    let keyring = Keyring::for_config(&expiring).unwrap();
+    assert!(keyring.encode(&serde_json::json!({ "sub": "user-1" })).is_ok());
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let err = keyring.encode(&serde_json::json!({ "sub": "user-1" })).unwrap_err();
+    assert!(err.to_string().contains("rotate jwt_active_kid"));
+}
+
+
+/// A signed-in user for the CSRF tests
+async fn csrf_login(db: &SqlitePool) -> (AuthService, User, edufy::models::LoginResponse) {
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let user = auth_service
+        .create_user_with_google("csrf@example.com".to_string(), "google_csrf".to_string(), None)
+        .await
+        .unwrap();
+    let (login, _) = auth_service.create_login_session(user.clone()).await.unwrap();
+    (auth_service, user, login)
+}
+
+/// A `/posts` route behind the auth middleware that reads on GET and
+/// writes on POST
+fn csrf_protected_app(db: &SqlitePool, kv_dir: &tempfile::TempDir) -> axum::Router {
+    use axum::{middleware, routing::get, Router};
+    use edufy::middleware::auth_middleware;
+
+    let kv = edufy::kv::KvStore::new(kv_dir.path().to_str().unwrap()).unwrap();
+    let state = edufy::AppState::new(db.clone(), test_config(), kv).unwrap();
+    Router::new()
+        .route("/posts", get(|| async { "read" }).post(|| async { "written" }))
+        .layer(middleware::from_fn_with_state(state, auth_middleware))
+}
+
+async fn send_with_csrf(
+    app: &axum::Router,
+    method: &str,
+    uri: &str,
+    auth: (&str, String),
+    csrf: Option<&str>,
+) -> axum::http::StatusCode {
+    use tower::ServiceExt;
+
+    let mut request = axum::http::Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(auth.0, auth.1);
+    if let Some(csrf) = csrf {
+        request = request.header(edufy::auth::CSRF_HEADER, csrf);
+    }
+    let request = request.body(axum::body::Body::empty()).unwrap();
+    app.clone().oneshot(request).await.unwrap().status()
+}
+
+fn session_cookie_header(
+    auth_service: &AuthService,
+    login: &edufy::models::LoginResponse,
+) -> (&'static str, String) {
+    let cookies = auth_service.cookie_policy().unwrap();
+    ("cookie", format!("{}={}", cookies.session_name(), login.token))
+}
+
+#[tokio::test]
+async fn test_csrf_tokens_are_bound_to_their_access_token() {
+    let db = setup_test_db().await;
+    let (auth_service, _, login) = csrf_login(&db).await;
+    let claims = auth_service.verify_jwt_token(&login.token).await.unwrap();
+
+    assert_eq!(login.csrf_token, auth_service.csrf_token(&claims.jti));
+    assert!(auth_service.verify_csrf_token(&claims.jti, &login.csrf_token).is_ok());
+    assert!(matches!(
+        auth_service.verify_csrf_token("other-jti", &login.csrf_token),
+        Err(AppError::Csrf(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_cookie_reads_and_echoed_writes_pass_csrf() {
+    use axum::http::StatusCode;
+
+    let db = setup_test_db().await;
+    let kv_dir = tempdir().unwrap();
+    let (auth_service, _, login) = csrf_login(&db).await;
+    let app = csrf_protected_app(&db, &kv_dir);
+    let cookie = || session_cookie_header(&auth_service, &login);
+
+    assert_eq!(send_with_csrf(&app, "GET", "/posts", cookie(), None).await, StatusCode::OK);
+    assert_eq!(
+        send_with_csrf(&app, "POST", "/posts", cookie(), Some(&login.csrf_token)).await,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_cookie_writes_without_a_valid_csrf_token_are_forbidden() {
+    use axum::http::StatusCode;
+
+    let db = setup_test_db().await;
+    let kv_dir = tempdir().unwrap();
+    let (auth_service, user, login) = csrf_login(&db).await;
+    let app = csrf_protected_app(&db, &kv_dir);
+    let cookie = || session_cookie_header(&auth_service, &login);
+
+    assert_eq!(
+        send_with_csrf(&app, "POST", "/posts", cookie(), None).await,
+        StatusCode::FORBIDDEN
+    );
+    // Nor does the token of another login pass
+    let (other_login, _) = auth_service.create_login_session(user).await.unwrap();
+    assert_eq!(
+        send_with_csrf(&app, "POST", "/posts", cookie(), Some(&other_login.csrf_token)).await,
+        StatusCode::FORBIDDEN
+    );
+}
+
+#[tokio::test]
+async fn test_bearer_writes_need_no_csrf_token() {
+    let db = setup_test_db().await;
+    let kv_dir = tempdir().unwrap();
+    let (_, _, login) = csrf_login(&db).await;
+    let app = csrf_protected_app(&db, &kv_dir);
+
+    // A cross-site form cannot send an Authorization header
+    let bearer = ("authorization", format!("Bearer {}", login.token));
+    assert_eq!(
+        send_with_csrf(&app, "POST", "/posts", bearer, None).await,
+        axum::http::StatusCode::OK
+    );
+}
+
+/// The refresh and CSRF cookies a browser holds for `login`
+fn refresh_cookie_header(
+    auth_service: &AuthService,
+    login: &edufy::models::LoginResponse,
+) -> (&'static str, String) {
+    let cookies = auth_service.cookie_policy().unwrap();
+    (
+        "cookie",
+        format!(
+            "{}={}; {}={}",
+            cookies.refresh_name(),
+            login.refresh_token,
+            cookies.csrf_name(),
+            login.csrf_token
+        ),
+    )
+}
+
+#[tokio::test]
+async fn test_cookie_refresh_and_logout_without_the_csrf_echo_are_forbidden() {
+    use axum::http::StatusCode;
+
+    let db = setup_test_db().await;
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let app = edufy::handlers::create_router(edufy::AppState::new(db.clone(), test_config(), kv).unwrap());
+    let (auth_service, _, login) = csrf_login(&db).await;
+    let cookies = || refresh_cookie_header(&auth_service, &login);
+
+    // A cross-site form sends the cookies but cannot echo the token
+    assert_eq!(
+        send_with_csrf(&app, "POST", "/api/auth/refresh", cookies(), None).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        send_with_csrf(&app, "POST", "/api/auth/refresh", cookies(), Some("forged")).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        send_with_csrf(&app, "POST", "/api/auth/logout", cookies(), None).await,
+        StatusCode::FORBIDDEN
+    );
+
+    // The refresh token was left alone and still works with the echo
+    assert_eq!(
+        send_with_csrf(&app, "POST", "/api/auth/refresh", cookies(), Some(&login.csrf_token)).await,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_refresh_tokens_posted_in_the_body_need_no_csrf_token() {
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use tower::ServiceExt;
+
+    let db = setup_test_db().await;
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let app = edufy::handlers::create_router(edufy::AppState::new(db.clone(), test_config(), kv).unwrap());
+    let (_, _, login) = csrf_login(&db).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/auth/refresh")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "refresh_token": login.refresh_token }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+
+/// Staging on its own domain with a Strict, hour-long session cookie
+fn staging_cookie_config() -> AppConfig {
+    let mut config = test_config();
+    config.environment = "staging".to_string();
+    config.session_cookie_name = "lla_staging".to_string();
+    config.cookie_domain = Some("staging.example.org".to_string());
+    config.cookie_same_site = Some("strict".to_string());
+    config.access_token_ttl_minutes = 60;
+    config
+}
+
+#[test]
+fn test_development_cookies_are_host_only_and_lax() {
+    use edufy::cookies::CookiePolicy;
+
+    let mut config = test_config();
+    config.environment = "development".to_string();
+    let dev = CookiePolicy::from_config(&config).unwrap();
+    assert_eq!(
+        dev.session_cookie("tok"),
+        "session=tok; Path=/; HttpOnly; SameSite=Lax; Max-Age=900"
+    );
+}
+
+#[test]
+fn test_cookies_take_their_name_domain_and_same_site_from_config() {
+    use edufy::cookies::CookiePolicy;
+
+    let staging = CookiePolicy::from_config(&staging_cookie_config()).unwrap();
+    assert_eq!(
+        staging.session_cookie("tok"),
+        "lla_staging=tok; Domain=staging.example.org; Path=/; Secure; HttpOnly; SameSite=Strict; Max-Age=3600"
+    );
+    assert_eq!(
+        staging.clear_refresh_cookie(),
+        "refresh_token=; Domain=staging.example.org; Path=/api/auth; Secure; HttpOnly; SameSite=Strict; Max-Age=0"
+    );
+}
+
+#[tokio::test]
+async fn test_sessions_expire_with_their_cookie() {
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), staging_cookie_config());
+    let user = auth_service
+        .create_user_with_google("cookie@example.com".to_string(), "google_cookie".to_string(), None)
+        .await
+        .unwrap();
+
+    // The JWT and the login response expire with the cookie
+    let (login, cookie) = auth_service.create_login_session(user).await.unwrap();
+    let claims = auth_service.verify_jwt_token(&login.token).await.unwrap();
+    assert_eq!(login.expires_at, claims.exp);
+    assert_eq!(claims.exp - claims.iat, 3600);
+    assert!(cookie.starts_with("lla_staging=") && cookie.ends_with("Max-Age=3600"));
+}
+
+#[test]
+fn test_host_prefixed_cookies_drop_the_domain() {
+    use edufy::cookies::CookiePolicy;
+
+    let mut config = staging_cookie_config();
+    config.cookie_domain = None;
+    config.cookie_host_prefix = true;
+    let host = CookiePolicy::from_config(&config).unwrap();
+    assert_eq!(host.session_name(), "__Host-lla_staging");
+    assert_eq!(host.csrf_name(), "__Host-csrf_token");
+    // The refresh cookie's path rules out __Host-, so it falls back to __Secure-
+    assert!(host.refresh_cookie("r").starts_with("__Secure-refresh_token=r; Path=/api/auth; Secure"));
+    assert!(!host.session_cookie("tok").contains("Domain="));
+}
+
+#[test]
+fn test_cookie_settings_browsers_would_drop_are_rejected() {
+    use edufy::cookies::CookiePolicy;
+
+    let mut host = staging_cookie_config();
+    host.cookie_host_prefix = true;
+    assert!(CookiePolicy::from_config(&host).is_err()); // __Host- forbids a Domain
+    host.cookie_domain = None;
+    host.cookie_path = "/portal".to_string();
+    assert!(CookiePolicy::from_config(&host).is_err());
+
+    let mut bad = test_config();
+    bad.cookie_secure = Some(false);
+    assert!(CookiePolicy::from_config(&bad).is_err()); // SameSite=None needs Secure
+    bad.cookie_same_site = Some("sometimes".to_string());
+    assert!(CookiePolicy::from_config(&bad).is_err());
+}
+
+
+/// Bursts of 3 per IP and 2 per identity, locking out for two minutes,
+/// with proxies in 10.0.0.0/8 trusted
+fn rate_limit_config() -> AppConfig {
+    let mut config = test_config();
+    config.auth_rate_limit_ip_burst = 3;
+    config.auth_rate_limit_identity_burst = 2;
+    config.auth_lockout_seconds = 120;
+    config.trusted_proxies = vec!["10.0.0.0/8".to_string()];
+    config
+}
+
+/// A stub login route behind the rate limit layer
+fn rate_limited_login_app(db: &SqlitePool, kv_dir: &tempfile::TempDir) -> axum::Router {
+    use axum::{middleware, routing::post, Router};
+    use edufy::middleware::rate_limit_middleware;
+
+    let kv = edufy::kv::KvStore::new(kv_dir.path().to_str().unwrap()).unwrap();
+    let state = edufy::AppState::new(db.clone(), rate_limit_config(), kv).unwrap();
+    Router::new()
+        .route("/api/auth/login", post(|| async { "ok" }))
+        .route_layer(middleware::from_fn_with_state(state, rate_limit_middleware))
+}
+
+/// Send a login from TCP peer `peer`, optionally with an X-Forwarded-For
+async fn send_login_from(
+    app: &axum::Router,
+    peer: &str,
+    forwarded_for: Option<&str>,
+) -> axum::response::Response {
+    use axum::{body::Body, extract::ConnectInfo, http::Request};
+    use std::net::SocketAddr;
+    use tower::ServiceExt;
+
+    let mut request = Request::post("/api/auth/login")
+        .extension(ConnectInfo(SocketAddr::new(peer.parse().unwrap(), 40000)));
+    if let Some(forwarded_for) = forwarded_for {
+        request = request.header("x-forwarded-for", forwarded_for);
+    }
+    app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_ip_over_its_burst_gets_429_with_retry_after() {
+    use axum::http::{header, StatusCode};
+
+    let db = setup_test_db().await;
+    let kv_dir = tempdir().unwrap();
+    let app = rate_limited_login_app(&db, &kv_dir);
+
+    // Counters are process-wide in memory, so each test uses its own addresses
+    for _ in 0..3 {
+        assert_eq!(send_login_from(&app, "198.51.100.7", None).await.status(), StatusCode::OK);
+    }
+    let limited = send_login_from(&app, "198.51.100.7", None).await;
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = limited.headers()[header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+    assert!(retry_after > 60 && retry_after <= 120);
+
+    // Other clients are unaffected
+    assert_eq!(send_login_from(&app, "198.51.100.8", None).await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_untrusted_clients_cannot_dodge_the_ip_limit_with_forwarded_headers() {
+    use axum::http::StatusCode;
+
+    let db = setup_test_db().await;
+    let kv_dir = tempdir().unwrap();
+    let app = rate_limited_login_app(&db, &kv_dir);
+
+    for _ in 0..3 {
+        send_login_from(&app, "198.51.100.17", None).await;
+    }
+    assert_eq!(
+        send_login_from(&app, "198.51.100.17", Some("203.0.113.1")).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+}
+
+#[tokio::test]
+async fn test_forwarded_client_is_counted_behind_a_trusted_proxy() {
+    use axum::http::StatusCode;
+
+    let db = setup_test_db().await;
+    let kv_dir = tempdir().unwrap();
+    let app = rate_limited_login_app(&db, &kv_dir);
+
+    // Read from the right, so entries the client prepended are skipped
+    for _ in 0..3 {
+        let response = send_login_from(&app, "10.0.0.2", Some("198.51.100.7, 192.0.2.9, 10.0.0.3")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    assert_eq!(
+        send_login_from(&app, "10.0.0.2", Some("192.0.2.9")).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+    assert_eq!(
+        send_login_from(&app, "10.0.0.2", Some("192.0.2.10")).await.status(),
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_session_ips_only_believe_trusted_proxies() {
+    use edufy::middleware::{client_info, TrustedProxies};
+
+    let config = rate_limit_config();
+    let mut headers = axum::http::HeaderMap::new();
+    headers.insert("x-forwarded-for", "192.0.2.50".parse().unwrap());
+
+    let untrusted = client_info(&config, Some("198.51.100.20".parse().unwrap()), &headers);
+    assert_eq!(untrusted.ip_address.as_deref(), Some("198.51.100.20"));
+    let proxied = client_info(&config, Some("10.1.2.3".parse().unwrap()), &headers);
+    assert_eq!(proxied.ip_address.as_deref(), Some("192.0.2.50"));
+
+    let mut bad_config = config;
+    bad_config.trusted_proxies = vec!["10.0.0.0/33".to_string()];
+    assert!(TrustedProxies::from_config(&bad_config).is_err());
+}
+
+#[tokio::test]
+async fn test_identity_limits_are_case_insensitive_on_both_backends() {
+    use edufy::rate_limit::AuthRateLimiter;
+
+    let db = setup_test_db().await;
+    let mut config = rate_limit_config();
+    for backend in ["memory", "sqlite"] {
+        config.rate_limit_backend = backend.to_string();
+        let limiter = AuthRateLimiter::new(db.clone(), config.clone()).unwrap();
+        let email = format!("{}@example.com", backend);
+        assert!(limiter.check_identity(&email).await.is_ok());
+        assert!(limiter.check_identity(&email.to_uppercase()).await.is_ok());
+        assert!(matches!(
+            limiter.check_identity(&email).await,
+            Err(AppError::RateLimited(seconds)) if seconds > 0
+        ));
+        // Still locked out on the next attempt
+        assert!(limiter.check_identity(&email).await.is_err());
+        assert!(limiter.check_identity("other@example.com").await.is_ok());
+    }
+    let stored: i64 = sqlx::query_scalar("SELECT hits FROM rate_limits WHERE key = 'identity:sqlite@example.com'")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(stored, 4);
+}
+
+#[tokio::test]
+async fn test_unknown_rate_limit_backend_is_refused() {
+    use edufy::rate_limit::AuthRateLimiter;
+
+    let db = setup_test_db().await;
+    let mut config = rate_limit_config();
+    config.rate_limit_backend = "redis".to_string();
+    assert!(AuthRateLimiter::new(db, config).is_err());
+}
+
+#[tokio::test]
+async fn test_each_lockout_is_audited_once_under_the_system_user() {
+    use edufy::audit::SYSTEM_USER_ID;
+    use edufy::rate_limit::AuthRateLimiter;
+
+    let db = setup_test_db().await;
+    let kv_dir = tempdir().unwrap();
+    let app = rate_limited_login_app(&db, &kv_dir);
+    for _ in 0..5 {
+        send_login_from(&app, "198.51.100.27", None).await;
+    }
+    let limiter = AuthRateLimiter::new(db.clone(), rate_limit_config()).unwrap();
+    for _ in 0..4 {
+        let _ = limiter.check_identity("pupil@example.com").await;
+    }
+
+    let logs = AuditService::new(db.clone())
+        .get_user_audit_logs(
+            SYSTEM_USER_ID,
+            chrono::Utc::now() - chrono::Duration::hours(1),
+            chrono::Utc::now() + chrono::Duration::hours(1),
+        )
+        .await
+        .unwrap();
+    let locked_keys: Vec<_> = logs
+        .iter()
+        .filter(|log| log.action == "auth_lockout")
+        .filter_map(|log| log.resource_id.clone())
+        .collect();
+    assert_eq!(locked_keys, vec!["ip:198.51.100.27", "identity:pupil@example.com"]);
+}
+
+#[tokio::test]
+async fn test_token_endpoints_are_limited_per_token_whatever_the_ip() {
+    use axum::{body::Body, extract::ConnectInfo, http::Request, http::StatusCode};
+    use std::net::SocketAddr;
+    use tower::ServiceExt;
+
+    let db = setup_test_db().await;
+    let temp_dir = tempdir().unwrap();
+    let mut config = test_config();
+    config.auth_rate_limit_identity_burst = 2;
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let router = edufy::handlers::create_router(edufy::AppState::new(db.clone(), config, kv).unwrap());
+    for (uri, body) in [
+        ("/api/auth/refresh", serde_json::json!({ "refresh_token": "guessed" })),
+        ("/api/auth/login/verify", serde_json::json!({ "token": "guessed" })),
+    ] {
+        for attempt in 0..3 {
+            let status = router
+                .clone()
+                .oneshot(
+                    Request::post(uri)
+                        .header("content-type", "application/json")
+                        .extension(ConnectInfo(SocketAddr::new(
+                            format!("198.51.100.{}", 100 + attempt).parse().unwrap(),
+                            40000,
+                        )))
+                        .body(Body::from(body.to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap()
+                .status();
+            if attempt < 2 {
+                assert_ne!(status, StatusCode::TOO_MANY_REQUESTS);
+            } else {
+                assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+            }
+        }
+    }
+}
+
+
+/// An admin (as the session sees them) and a parent account to impersonate
+async fn admin_and_parent(db: &SqlitePool) -> (edufy::models::UserResponse, User) {
+    use edufy::models::UserResponse;
+
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let admin = auth_service
+        .create_user_with_google("head@example.com".to_string(), "google_head_imp".to_string(), None)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE users SET role = 'admin' WHERE id = ?")
+        .bind(&admin.id)
+        .execute(db)
+        .await
+        .unwrap();
+    let parent = auth_service
+        .create_user_with_google("parent@example.com".to_string(), "google_parent".to_string(), None)
+        .await
+        .unwrap();
+    let admin = UserResponse {
+        id: admin.id,
+        email: admin.email,
+        role: "admin".to_string(),
+        full_name: None,
+        permissions: None,
+    };
+    (admin, parent)
+}
+
+#[tokio::test]
+async fn test_admins_cannot_impersonate_themselves_or_other_admins() {
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let (admin, _) = admin_and_parent(&db).await;
+
+    assert!(auth_service.impersonate(&admin, &admin.id, None).await.is_err());
+    let other_admin = auth_service
+        .create_user_with_google("deputy@example.com".to_string(), "google_deputy".to_string(), None)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE users SET role = 'admin' WHERE id = ?")
+        .bind(&other_admin.id)
+        .execute(&db)
+        .await
+        .unwrap();
+    assert!(auth_service.impersonate(&admin, &other_admin.id, None).await.is_err());
+}
+
+#[tokio::test]
+async fn test_impersonation_tokens_name_the_admin_as_actor() {
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let (admin, parent) = admin_and_parent(&db).await;
+
+    let impersonation = auth_service
+        .impersonate(&admin, &parent.id, Some("Portal looks wrong".to_string()))
+        .await
+        .unwrap();
+    let claims = auth_service.verify_jwt_token(&impersonation.token).await.unwrap();
+    assert_eq!(claims.sub, parent.id);
+    assert_eq!(claims.act.as_ref().unwrap().sub, admin.id);
+    assert_eq!(claims.exp - claims.iat, 30 * 60);
+    assert_eq!(auth_service.resolve_actor(&claims).await.unwrap().unwrap().id, admin.id);
+}
+
+#[tokio::test]
+async fn test_impersonated_requests_are_read_only_and_audited() {
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use edufy::handlers::create_router;
+    use tower::ServiceExt;
+
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let (admin, parent) = admin_and_parent(&db).await;
+    let impersonation = auth_service
+        .impersonate(&admin, &parent.id, Some("Portal looks wrong".to_string()))
+        .await
+        .unwrap();
+
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let app = create_router(edufy::AppState::new(db.clone(), test_config(), kv).unwrap());
+    let send = |method: &str, uri: &str| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("authorization", format!("Bearer {}", impersonation.token))
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    // Reads run as the parent; writes are refused unless the route allows them
+    let me = send("GET", "/api/users/me").await.unwrap();
+    assert_eq!(me.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(me.into_body(), usize::MAX).await.unwrap();
+    let me: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(me["id"], parent.id);
+    assert_eq!(
+        send("DELETE", "/api/users/me/sessions").await.unwrap().status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let current = send("GET", "/api/users/me/impersonation").await.unwrap();
+    let body = axum::body::to_bytes(current.into_body(), usize::MAX).await.unwrap();
+    let current: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(current["actor"]["id"], admin.id);
+    assert_eq!(current["user"]["id"], parent.id);
+
+    assert_eq!(
+        send("DELETE", "/api/users/me/impersonation").await.unwrap().status(),
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(send("GET", "/api/users/me").await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+    // Everything is audited under the admin's id
+    let logs = AuditService::new(db.clone())
+        .get_user_audit_logs(
+            &admin.id,
+            chrono::Utc::now() - chrono::Duration::hours(1),
+            chrono::Utc::now() + chrono::Duration::hours(1),
+        )
+        .await
+        .unwrap();
+    let actions: Vec<_> = logs.iter().map(|log| log.action.as_str()).collect();
+    assert_eq!(
+        actions,
+        vec![
+            "impersonation_started",
+            "impersonated_request",
+            "impersonated_request",
+            "impersonated_request",
+            "impersonation_ended",
+            "impersonated_request",
+        ]
+    );
+    let refused = &logs[2].details.as_ref().unwrap();
+    assert_eq!(refused["method"], "DELETE");
+    assert_eq!(refused["status"], 403);
+}
+
+#[tokio::test]
+async fn test_deactivated_admins_impersonation_tokens_stop_working() {
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let (admin, parent) = admin_and_parent(&db).await;
+    let deputy = auth_service
+        .create_user_with_google("deputy@example.com".to_string(), "google_deputy".to_string(), None)
+        .await
+        .unwrap();
+
+    let impersonation = auth_service.impersonate(&admin, &parent.id, None).await.unwrap();
+    UserService::new(db.clone())
+        .deactivate_user(&admin.id, &deputy.id)
+        .await
+        .unwrap();
+    let claims = auth_service.verify_jwt_token(&impersonation.token).await.unwrap();
+    assert!(auth_service.resolve_actor(&claims).await.is_err());
+}
+
+
+/// An admin, two parents and two students the admin created
+struct Family {
+    admin: User,
+    mother: User,
+    father: User,
+    first: User,
+    second: User,
+}
+
+async fn create_family(db: &SqlitePool) -> Family {
+    use edufy::models::CreateUserRequest;
+
+    let user_service = UserService::new(db.clone());
+    let admin = AuthService::new(db.clone(), test_config())
+        .create_user_with_google("head@example.com".to_string(), "google_head".to_string(), None)
+        .await
+        .unwrap();
+    let mut members = Vec::new();
+    for (email, role, name) in [
+        ("mum@example.com", "parent", "Ngozi"),
+        ("dad@example.com", "parent", "Emeka"),
+        ("ada@example.com", "student", "Ada"),
+        ("obi@example.com", "student", "Obi"),
+    ] {
+        let request = CreateUserRequest {
+            email: email.to_string(),
+            role: role.to_string(),
+            full_name: Some(name.to_string()),
+        };
+        members.push(user_service.create_user(request, &admin.id).await.unwrap());
+    }
+    let [mother, father, first, second] = members.try_into().unwrap();
+    Family { admin, mother, father, first, second }
+}
+
+fn link_request(
+    guardian: &User,
+    student: &User,
+    relationship: &str,
+    primary: bool,
+) -> edufy::models::LinkGuardianRequest {
+    edufy::models::LinkGuardianRequest {
+        guardian_id: guardian.id.clone(),
+        student_id: student.id.clone(),
+        relationship: relationship.to_string(),
+        is_primary_contact: primary,
+    }
+}
+
+#[tokio::test]
+async fn test_guardian_links_need_a_parent_a_student_and_a_known_relationship() {
+    use edufy::guardians::GuardianService;
+
+    let db = setup_test_db().await;
+    let guardian_service = GuardianService::new(db.clone());
+    let Family { admin, mother, father, first, second } = create_family(&db).await;
+
+    assert!(matches!(
+        guardian_service.link(link_request(&first, &second, "sibling", false), &admin.id).await,
+        Err(AppError::Validation(_))
+    ));
+    assert!(matches!(
+        guardian_service.link(link_request(&mother, &father, "other", false), &admin.id).await,
+        Err(AppError::Validation(_))
+    ));
+    assert!(matches!(
+        guardian_service.link(link_request(&mother, &first, "aunt", false), &admin.id).await,
+        Err(AppError::Validation(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_parents_and_students_can_have_several_links() {
+    use edufy::guardians::GuardianService;
+
+    let db = setup_test_db().await;
+    let guardian_service = GuardianService::new(db.clone());
+    let Family { admin, mother, father, first, second } = create_family(&db).await;
+
+    for (guardian, student, relationship) in [
+        (&mother, &first, "mother"),
+        (&mother, &second, "mother"),
+        (&father, &first, "father"),
+    ] {
+        guardian_service
+            .link(link_request(guardian, student, relationship, false), &admin.id)
+            .await
+            .unwrap();
+    }
+    let children = guardian_service.children_of(&mother.id).await.unwrap();
+    let names: Vec<_> = children.iter().filter_map(|c| c.full_name.as_deref()).collect();
+    assert_eq!(names, vec!["Ada", "Obi"]);
+    assert_eq!(guardian_service.guardians_of(&first.id).await.unwrap().len(), 2);
+    assert!(guardian_service.children_of(&first.id).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_making_a_guardian_primary_takes_the_flag_from_the_other() {
+    use edufy::guardians::GuardianService;
+
+    let db = setup_test_db().await;
+    let guardian_service = GuardianService::new(db.clone());
+    let Family { admin, mother, father, first, .. } = create_family(&db).await;
+
+    guardian_service
+        .link(link_request(&mother, &first, "mother", true), &admin.id)
+        .await
+        .unwrap();
+    guardian_service
+        .link(link_request(&father, &first, "father", true), &admin.id)
+        .await
+        .unwrap();
+    let guardians = guardian_service.guardians_of(&first.id).await.unwrap();
+    assert_eq!(guardians.len(), 2);
+    assert_eq!(guardians[0].id, father.id);
+    assert!(guardians[0].is_primary_contact);
+    assert!(!guardians[1].is_primary_contact);
+}
+
+#[tokio::test]
+async fn test_guardian_links_and_unlinks_are_audited() {
+    use edufy::guardians::GuardianService;
+
+    let db = setup_test_db().await;
+    let guardian_service = GuardianService::new(db.clone());
+    let Family { admin, father, first, .. } = create_family(&db).await;
+
+    guardian_service
+        .link(link_request(&father, &first, "father", false), &admin.id)
+        .await
+        .unwrap();
+    guardian_service
+        .unlink(&father.id, &first.id, &admin.id)
+        .await
+        .unwrap();
+    assert!(matches!(
+        guardian_service.unlink(&father.id, &first.id, &admin.id).await,
+        Err(AppError::NotFound(_))
+    ));
+    assert!(guardian_service.guardians_of(&first.id).await.unwrap().is_empty());
+
+    let logs = AuditService::new(db.clone())
+        .get_user_audit_logs(
+            &admin.id,
+            chrono::Utc::now() - chrono::Duration::hours(1),
+            chrono::Utc::now() + chrono::Duration::hours(1),
+        )
+        .await
+        .unwrap();
+    let actions: Vec<&str> = logs
+        .iter()
+        .map(|log| log.action.as_str())
+        .filter(|action| action.contains("guardian"))
+        .collect();
+    assert_eq!(actions, vec!["link_guardian", "unlink_guardian"]);
+}
+
+#[tokio::test]
+async fn test_guardian_links_go_with_the_user() {
+    use edufy::guardians::GuardianService;
+
+    let db = setup_test_db().await;
+    let guardian_service = GuardianService::new(db.clone());
+    let Family { admin, mother, first, second, .. } = create_family(&db).await;
+
+    for student in [&first, &second] {
+        guardian_service
+            .link(link_request(&mother, student, "mother", false), &admin.id)
+            .await
+            .unwrap();
+    }
+    UserService::new(db.clone())
+        .delete_user(&second.id, &admin.id)
+        .await
+        .unwrap();
+    assert_eq!(guardian_service.children_of(&mother.id).await.unwrap().len(), 1);
+}
+
+
+/// 09:00 on the day the fixed-clock TOTP tests run at
+fn totp_test_now() -> chrono::DateTime<chrono::Utc> {
+    use chrono::TimeZone;
+    chrono::Utc.with_ymd_and_hms(2026, 10, 16, 9, 0, 0).unwrap()
+}
+
+fn totp_admin_config() -> AppConfig {
+    let mut config = test_config();
+    config.admin_totp_required = true;
+    config.signup_role_rules = vec!["head@example.com=admin".to_string()];
+    config
+}
+
+fn totp_at(
+    db: &SqlitePool,
+    config: &AppConfig,
+    time: chrono::DateTime<chrono::Utc>,
+) -> edufy::totp::TotpService {
+    use edufy::totp::{FixedClock, TotpService};
+    TotpService::new(db.clone(), config.clone()).with_clock(FixedClock(time))
+}
+
+fn expect_mfa_challenge(outcome: edufy::models::LoginOutcome) -> edufy::models::MfaChallengeResponse {
+    match outcome {
+        edufy::models::LoginOutcome::MfaRequired(challenge) => challenge,
+        edufy::models::LoginOutcome::Session(..) => panic!("expected a second-factor challenge"),
+    }
+}
+
+/// An admin who enrolled through the login challenge, with their secret,
+/// the session it gave them and their recovery codes
+async fn enrolled_totp_admin(
+    db: &SqlitePool,
+    config: &AppConfig,
+) -> (User, Vec<u8>, edufy::models::LoginResponse, Vec<String>) {
+    let admin = AuthService::new(db.clone(), config.clone())
+        .create_user_with_google("head@example.com".to_string(), "google_head".to_string(), None)
+        .await
+        .unwrap();
+    let (secret, session, recovery_codes) = enroll_at_login(db, config, &admin).await;
+    (admin, secret, session, recovery_codes)
+}
+
+/// Log in and enroll through the challenge
+async fn enroll_at_login(
+    db: &SqlitePool,
+    config: &AppConfig,
+    user: &User,
+) -> (Vec<u8>, edufy::models::LoginResponse, Vec<String>) {
+    use edufy::totp::{base32_decode, totp_code};
+
+    let auth_service = AuthService::new(db.clone(), config.clone());
+    let now = totp_test_now();
+    let challenge = expect_mfa_challenge(auth_service.begin_login(user.clone()).await.unwrap());
+    let enrollment = auth_service
+        .start_challenge_enrollment(&totp_at(db, config, now), &challenge.challenge_token)
+        .await
+        .unwrap();
+    let secret = base32_decode(&enrollment.secret).unwrap();
+    let (login, _) = auth_service
+        .complete_mfa_login(&totp_at(db, config, now), &challenge.challenge_token, &totp_code(&secret, now))
+        .await
+        .unwrap();
+    (secret, login.session, login.recovery_codes.unwrap())
+}
+
+#[test]
+fn test_totp_codes_match_the_rfc_vectors() {
+    use chrono::TimeZone;
+    use edufy::totp::totp_code;
+
+    // RFC 6238 SHA-1 test vectors, truncated to 6 digits
+    let rfc_secret = b"12345678901234567890";
+    assert_eq!(totp_code(rfc_secret, chrono::Utc.timestamp_opt(59, 0).unwrap()), "287082");
+    assert_eq!(
+        totp_code(rfc_secret, chrono::Utc.timestamp_opt(1_111_111_109, 0).unwrap()),
+        "081804"
+    );
+}
+
+#[tokio::test]
+async fn test_admins_enroll_in_totp_before_they_get_a_session() {
+    use edufy::totp::{base32_decode, totp_code};
+
+    let db = setup_test_db().await;
+    let config = totp_admin_config();
+    let auth_service = AuthService::new(db.clone(), config.clone());
+    let now = totp_test_now();
+    let admin = auth_service
+        .create_user_with_google("head@example.com".to_string(), "google_head".to_string(), None)
+        .await
+        .unwrap();
+    assert_eq!(admin.role, "admin");
+
+    let challenge = expect_mfa_challenge(auth_service.begin_login(admin.clone()).await.unwrap());
+    assert!(challenge.enrollment_required);
+    let enrollment = auth_service
+        .start_challenge_enrollment(&totp_at(&db, &config, now), &challenge.challenge_token)
+        .await
+        .unwrap();
+    assert!(enrollment
+        .provisioning_uri
+        .starts_with("otpauth://totp/LLA%20Portal:head@example.com?secret="));
+    let secret = base32_decode(&enrollment.secret).unwrap();
+    assert_eq!(secret.len(), 20);
+
+    // Codes are checked against the injected clock, one step of drift allowed
+    let stale = totp_code(&secret, now - chrono::Duration::minutes(5));
+    assert!(auth_service
+        .complete_mfa_login(&totp_at(&db, &config, now), &challenge.challenge_token, &stale)
+        .await
+        .is_err());
+    let drifted = totp_code(&secret, now - chrono::Duration::seconds(30));
+    let (login, cookie) = auth_service
+        .complete_mfa_login(&totp_at(&db, &config, now), &challenge.challenge_token, &drifted)
+        .await
+        .unwrap();
+    assert_eq!(login.session.user.id, admin.id);
+    assert!(cookie.starts_with("session="));
+    assert_eq!(login.recovery_codes.unwrap().len(), 10);
+
+    // Challenges are single-use
+    assert!(auth_service
+        .complete_mfa_login(&totp_at(&db, &config, now), &challenge.challenge_token, &totp_code(&secret, now))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_logins_from_before_totp_was_required_cannot_be_refreshed() {
+    let db = setup_test_db().await;
+    let config = totp_admin_config();
+    let auth_service = AuthService::new(db.clone(), config.clone());
+    let admin = auth_service
+        .create_user_with_google("head@example.com".to_string(), "google_head".to_string(), None)
+        .await
+        .unwrap();
+
+    let (early_login, _) = auth_service.create_login_session(admin).await.unwrap();
+    assert!(matches!(
+        auth_service.refresh_session(&early_login.refresh_token).await,
+        Err(AppError::Auth(_))
+    ));
+    // The whole login ended, not just the refresh
+    assert!(auth_service.verify_jwt_token(&early_login.token).await.is_err());
+}
+
+#[tokio::test]
+async fn test_logins_that_passed_totp_keep_refreshing() {
+    let db = setup_test_db().await;
+    let config = totp_admin_config();
+    let auth_service = AuthService::new(db.clone(), config.clone());
+    let (_, _, session, _) = enrolled_totp_admin(&db, &config).await;
+
+    let (refreshed, _) = auth_service.refresh_session(&session.refresh_token).await.unwrap();
+    assert!(auth_service.refresh_session(&refreshed.refresh_token).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_enrolling_at_login_ends_the_logins_without_totp() {
+    let db = setup_test_db().await;
+    let mut config = totp_admin_config();
+    config.admin_totp_required = false;
+    let auth_service = AuthService::new(db.clone(), config);
+    let admin = auth_service
+        .create_user_with_google("head@example.com".to_string(), "google_head".to_string(), None)
+        .await
+        .unwrap();
+    let (before, _) = auth_service.create_login_session(admin.clone()).await.unwrap();
+
+    // Requiring TOTP later makes the next login enroll
+    let config = totp_admin_config();
+    let auth_service = AuthService::new(db.clone(), config.clone());
+    let (_, after, _) = enroll_at_login(&db, &config, &admin).await;
+    assert!(auth_service.verify_jwt_token(&before.token).await.is_err());
+    assert!(auth_service.refresh_session(&before.refresh_token).await.is_err());
+    assert!(auth_service.refresh_session(&after.refresh_token).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_enrolled_users_need_a_fresh_totp_code() {
+    use edufy::totp::totp_code;
+
+    let db = setup_test_db().await;
+    let config = totp_admin_config();
+    let auth_service = AuthService::new(db.clone(), config.clone());
+    let (admin, secret, _, _) = enrolled_totp_admin(&db, &config).await;
+    let now = totp_test_now();
+
+    let challenge = expect_mfa_challenge(auth_service.begin_login(admin.clone()).await.unwrap());
+    assert!(!challenge.enrollment_required);
+    // The code used to enroll cannot be replayed
+    assert!(auth_service
+        .complete_mfa_login(&totp_at(&db, &config, now), &challenge.challenge_token, &totp_code(&secret, now))
+        .await
+        .is_err());
+    let later = now + chrono::Duration::minutes(1);
+    assert!(auth_service
+        .complete_mfa_login(&totp_at(&db, &config, later), &challenge.challenge_token, &totp_code(&secret, later))
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn test_recovery_codes_work_once_in_any_case() {
+    let db = setup_test_db().await;
+    let config = totp_admin_config();
+    let (admin, _, _, recovery_codes) = enrolled_totp_admin(&db, &config).await;
+    let totp = totp_at(&db, &config, totp_test_now());
+
+    let recovery = recovery_codes[0].replace('-', "").to_uppercase();
+    assert!(totp.verify(&admin.id, &recovery).await.is_ok());
+    assert!(totp.verify(&admin.id, &recovery_codes[0]).await.is_err());
+    let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM totp_recovery_codes WHERE code_hash = ?")
+        .bind(&recovery_codes[1])
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(stored, 0);
+}
+
+#[tokio::test]
+async fn test_mandatory_totp_cannot_be_switched_off() {
+    let db = setup_test_db().await;
+    let config = totp_admin_config();
+    let (admin, _, _, recovery_codes) = enrolled_totp_admin(&db, &config).await;
+    let totp = totp_at(&db, &config, totp_test_now());
+
+    assert!(matches!(
+        totp.disable(&admin.id, Some(UserRole::Admin), &recovery_codes[1]).await,
+        Err(AppError::Validation(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_too_many_wrong_totp_codes_void_the_challenge() {
+    use edufy::totp::totp_code;
+
+    let db = setup_test_db().await;
+    let config = totp_admin_config();
+    let auth_service = AuthService::new(db.clone(), config.clone());
+    let (admin, secret, _, _) = enrolled_totp_admin(&db, &config).await;
+    let later = totp_test_now() + chrono::Duration::minutes(1);
+    let totp = totp_at(&db, &config, later);
+
+    let challenge = expect_mfa_challenge(auth_service.begin_login(admin).await.unwrap());
+    for _ in 0..5 {
+        assert!(auth_service
+            .complete_mfa_login(&totp, &challenge.challenge_token, "000000")
+            .await
+            .is_err());
+    }
+    assert!(auth_service
+        .complete_mfa_login(&totp, &challenge.challenge_token, &totp_code(&secret, later))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_users_without_totp_get_a_session_straight_away() {
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), totp_admin_config());
+    let pupil = auth_service
+        .create_user_with_google("pupil@example.com".to_string(), "google_pupil".to_string(), None)
+        .await
+        .unwrap();
+
+    let (session, _) = expect_session(auth_service.begin_login(pupil.clone()).await.unwrap());
+    assert_eq!(session.user.id, pupil.id);
+    assert!(auth_service.refresh_session(&session.refresh_token).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_turning_totp_on_ends_the_other_logins() {
+    use edufy::totp::{base32_decode, totp_code};
+
+    let db = setup_test_db().await;
+    let config = totp_admin_config();
+    let auth_service = AuthService::new(db.clone(), config.clone());
+    let now = totp_test_now();
+    let pupil = auth_service
+        .create_user_with_google("pupil@example.com".to_string(), "google_pupil".to_string(), None)
+        .await
+        .unwrap();
+    let (phone, _) = expect_session(auth_service.begin_login(pupil.clone()).await.unwrap());
+    let (laptop, _) = auth_service.create_login_session(pupil.clone()).await.unwrap();
+
+    // The login the code was entered in keeps refreshing
+    let totp = totp_at(&db, &config, now);
+    let enrollment = totp.start_enrollment(&pupil.id, &pupil.email).await.unwrap();
+    let pupil_secret = base32_decode(&enrollment.secret).unwrap();
+    totp.confirm_enrollment(&pupil.id, &totp_code(&pupil_secret, now))
+        .await
+        .unwrap();
+    let laptop_jti = auth_service.verify_jwt_token(&laptop.token).await.unwrap().jti;
+    auth_service
+        .end_sessions_without_mfa(&pupil.id, Some(&laptop_jti))
+        .await
+        .unwrap();
+    assert!(auth_service.refresh_session(&phone.refresh_token).await.is_err());
+    assert!(auth_service.verify_jwt_token(&phone.token).await.is_err());
+    assert!(auth_service.refresh_session(&laptop.refresh_token).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_refreshing_a_login_without_totp_fails_once_it_is_enabled() {
+    use edufy::totp::{base32_decode, totp_code};
+
+    let db = setup_test_db().await;
+    let config = totp_admin_config();
+    let auth_service = AuthService::new(db.clone(), config.clone());
+    let now = totp_test_now();
+    let pupil = auth_service
+        .create_user_with_google("pupil@example.com".to_string(), "google_pupil".to_string(), None)
+        .await
+        .unwrap();
+    let (phone, _) = expect_session(auth_service.begin_login(pupil.clone()).await.unwrap());
+
+    // Enabled without ending the other logins first
+    let totp = totp_at(&db, &config, now);
+    let enrollment = totp.start_enrollment(&pupil.id, &pupil.email).await.unwrap();
+    let pupil_secret = base32_decode(&enrollment.secret).unwrap();
+    totp.confirm_enrollment(&pupil.id, &totp_code(&pupil_secret, now))
+        .await
+        .unwrap();
+    assert!(matches!(
+        auth_service.refresh_session(&phone.refresh_token).await,
+        Err(AppError::Auth(_))
+    ));
+    assert!(auth_service.verify_jwt_token(&phone.token).await.is_err());
+}
+
+
+/// The session of a login that needed no second factor
+fn expect_session(outcome: edufy::models::LoginOutcome) -> (edufy::models::LoginResponse, String) {
+    match outcome {
+        edufy::models::LoginOutcome::Session(response, cookie) => (response, cookie),
+        edufy::models::LoginOutcome::MfaRequired(_) => panic!("expected a session"),
+    }
+}
+
+fn blog_post_request(title: &str, status: Option<&str>) -> CreateBlogPostRequest {
+    CreateBlogPostRequest {
+        title: title.to_string(),
+        summary: None,
+        body_html: "<p>Body</p>".to_string(),
+        tags: vec!["news".to_string()],
+        visibility: "public".to_string(),
+        status: status.map(str::to_string),
+        publish_at: None,
+        unpublish_at: None,
+        cover_image: None,
+        attachments: vec![],
+    }
+}
+
+/// A blog service over a fresh database and KV store, with a post author
+async fn blog_with_author() -> (BlogService, String, tempfile::TempDir) {
+    let db = setup_test_db().await;
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let blog_service = BlogService::new(kv, db.clone());
+    let author = AuthService::new(db, test_config())
+        .create_user_with_google("teacher@example.com".to_string(), "g-teacher".to_string(), None)
+        .await
+        .unwrap()
+        .id;
+    (blog_service, author, temp_dir)
+}
+
+/// "Open Day", scheduled an hour after `now` until three hours after it
+fn scheduled_post_request(now: chrono::DateTime<chrono::Utc>) -> CreateBlogPostRequest {
+    let mut request = blog_post_request("Open Day", Some("scheduled"));
+    request.publish_at = Some(now + chrono::Duration::hours(1));
+    request.unpublish_at = Some(now + chrono::Duration::hours(3));
+    request
+}
+
+#[tokio::test]
+async fn test_posts_without_a_status_publish_immediately() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+
+    let live = blog_service
+        .create_post(blog_post_request("Sports Day", None), author)
+        .await
+        .unwrap();
+    assert_eq!(live.status, "published");
+    assert!(blog_service.get_public_post(&live.slug).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_public_reads_only_see_live_posts() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    let now = chrono::Utc::now();
+    blog_service
+        .create_post(blog_post_request("Sports Day", None), author.clone())
+        .await
+        .unwrap();
+    let draft = blog_service
+        .create_post(blog_post_request("Draft Notes", Some("draft")), author.clone())
+        .await
+        .unwrap();
+    let scheduled = blog_service
+        .create_post(scheduled_post_request(now), author)
+        .await
+        .unwrap();
+    assert_eq!(scheduled.date_published, (now + chrono::Duration::hours(1)).to_rfc3339());
+
+    let public_slugs: Vec<_> = blog_service
+        .list_posts(false)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|post| post.slug)
+        .collect();
+    assert_eq!(public_slugs, vec!["sports-day"]);
+    assert_eq!(blog_service.list_posts(true).await.unwrap().len(), 3);
+    assert!(blog_service.get_public_post(&draft.slug).await.unwrap().is_none());
+    assert!(blog_service.get_public_post(&scheduled.slug).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_scheduled_posts_need_a_publish_time() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+
+    let result = blog_service
+        .create_post(blog_post_request("No Time", Some("scheduled")), author.clone())
+        .await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+
+    // Nor can a draft move to scheduled without one
+    let draft = blog_service
+        .create_post(blog_post_request("Draft Notes", Some("draft")), author.clone())
+        .await
+        .unwrap();
+    let result = blog_service
+        .update_post(&draft.slug, blog_post_request("Draft Notes", Some("scheduled")), author)
+        .await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+    assert_eq!(blog_service.get_post(&draft.slug).await.unwrap().unwrap().status, "draft");
+}
+
+#[tokio::test]
+async fn test_publish_windows_cannot_end_before_they_start() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    let now = chrono::Utc::now();
+
+    let mut request = blog_post_request("Backwards", Some("published"));
+    request.publish_at = Some(now);
+    request.unpublish_at = Some(now - chrono::Duration::hours(1));
+    let result = blog_service.create_post(request, author.clone()).await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+
+    // An update moving only the end is checked against the kept start
+    let scheduled = blog_service
+        .create_post(scheduled_post_request(now), author.clone())
+        .await
+        .unwrap();
+    let mut edit = blog_post_request("Open Day", None);
+    edit.unpublish_at = Some(now);
+    let result = blog_service.update_post(&scheduled.slug, edit, author).await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+}
+
+#[tokio::test]
+async fn test_updates_without_a_status_keep_the_stored_status_and_window() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    let scheduled = blog_service
+        .create_post(scheduled_post_request(chrono::Utc::now()), author.clone())
+        .await
+        .unwrap();
+    let draft = blog_service
+        .create_post(blog_post_request("Draft Notes", Some("draft")), author.clone())
+        .await
+        .unwrap();
+
+    let mut edit = blog_post_request("Open Day", None);
+    edit.body_html = "<p>Tours at ten</p>".to_string();
+    let edited = blog_service
+        .update_post(&scheduled.slug, edit, author.clone())
+        .await
+        .unwrap();
+    assert_eq!(edited.status, "scheduled");
+    assert_eq!(edited.publish_at, scheduled.publish_at);
+    assert_eq!(edited.unpublish_at, scheduled.unpublish_at);
+    assert_eq!(edited.date_published, scheduled.date_published);
+    let edited = blog_service
+        .update_post(&draft.slug, blog_post_request("Draft Notes", None), author)
+        .await
+        .unwrap();
+    assert_eq!(edited.status, "draft");
+}
+
+#[tokio::test]
+async fn test_unknown_post_statuses_are_rejected() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+
+    let result = blog_service
+        .create_post(blog_post_request("Bogus", Some("bogus")), author.clone())
+        .await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+    let draft = blog_service
+        .create_post(blog_post_request("Draft Notes", Some("draft")), author.clone())
+        .await
+        .unwrap();
+    let result = blog_service
+        .update_post(&draft.slug, blog_post_request("Draft Notes", Some("bogus")), author)
+        .await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+}
+
+#[tokio::test]
+async fn test_due_posts_go_live_and_expired_ones_are_archived() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    let now = chrono::Utc::now();
+    let scheduled = blog_service
+        .create_post(scheduled_post_request(now), author)
+        .await
+        .unwrap();
+
+    // Nothing is due yet
+    assert_eq!(blog_service.publish_due_posts(now).await.unwrap(), 0);
+
+    // Past publish_at the job takes the post live
+    let later = now + chrono::Duration::hours(2);
+    assert_eq!(blog_service.publish_due_posts(later).await.unwrap(), 1);
+    let post = blog_service.get_post(&scheduled.slug).await.unwrap().unwrap();
+    assert_eq!(post.status, "published");
+    assert!(post.is_live(later));
+    let index = blog_service.list_posts(true).await.unwrap();
+    let entry = index.iter().find(|entry| entry.slug == scheduled.slug).unwrap();
+    assert_eq!(entry.status, "published");
+
+    // Past unpublish_at it leaves the public listing and is archived
+    let expired = now + chrono::Duration::hours(4);
+    assert!(!entry.is_live(expired));
+    assert_eq!(blog_service.publish_due_posts(expired).await.unwrap(), 1);
+    let post = blog_service.get_post(&scheduled.slug).await.unwrap().unwrap();
+    assert_eq!(post.status, "archived");
+
+    let logs = AuditService::new(blog_service.db.clone())
+        .get_user_audit_logs(
+            "system",
+            chrono::Utc::now() - chrono::Duration::hours(1),
+            chrono::Utc::now() + chrono::Duration::hours(1),
+        )
+        .await
+        .unwrap();
+    assert!(logs.iter().any(|log| log.action == "publish_scheduled_post"));
+    assert!(logs.iter().any(|log| log.action == "archive_expired_post"));
+}
+
+
+/// "Sports Day", then edited to "Sports Day Results" and then to a bad body,
+/// so it has three revisions
+async fn post_edited_twice(blog_service: &BlogService, author: &str) -> edufy::kv::BlogPostKv {
+    let post = blog_service
+        .create_post(blog_post_request("Sports Day", None), author.to_string())
+        .await
+        .unwrap();
+    let mut edit = blog_post_request("Sports Day Results", None);
+    edit.body_html = "<p>Blue house won</p>".to_string();
+    blog_service
+        .update_post(&post.slug, edit, author.to_string())
+        .await
+        .unwrap();
+    let mut bad_edit = blog_post_request("Sports Day Results", None);
+    bad_edit.body_html = "<p>oops</p>".to_string();
+    blog_service
+        .update_post(&post.slug, bad_edit, author.to_string())
+        .await
+        .unwrap();
+    post
+}
+
+/// A post stored before revisions existed: "Prize Giving" by a former
+/// teacher, with no revisions of its own
+async fn untracked_post(blog_service: &BlogService, author: &str) -> edufy::kv::BlogPostKv {
+    let mut post = blog_service
+        .create_post(blog_post_request("Prize Giving", None), author.to_string())
+        .await
+        .unwrap();
+    sqlx::query("DELETE FROM post_revisions WHERE post_id = ?")
+        .bind(&post.id)
+        .execute(&blog_service.db)
+        .await
+        .unwrap();
+    post.author_id = "former-teacher".to_string();
+    post.body_html = "<p>Original</p>".to_string();
+    blog_service.kv.put_blog_post(&post.slug, &post).await.unwrap();
+    post
+}
+
+#[tokio::test]
+async fn test_every_post_save_records_a_revision() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    let post = post_edited_twice(&blog_service, &author).await;
+
+    let revisions = blog_service.list_revisions(&post.slug).await.unwrap();
+    assert_eq!(
+        revisions.iter().map(|r| r.revision).collect::<Vec<_>>(),
+        vec![3, 2, 1]
+    );
+    assert!(revisions.iter().all(|r| r.author_id == author));
+}
+
+#[tokio::test]
+async fn test_revision_diffs_only_show_changed_fields() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    let post = post_edited_twice(&blog_service, &author).await;
+
+    let diff = blog_service.diff_revisions(&post.slug, 1, 2).await.unwrap();
+    let fields: Vec<&str> = diff.changes.iter().map(|c| c.field.as_str()).collect();
+    assert_eq!(fields, vec!["title", "body_html"]);
+    assert_eq!(diff.changes[0].before, "Sports Day");
+    assert_eq!(diff.changes[0].after, "Sports Day Results");
+}
+
+#[tokio::test]
+async fn test_restoring_a_revision_writes_a_new_one() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    let post = post_edited_twice(&blog_service, &author).await;
+
+    let (restored, revision) = blog_service
+        .restore_revision(&post.slug, 2, author.clone())
+        .await
+        .unwrap();
+    assert_eq!(revision, 4);
+    assert_eq!(restored.body_html, "<p>Blue house won</p>");
+    let current = blog_service.get_post(&post.slug).await.unwrap().unwrap();
+    assert_eq!(current.body_html, "<p>Blue house won</p>");
+    let latest = blog_service.get_revision(&post.slug, 4).await.unwrap();
+    assert_eq!(latest.restored_from, Some(2));
+    assert!(blog_service.diff_revisions(&post.slug, 2, 4).await.unwrap().changes.is_empty());
+
+    let logs = AuditService::new(blog_service.db.clone())
+        .get_user_audit_logs(
+            &author,
+            chrono::Utc::now() - chrono::Duration::hours(1),
+            chrono::Utc::now() + chrono::Duration::hours(1),
+        )
+        .await
+        .unwrap();
+    let restore = logs
+        .iter()
+        .find(|log| log.action == "restore_blog_post_revision")
+        .unwrap();
+    assert_eq!(
+        restore.details,
+        Some(serde_json::json!({ "slug": "sports-day", "restored_from": 2, "revision": 4 }))
+    );
+}
+
+#[tokio::test]
+async fn test_unknown_revisions_are_not_found() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    let post = post_edited_twice(&blog_service, &author).await;
+
+    assert!(matches!(
+        blog_service.get_revision(&post.slug, 9).await,
+        Err(AppError::NotFound(_))
+    ));
+    assert!(matches!(
+        blog_service.restore_revision(&post.slug, 9, author).await,
+        Err(AppError::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_untracked_posts_keep_their_content_as_revision_1_when_edited() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    let post = untracked_post(&blog_service, &author).await;
+
+    let mut edit = blog_post_request("Prize Giving", None);
+    edit.body_html = "<p>Edited</p>".to_string();
+    blog_service
+        .update_post(&post.slug, edit, author.clone())
+        .await
+        .unwrap();
+    let original = blog_service.get_revision(&post.slug, 1).await.unwrap();
+    assert_eq!(original.author_id, "former-teacher");
+    let payload: edufy::kv::BlogPostKv = serde_json::from_str(&original.payload).unwrap();
+    assert_eq!(payload.body_html, "<p>Original</p>");
+    let revisions = blog_service.list_revisions(&post.slug).await.unwrap();
+    assert_eq!(
+        revisions.iter().map(|r| r.revision).collect::<Vec<_>>(),
+        vec![2, 1]
+    );
+    assert_eq!(revisions[0].author_id, author);
+}
+
+#[tokio::test]
+async fn test_untracked_posts_keep_their_content_as_revision_1_when_moved() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    let post = untracked_post(&blog_service, &author).await;
+
+    blog_service
+        .change_slug(&post.slug, "prize-day", author.clone())
+        .await
+        .unwrap();
+    let original = blog_service.get_revision("prize-day", 1).await.unwrap();
+    assert_eq!(original.slug, "prize-giving");
+    assert_eq!(original.author_id, "former-teacher");
+    let payload: edufy::kv::BlogPostKv = serde_json::from_str(&original.payload).unwrap();
+    assert_eq!(payload.body_html, "<p>Original</p>");
+    let moved = blog_service.get_revision("prize-day", 2).await.unwrap();
+    assert_eq!(moved.slug, "prize-day");
+}
+
+
+/// The public router over the blog service's database and KV store
+fn public_blog_app(blog_service: &BlogService) -> axum::Router {
+    let state = edufy::AppState::new(blog_service.db.clone(), test_config(), blog_service.kv.clone());
+    edufy::handlers::create_router(state.unwrap())
+}
+
+async fn get_response(app: &axum::Router, uri: &str) -> axum::response::Response {
+    use tower::ServiceExt;
+
+    let request = axum::http::Request::get(uri).body(axum::body::Body::empty()).unwrap();
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_slug_changes_refuse_invalid_slugs() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    blog_service
+        .create_post(blog_post_request("Sports Day", None), author.clone())
+        .await
+        .unwrap();
+
+    let result = blog_service.change_slug("sports-day", "Sports Day!", author).await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+    assert!(blog_service.get_post("sports-day").await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_slug_changes_refuse_a_slug_another_post_has() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    for title in ["Sports Day", "Open Day"] {
+        blog_service
+            .create_post(blog_post_request(title, None), author.clone())
+            .await
+            .unwrap();
+    }
+
+    let result = blog_service.change_slug("sports-day", "open-day", author).await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+    assert!(blog_service.get_post("sports-day").await.unwrap().is_some());
+    assert_eq!(blog_service.get_post("open-day").await.unwrap().unwrap().title, "Open Day");
+}
+
+#[tokio::test]
+async fn test_slug_changes_move_the_post_and_its_index_entry() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    let post = blog_service
+        .create_post(blog_post_request("Sports Day", None), author.clone())
+        .await
+        .unwrap();
+
+    let moved = blog_service
+        .change_slug("sports-day", "sports-day-2024", author)
+        .await
+        .unwrap();
+    assert_eq!(moved.id, post.id);
+    assert!(blog_service.get_post("sports-day").await.unwrap().is_none());
+    let slugs: Vec<String> = blog_service
+        .list_posts(true)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.slug)
+        .collect();
+    assert_eq!(slugs, vec!["sports-day-2024"]);
+}
+
+#[tokio::test]
+async fn test_old_slugs_stay_one_hop_from_the_current_slug() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    blog_service
+        .create_post(blog_post_request("Sports Day", None), author.clone())
+        .await
+        .unwrap();
+
+    blog_service
+        .change_slug("sports-day", "sports-day-2024", author.clone())
+        .await
+        .unwrap();
+    blog_service
+        .change_slug("sports-day-2024", "inter-house-sports", author)
+        .await
+        .unwrap();
+    for old in ["sports-day", "sports-day-2024"] {
+        assert_eq!(
+            blog_service.get_public_redirect(old).await.unwrap().as_deref(),
+            Some("inter-house-sports")
+        );
+    }
+    assert_eq!(blog_service.list_revisions("inter-house-sports").await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_old_slugs_answer_with_a_permanent_redirect() {
+    use axum::http::StatusCode;
+
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    blog_service
+        .create_post(blog_post_request("Sports Day", None), author.clone())
+        .await
+        .unwrap();
+    blog_service
+        .change_slug("sports-day", "inter-house-sports", author)
+        .await
+        .unwrap();
+    let app = public_blog_app(&blog_service);
+
+    for base_path in ["/api/blog/post", "/api/blog/public"] {
+        let response = get_response(&app, &format!("{base_path}/sports-day")).await;
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            response.headers()["location"],
+            format!("{base_path}/inter-house-sports").as_str()
+        );
+        // The blog page loader redirects the reader to the slug in the body
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["slug"], "inter-house-sports");
+
+        let response = get_response(&app, &format!("{base_path}/inter-house-sports")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn test_deleting_a_moved_post_drops_its_redirects() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    blog_service
+        .create_post(blog_post_request("Sports Day", None), author.clone())
+        .await
+        .unwrap();
+    blog_service
+        .change_slug("sports-day", "inter-house-sports", author.clone())
+        .await
+        .unwrap();
+
+    // Rather than pointing at a 404
+    blog_service
+        .delete_post("inter-house-sports", author)
+        .await
+        .unwrap();
+    assert!(blog_service.get_public_redirect("sports-day").await.unwrap().is_none());
+    let response = get_response(&public_blog_app(&blog_service), "/api/blog/post/sports-day").await;
+    assert_eq!(response.status(), axum::http::StatusCode::NOT_FOUND);
+}
+
+
+/// Record a slug change the way `change_slug` does before its KV writes
+async fn record_slug_move(db: &sqlx::SqlitePool, post_id: &str, old_slug: &str, new_slug: &str, author: &str) {
+    sqlx::query(
+        "INSERT INTO post_slug_moves (post_id, old_slug, new_slug, created_by, created_at) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(post_id)
+    .bind(old_slug)
+    .bind(new_slug)
+    .bind(author)
+    .bind(chrono::Utc::now())
+    .execute(db)
+    .await
+    .unwrap();
+}
+
+async fn pending_slug_moves(db: &sqlx::SqlitePool) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM post_slug_moves")
+        .fetch_one(db)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_retrying_an_interrupted_slug_change_finishes_it() {
+    let db = setup_test_db().await;
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let blog_service = BlogService::new(kv.clone(), db.clone());
+    let author = AuthService::new(db.clone(), test_config())
+        .create_user_with_google("teacher@example.com".to_string(), "g-teacher".to_string(), None)
+        .await
+        .unwrap()
+        .id;
+    let mut post = blog_service
+        .create_post(blog_post_request("Sports Day", None), author.clone())
+        .await
+        .unwrap();
+
+    // The KV move went through but the redirect was never written
+    record_slug_move(&db, &post.id, "sports-day", "sports-day-2024", &author).await;
+    post.slug = "sports-day-2024".to_string();
+    kv.rename_blog_post("sports-day", &post).await.unwrap();
+    assert!(blog_service.get_public_redirect("sports-day").await.unwrap().is_none());
+
+    let moved = blog_service
+        .change_slug("sports-day", "sports-day-2024", author.clone())
+        .await
+        .unwrap();
+    assert_eq!((moved.id.as_str(), moved.slug.as_str()), (post.id.as_str(), "sports-day-2024"));
+    assert_eq!(
+        blog_service.get_public_redirect("sports-day").await.unwrap().as_deref(),
+        Some("sports-day-2024")
+    );
+    assert_eq!(blog_service.list_revisions("sports-day-2024").await.unwrap().len(), 2);
+    assert_eq!(pending_slug_moves(&db).await, 0);
+
+    // Repeating a finished change finds nothing to move
+    let result = blog_service
+        .change_slug("sports-day", "sports-day-2024", author.clone())
+        .await;
+    assert!(matches!(result, Err(AppError::NotFound(_))));
+}
+
+#[tokio::test]
+async fn test_interrupted_slug_changes_are_finished_at_startup() {
+    let db = setup_test_db().await;
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let blog_service = BlogService::new(kv.clone(), db.clone());
+    let author = AuthService::new(db.clone(), test_config())
+        .create_user_with_google("teacher@example.com".to_string(), "g-teacher".to_string(), None)
+        .await
+        .unwrap()
+        .id;
+    let mut post = blog_service
+        .create_post(blog_post_request("Sports Day", None), author.clone())
+        .await
+        .unwrap();
+    let gone = blog_service
+        .create_post(blog_post_request("Open Day", None), author.clone())
+        .await
+        .unwrap();
+
+    // Only the new key was written before the failure
+    record_slug_move(&db, &post.id, "sports-day", "sports-day-2024", &author).await;
+    post.slug = "sports-day-2024".to_string();
+    kv.put("blog:post:sports-day-2024", &serde_json::to_string(&post).unwrap())
+        .await
+        .unwrap();
+
+    // A move whose post has since disappeared is dropped
+    record_slug_move(&db, &gone.id, "open-day", "open-day-2024", &author).await;
+    kv.delete_blog_post("open-day").await.unwrap();
+
+    assert_eq!(blog_service.finish_slug_moves().await.unwrap(), 1);
+    assert!(blog_service.get_post("sports-day").await.unwrap().is_none());
+    let slugs: Vec<String> = blog_service
+        .list_posts(true)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.slug)
+        .collect();
+    assert_eq!(slugs, vec!["sports-day-2024"]);
+    assert_eq!(
+        blog_service.get_public_redirect("sports-day").await.unwrap().as_deref(),
+        Some("sports-day-2024")
+    );
+    assert_eq!(pending_slug_moves(&db).await, 0);
+}
+
+#[tokio::test]
+async fn test_unfinished_slug_change_blocks_a_different_one() {
+    let db = setup_test_db().await;
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let blog_service = BlogService::new(kv, db.clone());
+    let author = AuthService::new(db.clone(), test_config())
+        .create_user_with_google("teacher@example.com".to_string(), "g-teacher".to_string(), None)
+        .await
+        .unwrap()
+        .id;
+    let post = blog_service
+        .create_post(blog_post_request("Sports Day", None), author.clone())
+        .await
+        .unwrap();
+
+    record_slug_move(&db, &post.id, "sports-day", "sports-day-2024", &author).await;
+    let result = blog_service
+        .change_slug("sports-day", "athletics", author.clone())
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+
+    // Deleting the post abandons the move along with any copy it left
+    blog_service.delete_post("sports-day", author.clone()).await.unwrap();
+    assert_eq!(pending_slug_moves(&db).await, 0);
+}
+
+#[tokio::test]
+async fn test_slug_change_keeps_other_posts_redirects() {
+    let db = setup_test_db().await;
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    let blog_service = BlogService::new(kv, db.clone());
+    let author = AuthService::new(db.clone(), test_config())
+        .create_user_with_google("teacher@example.com".to_string(), "g-teacher".to_string(), None)
+        .await
+        .unwrap()
+        .id;
+    let open_day = blog_service
+        .create_post(blog_post_request("Open Day", None), author.clone())
+        .await
+        .unwrap();
+    blog_service
+        .create_post(blog_post_request("Sports Day", None), author.clone())
+        .await
+        .unwrap();
+
+    // A redirect from before slugs were claimed, owned by another post
+    sqlx::query(
+        "INSERT INTO post_redirects (old_slug, new_slug, post_id, created_by, created_at) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind("open-day-2023")
+    .bind("open-day")
+    .bind(&open_day.id)
+    .bind(&author)
+    .bind(chrono::Utc::now())
+    .execute(&db)
+    .await
+    .unwrap();
+
+    blog_service
+        .change_slug("sports-day", "open-day-2023", author.clone())
+        .await
+        .unwrap();
+    blog_service.delete_post("open-day-2023", author.clone()).await.unwrap();
+    assert_eq!(
+        blog_service.get_public_redirect("open-day-2023").await.unwrap().as_deref(),
+        Some("open-day")
+    );
+}
+
+#[test]
+fn test_slugs_are_transliterated_and_trimmed() {
+    use edufy::slug::{slugify, MAX_SLUG_LENGTH};
+
+    assert_eq!(slugify("Prize-Giving Day — 2024!"), "prize-giving-day-2024");
+    assert_eq!(slugify("Crème brûlée à l'École"), "creme-brulee-a-l-ecole");
+    assert_eq!(slugify("  Straße   Fest  "), "strasse-fest");
+    assert_eq!(slugify("!!!"), "post");
+    let long = slugify(&"word ".repeat(40));
+    assert!(long.len() <= MAX_SLUG_LENGTH);
+    assert!(!long.ends_with('-'));
+}
+
+#[tokio::test]
+async fn test_colliding_titles_get_numbered_slugs() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+
+    // Instead of a conflict
+    let mut slugs = Vec::new();
+    for title in ["Prize-Giving Day — 2024!", "Prize Giving Day 2024", "prize giving day, 2024"] {
+        let post = blog_service
+            .create_post(blog_post_request(title, None), author.clone())
+            .await
+            .unwrap();
+        slugs.push(post.slug);
+    }
+    assert_eq!(
+        slugs,
+        vec!["prize-giving-day-2024", "prize-giving-day-2024-2", "prize-giving-day-2024-3"]
+    );
+}
+
+#[tokio::test]
+async fn test_old_slugs_of_moved_posts_are_not_reused() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    blog_service
+        .create_post(blog_post_request("Prize-Giving Day 2024", None), author.clone())
+        .await
+        .unwrap();
+
+    // The old slug redirects to the moved post
+    blog_service
+        .change_slug("prize-giving-day-2024", "prize-giving-2024", author.clone())
+        .await
+        .unwrap();
+    let post = blog_service
+        .create_post(blog_post_request("Prize-Giving Day 2024", None), author)
+        .await
+        .unwrap();
+    assert_eq!(post.slug, "prize-giving-day-2024-2");
+}
+
+#[tokio::test]
+async fn test_model_constructor_numbers_slugs_the_same_way() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    blog_service
+        .create_post(blog_post_request("Prize-Giving Day 2024", None), author.clone())
+        .await
+        .unwrap();
+
+    let mut request = blog_post_request("Prize-Giving Day — 2024!", None);
+    request.visibility = "private".to_string();
+    let post = blog_service
+        .create_post_with_model(request, author)
+        .await
+        .unwrap();
+    assert_eq!(post.slug, "prize-giving-day-2024-2");
+}
+
+#[tokio::test]
+async fn test_posts_created_at_once_get_different_slugs() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+
+    let (first, second) = tokio::join!(
+        blog_service.create_post(blog_post_request("Founders Day", None), author.clone()),
+        blog_service.create_post(blog_post_request("Founders Day", None), author.clone()),
+    );
+    let mut slugs = vec![first.unwrap().slug, second.unwrap().slug];
+    slugs.sort();
+    assert_eq!(slugs, vec!["founders-day", "founders-day-2"]);
+}
+
+#[tokio::test]
+async fn test_slugs_claimed_by_a_post_not_yet_saved_are_skipped() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+
+    // Another create claimed the slug but hasn't written its post to KV yet
+    sqlx::query("INSERT INTO post_slugs (slug, post_id) VALUES ('founders-day', 'post-in-flight')")
+        .execute(&blog_service.db)
+        .await
+        .unwrap();
+    let post = blog_service
+        .create_post(blog_post_request("Founders Day", None), author.clone())
+        .await
+        .unwrap();
+    assert_eq!(post.slug, "founders-day-2");
+    let result = blog_service
+        .change_slug(&post.slug, "founders-day", author)
+        .await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+}
+
+#[tokio::test]
+async fn test_slug_changes_cannot_take_another_posts_old_slug() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    blog_service
+        .create_post(blog_post_request("Sports Day", None), author.clone())
+        .await
+        .unwrap();
+    blog_service
+        .change_slug("sports-day", "sports-day-2024", author.clone())
+        .await
+        .unwrap();
+    blog_service
+        .create_post(blog_post_request("Open Day", None), author.clone())
+        .await
+        .unwrap();
+
+    let result = blog_service.change_slug("open-day", "sports-day", author).await;
+    assert!(matches!(result, Err(AppError::Conflict(_))));
+    assert!(blog_service.get_post("open-day").await.unwrap().is_some());
+    assert_eq!(
+        blog_service.get_public_redirect("sports-day").await.unwrap().as_deref(),
+        Some("sports-day-2024")
+    );
+}
+
+#[tokio::test]
+async fn test_hand_set_slugs_may_be_longer_than_generated_ones() {
+    use edufy::slug::{MAX_CUSTOM_SLUG_LENGTH, MAX_SLUG_LENGTH};
+
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+    let post = blog_service
+        .create_post(blog_post_request("Prize Giving", None), author.clone())
+        .await
+        .unwrap();
+
+    let custom = "prize-giving-".repeat(15) + "day-1";
+    assert!(custom.len() > MAX_SLUG_LENGTH);
+    assert_eq!(custom.len(), MAX_CUSTOM_SLUG_LENGTH);
+    blog_service
+        .change_slug(&post.slug, &custom, author.clone())
+        .await
+        .unwrap();
+    assert!(blog_service.get_post(&custom).await.unwrap().is_some());
+    let too_long = "a".repeat(MAX_CUSTOM_SLUG_LENGTH + 1);
+    let result = blog_service.change_slug(&custom, &too_long, author).await;
+    assert!(matches!(result, Err(AppError::Validation(_))));
+}
+
+
+/// Five live posts a day apart from `start` by two authors, plus a draft
+struct IndexedBlog {
+    blog_service: BlogService,
+    teacher: String,
+    head: String,
+    start: chrono::DateTime<chrono::Utc>,
+    _kv_dir: tempfile::TempDir,
+}
+
+async fn indexed_blog() -> IndexedBlog {
+    let (blog_service, teacher, _kv_dir) = blog_with_author().await;
+    let head = AuthService::new(blog_service.db.clone(), test_config())
+        .create_user_with_google("head@example.com".to_string(), "g-head".to_string(), None)
+        .await
+        .unwrap()
+        .id;
+
+    let start = chrono::Utc::now() - chrono::Duration::days(10);
+    for (day, title, tag, author) in [
+        (0, "Athletics", "Sports Day", &teacher),
+        (1, "Choir", "Music", &teacher),
+        (2, "Football", "sports-day", &head),
+        (3, "Band", "Music", &head),
+        (4, "Swimming", "Sports Day", &teacher),
+    ] {
+        let mut request = blog_post_request(title, None);
+        request.tags = vec![tag.to_string()];
+        request.publish_at = Some(start + chrono::Duration::days(day));
+        blog_service.create_post(request, author.clone()).await.unwrap();
+    }
+    blog_service
+        .create_post(blog_post_request("Unfinished", Some("draft")), teacher.clone())
+        .await
+        .unwrap();
+    IndexedBlog { blog_service, teacher, head, start, _kv_dir }
+}
+
+fn index_titles(posts: &[edufy::kv::BlogIndexEntry]) -> Vec<String> {
+    posts.iter().map(|post| post.title.clone()).collect()
+}
+
+#[tokio::test]
+async fn test_blog_index_pages_newest_first_with_links() {
+    use edufy::models::BlogIndexQuery;
+
+    let IndexedBlog { blog_service, _kv_dir, .. } = indexed_blog().await;
+
+    let query = BlogIndexQuery {
+        per_page: Some(2),
+        ..Default::default()
+    };
+    let page = blog_service.query_posts(&query, false, "/api/blog/index").await.unwrap();
+    assert_eq!(index_titles(&page.posts), vec!["Swimming", "Band"]);
+    assert_eq!((page.total, page.total_pages), (5, 3));
+    assert_eq!(page.next.as_deref(), Some("/api/blog/index?page=2&per_page=2"));
+    assert!(page.prev.is_none());
+
+    let query = BlogIndexQuery {
+        page: Some(3),
+        per_page: Some(2),
+        ..Default::default()
+    };
+    let page = blog_service.query_posts(&query, false, "/api/blog/index").await.unwrap();
+    assert_eq!(index_titles(&page.posts), vec!["Athletics"]);
+    assert!(page.next.is_none());
+    assert_eq!(page.prev.as_deref(), Some("/api/blog/index?page=2&per_page=2"));
+}
+
+#[tokio::test]
+async fn test_blog_index_page_far_past_the_end_is_empty() {
+    use edufy::models::BlogIndexQuery;
+
+    let IndexedBlog { blog_service, _kv_dir, .. } = indexed_blog().await;
+
+    // Rather than an overflow of the offset
+    let query = BlogIndexQuery {
+        page: Some(u32::MAX),
+        per_page: Some(100),
+        ..Default::default()
+    };
+    let page = blog_service.query_posts(&query, false, "/api/blog/index").await.unwrap();
+    assert!(page.posts.is_empty());
+    assert_eq!(page.total, 5);
+    assert!(page.next.is_none());
+    assert_eq!(page.prev.as_deref(), Some("/api/blog/index?page=1&per_page=100"));
+}
+
+#[tokio::test]
+async fn test_blog_index_matches_tags_by_slug() {
+    use edufy::models::BlogIndexQuery;
+
+    let IndexedBlog { blog_service, _kv_dir, .. } = indexed_blog().await;
+
+    let query = BlogIndexQuery {
+        tag: Some("Sports Day".to_string()),
+        sort: Some("oldest".to_string()),
+        ..Default::default()
+    };
+    let page = blog_service.query_posts(&query, false, "/api/blog/index").await.unwrap();
+    assert_eq!(index_titles(&page.posts), vec!["Athletics", "Football", "Swimming"]);
+}
+
+#[tokio::test]
+async fn test_blog_index_narrows_by_author_and_date() {
+    use edufy::models::BlogIndexQuery;
+
+    let IndexedBlog { blog_service, teacher, start, _kv_dir, .. } = indexed_blog().await;
+
+    let query = BlogIndexQuery {
+        tag: Some("sports-day".to_string()),
+        author: Some(teacher),
+        from: Some(start + chrono::Duration::hours(12)),
+        ..Default::default()
+    };
+    let page = blog_service.query_posts(&query, false, "/api/blog/index").await.unwrap();
+    assert_eq!(index_titles(&page.posts), vec!["Swimming"]);
+
+    let query = BlogIndexQuery {
+        to: Some(start + chrono::Duration::days(2)),
+        sort: Some("title".to_string()),
+        ..Default::default()
+    };
+    let page = blog_service.query_posts(&query, false, "/api/blog/index").await.unwrap();
+    assert_eq!(index_titles(&page.posts), vec!["Athletics", "Choir"]);
+}
+
+#[tokio::test]
+async fn test_admin_listing_shares_the_index_and_sees_drafts() {
+    use edufy::models::BlogIndexQuery;
+
+    let IndexedBlog { blog_service, _kv_dir, .. } = indexed_blog().await;
+
+    let query = BlogIndexQuery {
+        status: Some("draft".to_string()),
+        ..Default::default()
+    };
+    let page = blog_service.query_posts(&query, true, "/api/admin/posts").await.unwrap();
+    assert_eq!(index_titles(&page.posts), vec!["Unfinished"]);
+    let page = blog_service
+        .query_posts(&BlogIndexQuery::default(), true, "/api/admin/posts")
+        .await
+        .unwrap();
+    assert_eq!(page.total, 6);
+}
+
+#[tokio::test]
+async fn test_blog_index_rejects_unknown_sorts_and_statuses() {
+    use edufy::models::BlogIndexQuery;
+
+    let IndexedBlog { blog_service, _kv_dir, .. } = indexed_blog().await;
+
+    let query = BlogIndexQuery {
+        sort: Some("random".to_string()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        blog_service.query_posts(&query, false, "/api/blog/index").await,
+        Err(AppError::Validation(_))
+    ));
+    let query = BlogIndexQuery {
+        status: Some("bogus".to_string()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        blog_service.query_posts(&query, true, "/api/admin/posts").await,
+        Err(AppError::Validation(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_blog_index_author_ids_are_backfilled_from_posts() {
+    use edufy::models::BlogIndexQuery;
+
+    let IndexedBlog { blog_service, head, _kv_dir, .. } = indexed_blog().await;
+
+    // An entry written before author_id was indexed
+    let index_json = blog_service.kv.get("blog:index").await.unwrap().unwrap();
+    let mut index: Vec<serde_json::Value> = serde_json::from_str(&index_json).unwrap();
+    let band = index.iter_mut().find(|entry| entry["slug"] == "band").unwrap();
+    band["author_id"] = serde_json::json!("");
+    blog_service
+        .kv
+        .put("blog:index", &serde_json::to_string(&index).unwrap())
+        .await
+        .unwrap();
+    let by_head = BlogIndexQuery {
+        author: Some(head.clone()),
+        ..Default::default()
+    };
+    let page = blog_service.query_posts(&by_head, false, "/api/blog/index").await.unwrap();
+    assert_eq!(index_titles(&page.posts), vec!["Football"]);
+
+    assert_eq!(blog_service.kv.backfill_blog_index_authors().await.unwrap(), 1);
+    assert_eq!(blog_service.kv.backfill_blog_index_authors().await.unwrap(), 0);
+    let page = blog_service.query_posts(&by_head, false, "/api/blog/index").await.unwrap();
+    assert_eq!(index_titles(&page.posts), vec!["Band", "Football"]);
+}
+
+
+/// Three live posts and two drafts whose tags overlap in different spellings
+async fn tagged_blog() -> (BlogService, String, tempfile::TempDir) {
+    let (blog_service, author, kv_dir) = blog_with_author().await;
+    for (title, tags, status) in [
+        ("Athletics", vec!["Sports Day", "Events"], None),
+        ("Football", vec!["sports-day"], None),
+        ("Concert", vec!["Music", "Events"], None),
+        ("Rehearsal", vec!["Music"], Some("draft")),
+        ("Auditions", vec!["Choir"], Some("draft")),
+    ] {
+        let mut request = blog_post_request(title, status);
+        request.tags = tags.into_iter().map(str::to_string).collect();
+        blog_service.create_post(request, author.clone()).await.unwrap();
+    }
+    (blog_service, author, kv_dir)
+}
+
+fn tag_request(name: &str, description: Option<&str>) -> edufy::models::UpdateTagRequest {
+    edufy::models::UpdateTagRequest {
+        name: name.to_string(),
+        description: description.map(str::to_string),
+    }
+}
+
+#[tokio::test]
+async fn test_public_tag_counts_only_see_live_posts() {
+    let (blog_service, _, _kv_dir) = tagged_blog().await;
+
+    // Spellings of a tag share one slug, named as first saved, and tags
+    // without live posts are hidden
+    let public: Vec<_> = blog_service
+        .list_tags(false)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|tag| (tag.slug, tag.name, tag.post_count))
+        .collect();
+    assert_eq!(
+        public,
+        vec![
+            ("events".to_string(), "Events".to_string(), 2),
+            ("music".to_string(), "Music".to_string(), 1),
+            ("sports-day".to_string(), "Sports Day".to_string(), 2),
+        ]
+    );
+    assert!(matches!(
+        blog_service.get_tag("choir", false).await,
+        Err(AppError::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_admin_tag_counts_include_drafts() {
+    let (blog_service, _, _kv_dir) = tagged_blog().await;
+
+    let admin = blog_service.list_tags(true).await.unwrap();
+    assert_eq!(admin.iter().find(|tag| tag.slug == "music").unwrap().post_count, 2);
+    assert_eq!(admin.iter().find(|tag| tag.slug == "choir").unwrap().post_count, 1);
+}
+
+#[tokio::test]
+async fn test_tag_pages_page_through_the_tags_live_posts() {
+    use edufy::models::BlogIndexQuery;
+
+    let (blog_service, _, _kv_dir) = tagged_blog().await;
+
+    let query = BlogIndexQuery {
+        per_page: Some(1),
+        ..Default::default()
+    };
+    let tag_page = blog_service.tag_page("sports-day", query).await.unwrap();
+    assert_eq!(tag_page.tag.name, "Sports Day");
+    assert_eq!((tag_page.page.total, tag_page.page.posts.len()), (2, 1));
+    assert!(tag_page
+        .page
+        .next
+        .unwrap()
+        .starts_with("/api/blog/tags/sports-day?page=2"));
+}
+
+#[tokio::test]
+async fn test_renaming_a_tag_rewrites_every_spelling_on_posts() {
+    let (blog_service, author, _kv_dir) = tagged_blog().await;
+
+    let renamed = blog_service
+        .update_tag("sports-day", tag_request("Athletics", Some("Track, field and team games")), author.clone())
+        .await
+        .unwrap();
+    assert_eq!((renamed.slug.as_str(), renamed.post_count), ("athletics", 2));
+    assert_eq!(renamed.description.as_deref(), Some("Track, field and team games"));
+    let football = blog_service.get_post("football").await.unwrap().unwrap();
+    assert_eq!(football.tags, vec!["Athletics"]);
+    assert!(matches!(
+        blog_service.get_tag("sports-day", true).await,
+        Err(AppError::NotFound(_))
+    ));
+
+    let logs = AuditService::new(blog_service.db.clone())
+        .get_user_audit_logs(
+            &author,
+            chrono::Utc::now() - chrono::Duration::hours(1),
+            chrono::Utc::now() + chrono::Duration::hours(1),
+        )
+        .await
+        .unwrap();
+    assert!(logs.iter().any(|log| log.action == "update_tag"));
+}
+
+#[tokio::test]
+async fn test_renaming_a_tag_to_another_tags_name_needs_a_merge() {
+    let (blog_service, author, _kv_dir) = tagged_blog().await;
+
+    let taken = blog_service
+        .update_tag("sports-day", tag_request("Events", None), author)
+        .await;
+    assert!(matches!(taken, Err(AppError::Conflict(_))));
+    let football = blog_service.get_post("football").await.unwrap().unwrap();
+    assert_eq!(football.tags, vec!["sports-day"]);
+}
+
+#[tokio::test]
+async fn test_merging_tags_leaves_one_copy_of_the_target() {
+    let (blog_service, author, _kv_dir) = tagged_blog().await;
+
+    let merged = blog_service
+        .merge_tag("events", "sports-day", author.clone())
+        .await
+        .unwrap();
+    assert_eq!((merged.slug.as_str(), merged.post_count), ("sports-day", 3));
+    let athletics = blog_service.get_post("athletics").await.unwrap().unwrap();
+    assert_eq!(athletics.tags, vec!["Sports Day"]);
+    let concert = blog_service.get_post("concert").await.unwrap().unwrap();
+    assert_eq!(concert.tags, vec!["Music", "Sports Day"]);
+    assert!(matches!(
+        blog_service.get_tag("events", true).await,
+        Err(AppError::NotFound(_))
+    ));
+
+    let logs = AuditService::new(blog_service.db.clone())
+        .get_user_audit_logs(
+            &author,
+            chrono::Utc::now() - chrono::Duration::hours(1),
+            chrono::Utc::now() + chrono::Duration::hours(1),
+        )
+        .await
+        .unwrap();
+    let merge = logs.iter().find(|log| log.action == "merge_tags").unwrap();
+    assert_eq!(
+        merge.details,
+        Some(serde_json::json!({ "from": "events", "into": "sports-day", "posts_changed": 2 }))
+    );
+}
+
+
+#[tokio::test]
+async fn test_tags_without_letters_or_digits_are_rejected() {
+    let (blog_service, author, _kv_dir) = blog_with_author().await;
+
+    // Punctuation-only tags would otherwise share the fallback slug with "Post"
+    let mut request = blog_post_request("Prize Giving", None);
+    request.tags = vec!["Post".to_string(), "!!!".to_string()];
+    assert!(matches!(
+        blog_service.create_post(request, author.clone()).await,
+        Err(AppError::Validation(_))
+    ));
+
+    let mut request = blog_post_request("Prize Giving", None);
+    request.tags = vec!["Post".to_string()];
+    blog_service.create_post(request, author.clone()).await.unwrap();
+    let renamed = blog_service
+        .update_tag("post", tag_request("— ? —", None), author.clone())
+        .await;
+    assert!(matches!(renamed, Err(AppError::Validation(_))));
+
+    let tags = blog_service.list_tags(true).await.unwrap();
+    assert_eq!(tags.len(), 1);
+    assert_eq!((tags[0].slug.as_str(), tags[0].name.as_str()), ("post", "Post"));
+}
+
+
+/// A router over a fresh database and KV store, with a bearer token for a
+/// teacher (`posts:write` without `posts:publish`)
+struct TeacherBlogApp {
+    blog_service: BlogService,
+    app: axum::Router,
+    token: String,
+    teacher_id: String,
+    _kv_dir: tempfile::TempDir,
+}
+
+async fn teacher_blog_app() -> TeacherBlogApp {
+    let db = setup_test_db().await;
+    let auth_service = AuthService::new(db.clone(), test_config());
+    let mut teacher = auth_service
+        .create_user_with_google("author@example.com".to_string(), "g-author".to_string(), None)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE users SET role = 'teacher' WHERE id = ?")
+        .bind(&teacher.id)
+        .execute(&db)
+        .await
+        .unwrap();
+    teacher.role = "teacher".to_string();
+    let teacher_id = teacher.id.clone();
+    let (login, _) = auth_service.create_login_session(teacher).await.unwrap();
+
+    let temp_dir = tempdir().unwrap();
+    let kv = edufy::kv::KvStore::new(temp_dir.path().to_str().unwrap()).unwrap();
+    TeacherBlogApp {
+        blog_service: BlogService::new(kv.clone(), db.clone()),
+        app: edufy::handlers::create_router(edufy::AppState::new(db, test_config(), kv).unwrap()),
+        token: login.token,
+        teacher_id,
+        _kv_dir: temp_dir,
+    }
+}
+
+async fn send_json(
+    app: &axum::Router,
+    method: &str,
+    uri: &str,
+    token: &str,
+    body: serde_json::Value,
+) -> axum::http::StatusCode {
+    use tower::ServiceExt;
+
+    let request = axum::http::Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("authorization", format!("Bearer {}", token))
+        .header("content-type", "application/json")
+        .body(axum::body::Body::from(body.to_string()))
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn test_admin_posts_route_is_paged() {
+    use tower::ServiceExt;
+
+    let TeacherBlogApp { blog_service, app, token, teacher_id, _kv_dir } = teacher_blog_app().await;
+    for title in ["First", "Second", "Third"] {
+        blog_service
+            .create_post(blog_post_request(title, Some("draft")), teacher_id.clone())
+            .await
+            .unwrap();
+    }
+
+    let request = axum::http::Request::get("/api/admin/posts?page=2&per_page=2")
+        .header("authorization", format!("Bearer {}", token))
+        .body(axum::body::Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), axum::http::StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let page: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(page["posts"].as_array().unwrap().len(), 1);
+    assert_eq!(page["total"], 3);
+    assert_eq!(page["prev"], "/api/admin/posts?page=1&per_page=2");
+}
+
+#[tokio::test]
+async fn test_tag_rename_and_merge_routes_need_publish_permission() {
+    let TeacherBlogApp { blog_service, app, token, teacher_id, _kv_dir } = teacher_blog_app().await;
+    let mut request = blog_post_request("Concert", None);
+    request.tags = vec!["Music".to_string(), "Events".to_string()];
+    blog_service.create_post(request, teacher_id).await.unwrap();
+
+    let rename = serde_json::json!({ "name": "Concerts" });
+    let status = send_json(&app, "PUT", "/api/admin/tags/music", &token, rename).await;
+    assert_eq!(status, axum::http::StatusCode::FORBIDDEN);
+    let merge = serde_json::json!({ "into": "music" });
+    let status = send_json(&app, "POST", "/api/admin/tags/events/merge", &token, merge).await;
+    assert_eq!(status, axum::http::StatusCode::FORBIDDEN);
+    let concert = blog_service.get_post("concert").await.unwrap().unwrap();
+    assert_eq!(concert.tags, vec!["Music", "Events"]);
+}
+
+#[tokio::test]
+async fn test_teacher_can_delete_their_own_draft() {
+    let TeacherBlogApp { blog_service, app, token, teacher_id, _kv_dir } = teacher_blog_app().await;
+    blog_service
+        .create_post(blog_post_request("Own Draft", Some("draft")), teacher_id)
+        .await
+        .unwrap();
+
+    let uri = "/api/admin/posts/own-draft";
+    let status = send_json(&app, "DELETE", uri, &token, serde_json::Value::Null).await;
+    assert_eq!(status, axum::http::StatusCode::NO_CONTENT);
+    assert!(blog_service.get_post("own-draft").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_deleting_a_live_post_needs_publish_permission() {
+    let TeacherBlogApp { blog_service, app, token, teacher_id, _kv_dir } = teacher_blog_app().await;
+    blog_service
+        .create_post(blog_post_request("Own Live Post", None), teacher_id)
+        .await
+        .unwrap();
+
+    let uri = "/api/admin/posts/own-live-post";
+    let status = send_json(&app, "DELETE", uri, &token, serde_json::Value::Null).await;
+    assert_eq!(status, axum::http::StatusCode::FORBIDDEN);
+    assert!(blog_service.get_post("own-live-post").await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_deleting_another_authors_post_needs_publish_permission() {
+    let TeacherBlogApp { blog_service, app, token, _kv_dir, .. } = teacher_blog_app().await;
+    let colleague = AuthService::new(blog_service.db.clone(), test_config())
+        .create_user_with_google("colleague@example.com".to_string(), "g-colleague".to_string(), None)
+        .await
+        .unwrap();
+    blog_service
+        .create_post(blog_post_request("Colleague Draft", Some("draft")), colleague.id)
+        .await
+        .unwrap();
+
+    let uri = "/api/admin/posts/colleague-draft";
+    let status = send_json(&app, "DELETE", uri, &token, serde_json::Value::Null).await;
+    assert_eq!(status, axum::http::StatusCode::FORBIDDEN);
+    assert!(blog_service.get_post("colleague-draft").await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_model_create_route_keeps_drafts_unpublished() {
+    let TeacherBlogApp { blog_service, app, token, _kv_dir, .. } = teacher_blog_app().await;
+    let draft = serde_json::json!({
+        "title": "Model Draft",
+        "body_html": "<p>Body</p>",
+        "tags": [],
+        "visibility": "public",
+        "status": "draft",
+        "attachments": []
+    });
+
+    // A draft needs no posts:publish, and the model route stores it as one
+    let status = send_json(&app, "POST", "/api/admin/posts/model", &token, draft.clone()).await;
+    assert_eq!(status, axum::http::StatusCode::OK);
+    let post = blog_service.get_post("model-draft").await.unwrap().unwrap();
+    assert_eq!(post.status, "draft");
+    assert!(!post.is_live(chrono::Utc::now()));
+    assert!(blog_service.get_public_post("model-draft").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_model_create_route_needs_publish_permission_to_go_live() {
+    let TeacherBlogApp { blog_service, app, token, _kv_dir, .. } = teacher_blog_app().await;
+    let live = serde_json::json!({
+        "title": "Model Live",
+        "body_html": "<p>Body</p>",
+        "tags": [],
+        "visibility": "public",
+        "attachments": []
+    });
+
+    let status = send_json(&app, "POST", "/api/admin/posts/model", &token, live).await;
+    assert_eq!(status, axum::http::StatusCode::FORBIDDEN);
+    assert!(blog_service.get_post("model-live").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_create_route_needs_publish_permission_to_schedule() {
+    let TeacherBlogApp { blog_service, app, token, _kv_dir, .. } = teacher_blog_app().await;
+    let publish_at = chrono::Utc::now() + chrono::Duration::hours(1);
+    let scheduled = serde_json::json!({
+        "title": "Open Day",
+        "body_html": "<p>Body</p>",
+        "tags": [],
+        "visibility": "public",
+        "status": "scheduled",
+        "publish_at": publish_at,
+        "attachments": []
+    });
+
+    let status = send_json(&app, "POST", "/api/admin/posts", &token, scheduled).await;
+    assert_eq!(status, axum::http::StatusCode::FORBIDDEN);
+    assert!(blog_service.get_post("open-day").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_model_update_route_cannot_take_a_live_post_down_without_publish() {
+    let TeacherBlogApp { blog_service, app, token, teacher_id, _kv_dir } = teacher_blog_app().await;
+    blog_service
+        .create_post(blog_post_request("Live Post", None), teacher_id)
+        .await
+        .unwrap();
+
+    let draft = serde_json::json!({
+        "title": "Live Post",
+        "body_html": "<p>Changed</p>",
+        "tags": [],
+        "visibility": "public",
+        "status": "draft",
+        "attachments": []
+    });
+    let status = send_json(&app, "PUT", "/api/admin/posts/model/live-post", &token, draft).await;
+    assert_eq!(status, axum::http::StatusCode::FORBIDDEN);
+    let post = blog_service.get_post("live-post").await.unwrap().unwrap();
+    assert_eq!(post.status, "published");
+    assert_eq!(post.body_html, "<p>Body</p>");
+}
+
+#[tokio::test]
+async fn test_model_update_route_applies_the_requested_status() {
+    let TeacherBlogApp { blog_service, app, token, teacher_id, _kv_dir } = teacher_blog_app().await;
+    blog_service
+        .create_post(blog_post_request("Notes", Some("draft")), teacher_id)
+        .await
+        .unwrap();
+
+    let review = serde_json::json!({
+        "title": "Notes",
+        "body_html": "<p>Ready</p>",
+        "tags": [],
+        "visibility": "public",
+        "status": "in_review",
+        "attachments": []
+    });
+    let status = send_json(&app, "PUT", "/api/admin/posts/model/notes", &token, review).await;
+    assert_eq!(status, axum::http::StatusCode::OK);
+    assert_eq!(blog_service.get_post("notes").await.unwrap().unwrap().status, "in_review");
+}
+
+#[tokio::test]
+async fn test_update_route_cannot_take_a_live_post_down_without_publish() {
+    let TeacherBlogApp { blog_service, app, token, teacher_id, _kv_dir } = teacher_blog_app().await;
+    blog_service
+        .create_post(blog_post_request("Live Post", None), teacher_id)
+        .await
+        .unwrap();
+
+    for status in ["draft", "in_review", "archived"] {
+        let body = serde_json::json!({
+            "title": "Live Post",
+            "body_html": "<p>Body</p>",
+            "tags": [],
+            "visibility": "public",
+            "status": status,
+            "attachments": []
+        });
+        let response = send_json(&app, "PUT", "/api/admin/posts/live-post", &token, body).await;
+        assert_eq!(response, axum::http::StatusCode::FORBIDDEN);
+    }
+    let private = serde_json::json!({
+        "title": "Live Post",
+        "body_html": "<p>Body</p>",
+        "tags": [],
+        "visibility": "private",
+        "attachments": []
+    });
+    let response = send_json(&app, "PUT", "/api/admin/posts/live-post", &token, private).await;
+    assert_eq!(response, axum::http::StatusCode::FORBIDDEN);
+    assert!(blog_service.get_public_post("live-post").await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_update_route_lets_writers_edit_their_drafts() {
+    let TeacherBlogApp { blog_service, app, token, teacher_id, _kv_dir } = teacher_blog_app().await;
+    blog_service
+        .create_post(blog_post_request("Notes", Some("draft")), teacher_id)
+        .await
+        .unwrap();
+
+    let body = serde_json::json!({
+        "title": "Notes",
+        "body_html": "<p>More</p>",
+        "tags": [],
+        "visibility": "public",
+        "attachments": []
+    });
+    let response = send_json(&app, "PUT", "/api/admin/posts/notes", &token, body).await;
+    assert_eq!(response, axum::http::StatusCode::OK);
+    let post = blog_service.get_post("notes").await.unwrap().unwrap();
+    assert_eq!((post.status.as_str(), post.body_html.as_str()), ("draft", "<p>More</p>"));
+}